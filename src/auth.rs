@@ -0,0 +1,106 @@
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+
+use crate::error::BusError;
+
+tokio::task_local! {
+    static PRINCIPAL: Principal;
+}
+
+/// The identity on whose behalf commands are dispatched, with the
+/// permissions it has been granted.
+///
+/// Permissions are opaque strings, such as `"orders:cancel"`, compared for
+/// equality. How they are granted, e.g. from the roles in a verified token,
+/// is up to the application.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Principal {
+    id: String,
+    permissions: BTreeSet<String>,
+}
+
+impl Principal {
+    /// Creates a principal with no permissions.
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            permissions: BTreeSet::new(),
+        }
+    }
+
+    /// Grants the principal a permission.
+    pub fn with_permission(mut self, permission: impl Into<String>) -> Self {
+        self.permissions.insert(permission.into());
+        self
+    }
+
+    /// The principal's identifier, such as a user id.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Whether the principal has been granted the permission.
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.permissions.contains(permission)
+    }
+}
+
+/// Runs `fut` on behalf of a principal.
+///
+/// Commands dispatched by the application within `fut` are authorized
+/// against the principal: the permissions their handler requires (see
+/// [`CommandHandler::required_permissions`]) must all have been granted,
+/// or the dispatch fails with [`BusError::Unauthorized`] before a unit of
+/// work is created.
+///
+/// Typically an HTTP handler wraps its dispatch with the authenticated
+/// caller:
+///
+/// ```rust,ignore
+/// let principal = Principal::new(claims.sub).with_permission("orders:cancel");
+/// as_principal(principal, bus.dispatch(cmd)).await?;
+/// ```
+///
+/// Commands received from the broker are not authorized again: they were
+/// authorized when they were submitted, or were issued by the application's
+/// own policies.
+///
+/// [`CommandHandler::required_permissions`]: crate::handler::CommandHandler::required_permissions
+pub async fn as_principal<F: Future>(principal: Principal, fut: F) -> F::Output {
+    PRINCIPAL.scope(principal, fut).await
+}
+
+/// The principal of the current task, if it runs within [`as_principal`].
+pub fn current_principal() -> Option<Principal> {
+    PRINCIPAL.try_with(Principal::clone).ok()
+}
+
+/// Fails with [`BusError::Unauthorized`] unless the current task's
+/// principal has been granted every one of the `required` permissions.
+///
+/// Work requiring no permissions is always authorized, even without a
+/// principal.
+pub fn check_permissions(required: &[String]) -> Result<()> {
+    if required.is_empty() {
+        return Ok(());
+    }
+    let principal = current_principal();
+    let missing = required
+        .iter()
+        .filter(|permission| {
+            principal
+                .as_ref()
+                .is_none_or(|principal| !principal.has_permission(permission))
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    Err(BusError::Unauthorized {
+        principal: principal.map(|principal| principal.id),
+        missing,
+    }
+    .into())
+}
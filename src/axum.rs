@@ -0,0 +1,183 @@
+use std::{convert::Infallible, ops::Deref};
+
+use ::axum::{
+    Json,
+    extract::{FromRef, FromRequestParts},
+    http::{StatusCode, request::Parts},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+
+use crate::{
+    bus::MessageBus,
+    driver::MessageBusDriver,
+    error::{BusError, FieldErrors},
+};
+
+/// A [`MessageBus`] shared with axum handlers as application state.
+///
+/// Together with [`BusRejection`], which turns the errors of a dispatch
+/// into HTTP responses, it lets handlers drive the bus with `?` and no
+/// error-mapping glue:
+///
+/// ```rust,ignore
+/// async fn place_order(
+///     bus: BusState<MyDriver>,
+///     Json(cmd): Json<PlaceOrder>,
+/// ) -> Result<Json<Option<Uuid>>, BusRejection> {
+///     let res = bus.dispatch(cmd).await?;
+///     Ok(Json(res.result))
+/// }
+///
+/// let app = Router::new()
+///     .route("/orders", post(place_order))
+///     .with_state(BusState::from(MessageBus::from(&driver)));
+/// ```
+///
+/// GraphQL servers can share the same state and map errors through
+/// [`BusError::category`], e.g. a mutation resolver with `async-graphql`:
+///
+/// ```rust,ignore
+/// #[Object]
+/// impl Mutation {
+///     async fn place_order(&self, ctx: &Context<'_>, cmd: PlaceOrder) -> Result<bool> {
+///         let bus = ctx.data::<BusState<MyDriver>>()?;
+///         let res = bus.dispatch(cmd).await.map_err(|e| {
+///             match e.downcast_ref::<BusError>() {
+///                 Some(err) => Error::new(err.to_string())
+///                     .extend_with(|_, ext| ext.set("code", err.category())),
+///                 None => Error::new("internal error"),
+///             }
+///         })?;
+///         Ok(res.changed)
+///     }
+/// }
+/// ```
+///
+/// `BusState` dereferences to the bus, and can be extracted directly in a
+/// handler's arguments from any router state it can be derived from with
+/// [`FromRef`], including a `BusState` itself. Requires the `axum` feature.
+pub struct BusState<D: MessageBusDriver>(pub MessageBus<D>);
+
+impl<D: MessageBusDriver> Clone for BusState<D> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<D: MessageBusDriver> From<MessageBus<D>> for BusState<D> {
+    fn from(bus: MessageBus<D>) -> Self {
+        Self(bus)
+    }
+}
+
+impl<D: MessageBusDriver> Deref for BusState<D> {
+    type Target = MessageBus<D>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<D, S> FromRequestParts<S> for BusState<D>
+where
+    D: MessageBusDriver,
+    S: Send + Sync,
+    BusState<D>: FromRef<S>,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(_parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self::from_ref(state))
+    }
+}
+
+impl BusError {
+    /// The HTTP status that best describes this error to a client.
+    ///
+    /// Invalid input is `422 Unprocessable Entity`, sealed aggregates are
+    /// `409 Conflict`, and missing permissions are `401 Unauthorized` or
+    /// `403 Forbidden`, depending on whether a principal was present.
+    /// Passed deadlines are `504 Gateway Timeout`, and retryable
+    /// infrastructure failures are `503 Service Unavailable`. Everything
+    /// else is `500 Internal Server Error`.
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            BusError::Validation { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            BusError::AggregateSealed { .. } => StatusCode::CONFLICT,
+            BusError::Unauthorized {
+                principal: None, ..
+            } => StatusCode::UNAUTHORIZED,
+            BusError::Unauthorized { .. } => StatusCode::FORBIDDEN,
+            BusError::DeadlineExceeded { .. } => StatusCode::GATEWAY_TIMEOUT,
+            BusError::ReadUnavailable { .. }
+            | BusError::PolicyContextUnavailable { .. }
+            | BusError::CreateFailed {
+                retryable: true, ..
+            } => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// Responds with the error's [status](BusError::status_code) and a JSON
+/// body holding its [category](BusError::category) and message, plus the
+/// invalid fields of a [`BusError::Validation`].
+///
+/// The error's sources are not included, since they may reveal internal
+/// details.
+impl IntoResponse for BusError {
+    fn into_response(self) -> Response {
+        let fields = match &self {
+            BusError::Validation { errors } => Some(errors),
+            _ => None,
+        };
+        let body = ErrorBody {
+            error: self.category(),
+            message: self.to_string(),
+            fields,
+        };
+        (self.status_code(), Json(body)).into_response()
+    }
+}
+
+/// The JSON body of an error response.
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    error: &'static str,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fields: Option<&'a FieldErrors>,
+}
+
+/// An error returned from an axum handler that drives the message bus.
+///
+/// Any error converts into a `BusRejection`, so handlers can use `?` on the
+/// results of [`MessageBus::dispatch`] and friends. A [`BusError`] responds
+/// as described on its [`IntoResponse`] implementation. Any other error is
+/// logged and answered with a bare `500 Internal Server Error`.
+#[derive(Debug)]
+pub struct BusRejection(pub anyhow::Error);
+
+impl<E: Into<anyhow::Error>> From<E> for BusRejection {
+    fn from(err: E) -> Self {
+        Self(err.into())
+    }
+}
+
+impl IntoResponse for BusRejection {
+    fn into_response(self) -> Response {
+        match self.0.downcast::<BusError>() {
+            Ok(err) => err.into_response(),
+            Err(err) => {
+                tracing::error!("unhandled error in request: {err:#}");
+                let body = ErrorBody {
+                    error: "internal",
+                    message: "internal server error".to_owned(),
+                    fields: None,
+                };
+                (StatusCode::INTERNAL_SERVER_ERROR, Json(body)).into_response()
+            }
+        }
+    }
+}
@@ -1,5 +1,17 @@
+use std::{error::Error, fmt, future, sync::Arc, time::Duration};
+
 use anyhow::Result;
-use futures::stream::Stream;
+use futures::{FutureExt, StreamExt, pin_mut, stream::Stream};
+
+use crate::{error::ErrorDetails, uow::UnitOfWork};
+
+mod connection;
+
+pub use connection::*;
+
+/// Characters treated as wildcards by common subject-based brokers
+/// (`*` and `>` for NATS, `+` and `#` for MQTT).
+const WILDCARDS: [char; 4] = ['*', '>', '+', '#'];
 
 /// A trait for implementing message transport across the message bus.
 ///
@@ -15,7 +27,7 @@ pub trait MessageBroker: Clone + Send + Sync {
     /// The message type sent and received over the broker.
     ///
     /// This message will typically represent a `Command`, `Projection`, or
-    /// `Event` wrapped in an envelope (e.g. `DriverMessage<D>`). The broker
+    /// `Event` wrapped in an envelope (e.g. `DriverEnvelope<D>`). The broker
     /// does not inspect the message, but passes it through to the message bus.
     type Message: Send;
 
@@ -24,7 +36,7 @@ pub trait MessageBroker: Clone + Send + Sync {
     /// This identifier is used to acknowledge (`ack`) or reject (`nack`)
     /// the message after processing. The identifier must be unique per
     /// message and persistable across retries if necessary.
-    type Id: Send;
+    type Id: Clone + Send;
 
     /// A stream of incoming messages to be processed by the message bus.
     ///
@@ -36,6 +48,44 @@ pub trait MessageBroker: Clone + Send + Sync {
     /// consumption loop of the message bus.
     fn receiver(&self) -> impl Stream<Item = (Self::Id, Self::Message)> + Send;
 
+    /// Receive up to `max` messages at once.
+    ///
+    /// Used by the message bus instead of [`receiver`](Self::receiver) when
+    /// [`BusConfig::receive_batching`](crate::config::BusConfig::receive_batching)
+    /// is configured. Brokers that deliver messages in batches (e.g. a Kafka
+    /// `poll` or a Redis `XREAD COUNT`) should override this to fetch the
+    /// whole batch in one round trip. An empty batch means no messages are
+    /// currently available.
+    ///
+    /// The default implementation opens the receiver, waits for its first
+    /// message, and adds any further messages that are immediately ready.
+    /// The receiver is dropped after every batch, so brokers whose receiver
+    /// holds a subscription or prefetches messages must override this to
+    /// read from a long-lived consumer instead.
+    fn receive_batch(
+        &self,
+        max: usize,
+    ) -> impl Future<Output = Result<Vec<(Self::Id, Self::Message)>>> + Send {
+        async move {
+            let mut batch = Vec::new();
+            if max == 0 {
+                return Ok(batch);
+            }
+            let stream = self.receiver();
+            pin_mut!(stream);
+            if let Some(message) = stream.next().await {
+                batch.push(message);
+            }
+            while batch.len() < max {
+                match stream.next().now_or_never() {
+                    Some(Some(message)) => batch.push(message),
+                    _ => break,
+                }
+            }
+            Ok(batch)
+        }
+    }
+
     /// Publish a single message to be processed asynchronously.
     ///
     /// The message will be queued and delivered to the receiver at some point
@@ -43,6 +93,43 @@ pub trait MessageBroker: Clone + Send + Sync {
     /// only for it to be successfully published.
     fn publish(&self, message: Self::Message) -> impl Future<Output = Result<()>> + Send;
 
+    /// Publish a single message to a specific queue, shard, or partition.
+    ///
+    /// Used by the message bus instead of [`publish`](Self::publish) for
+    /// commands that the driver routes with
+    /// [`MessageBusDriver::route`](crate::driver::MessageBusDriver::route).
+    /// Sharded brokers should override this to select the target queue or
+    /// partition from the route key, e.g. keeping each aggregate's commands
+    /// on the shard that holds its data.
+    ///
+    /// The default implementation ignores the route and calls `publish`.
+    fn publish_to(
+        &self,
+        _route: &RouteKey,
+        message: Self::Message,
+    ) -> impl Future<Output = Result<()>> + Send {
+        self.publish(message)
+    }
+
+    /// Publish a message that may have been published before to the given
+    /// route.
+    ///
+    /// Used instead of [`publish_to`](Self::publish_to) for the routed side
+    /// effects of an event whose envelope carries an id, as
+    /// [`publish_idempotent`](Self::publish_idempotent) is for unrouted
+    /// ones. Brokers should override this to skip a message whose id has
+    /// already been published.
+    ///
+    /// The default implementation calls `publish_to` and does not
+    /// deduplicate.
+    fn publish_to_idempotent(
+        &self,
+        route: &RouteKey,
+        message: Self::Message,
+    ) -> impl Future<Output = Result<()>> + Send {
+        self.publish_to(route, message)
+    }
+
     /// Publish a batch of messages to be processed asynchronously.
     ///
     /// All messages will be enqueued and delivered independently. This method
@@ -51,6 +138,100 @@ pub trait MessageBroker: Clone + Send + Sync {
     fn publish_batch(&self, message: Vec<Self::Message>)
     -> impl Future<Output = Result<()>> + Send;
 
+    /// Publish a batch of messages, reporting the outcome of each one.
+    ///
+    /// The returned results are indexed to the input messages. This allows
+    /// the message bus to retry only the messages that failed instead of
+    /// re-publishing (and duplicating) the whole batch. Brokers that can
+    /// partially fail a batch should override this.
+    ///
+    /// The default implementation calls `publish_batch` and applies its
+    /// outcome to every message in the batch.
+    fn publish_batch_detailed(
+        &self,
+        messages: Vec<Self::Message>,
+    ) -> impl Future<Output = Vec<Result<(), PublishError>>> + Send {
+        let len = messages.len();
+        let published = self.publish_batch(messages);
+        async move {
+            match published.await {
+                Ok(()) => (0..len).map(|_| Ok(())).collect(),
+                Err(e) => {
+                    let err = PublishError::new(e);
+                    (0..len).map(|_| Err(err.clone())).collect()
+                }
+            }
+        }
+    }
+
+    /// Whether the broker can publish a batch of messages atomically.
+    ///
+    /// When this returns `true`, the message bus publishes the side effects
+    /// of an event with
+    /// [`publish_transactional`](Self::publish_transactional) instead of
+    /// `publish_idempotent`. Defaults to `false`.
+    fn supports_transactions(&self) -> bool {
+        false
+    }
+
+    /// Publish a batch of messages in a single transaction: either every
+    /// message is published or none is.
+    ///
+    /// Used by the message bus for the side effects of an event when
+    /// [`supports_transactions`](Self::supports_transactions) returns
+    /// `true`, so that a failed publish leaves nothing behind to be
+    /// duplicated when the event is handled again. Brokers with
+    /// transactional producers (e.g. Kafka transactions or AMQP `tx`
+    /// channels) should override both methods.
+    ///
+    /// The default implementation calls `publish_batch`, which is not
+    /// atomic.
+    fn publish_transactional(
+        &self,
+        messages: Vec<Self::Message>,
+    ) -> impl Future<Output = Result<()>> + Send {
+        self.publish_batch(messages)
+    }
+
+    /// Publish a batch of messages that may have been published before in a
+    /// single transaction.
+    ///
+    /// Used instead of [`publish_transactional`](Self::publish_transactional)
+    /// for the side effects of an event whose envelope carries an id. As
+    /// with [`publish_idempotent`](Self::publish_idempotent), each message
+    /// then has an id derived from the event's, and brokers should skip
+    /// messages whose id has already been published, so that an event
+    /// handled again after its transaction committed, but before it was
+    /// acknowledged, does not publish its side effects twice.
+    ///
+    /// The default implementation calls `publish_transactional` and does
+    /// not deduplicate.
+    fn publish_transactional_idempotent(
+        &self,
+        messages: Vec<Self::Message>,
+    ) -> impl Future<Output = Result<()>> + Send {
+        self.publish_transactional(messages)
+    }
+
+    /// Publish a batch of messages that may have been published before.
+    ///
+    /// Used by the message bus for the side effects of an event whose
+    /// envelope carries an id. Each message is then given a deterministic
+    /// [`Envelope::id`](crate::message::Envelope::id) derived from the
+    /// event's id, so if the event is handled again (e.g. after a partial
+    /// publish failure) the same messages are published with the same ids.
+    /// Brokers should override this to skip messages whose id has already
+    /// been published, making policy re-execution free of duplicates.
+    ///
+    /// The default implementation calls `publish_batch_detailed` and does
+    /// not deduplicate.
+    fn publish_idempotent(
+        &self,
+        messages: Vec<Self::Message>,
+    ) -> impl Future<Output = Vec<Result<(), PublishError>>> + Send {
+        self.publish_batch_detailed(messages)
+    }
+
     /// Acknowledge successful processing of a previously received message.
     ///
     /// This signals to the broker that the message has been handled and should
@@ -58,10 +239,261 @@ pub trait MessageBroker: Clone + Send + Sync {
     /// persistence or checkpoint consumer offsets.
     fn ack(&self, id: Self::Id) -> impl Future<Output = Result<()>> + Send;
 
+    /// Acknowledge a batch of received messages together.
+    ///
+    /// Used by the message bus to settle a batch received with
+    /// [`BatchAck::Atomic`](crate::config::BatchAck::Atomic) once every
+    /// message in it succeeded. Brokers that checkpoint by offset should
+    /// override this to commit the batch in one step.
+    ///
+    /// The default implementation acknowledges each message in turn,
+    /// stopping at the first failure.
+    fn ack_batch(&self, ids: Vec<Self::Id>) -> impl Future<Output = Result<()>> + Send {
+        async move {
+            for id in ids {
+                self.ack(id).await?;
+            }
+            Ok(())
+        }
+    }
+
     /// Negatively acknowledge a message that failed during processing.
     ///
     /// This signals to the broker that the message was not successfully handled,
     /// and should be retried or moved to a dead-letter queue depending on the
     /// broker configuration.
     fn nack(&self, id: Self::Id) -> impl Future<Output = Result<()>> + Send;
+
+    /// Negatively acknowledge a message, recording why it failed.
+    ///
+    /// Called by the message bus instead of `nack` so that brokers can keep
+    /// the failure reason alongside the message, e.g. as a reason column in
+    /// a dead-letter queue. Brokers that can store the reason should
+    /// override this.
+    ///
+    /// The default implementation ignores the reason and calls `nack`.
+    fn nack_with_reason(
+        &self,
+        id: Self::Id,
+        _reason: NackReason,
+    ) -> impl Future<Output = Result<()>> + Send {
+        self.nack(id)
+    }
+
+    /// Negatively acknowledge a batch of received messages together.
+    ///
+    /// Used by the message bus to settle the remaining messages of a batch
+    /// received with [`BatchAck::Atomic`](crate::config::BatchAck::Atomic)
+    /// once any message in it failed, so the whole batch is redelivered.
+    ///
+    /// The default implementation negatively acknowledges each message in
+    /// turn with [`nack_with_reason`](Self::nack_with_reason), stopping at
+    /// the first failure.
+    fn nack_batch(
+        &self,
+        ids: Vec<Self::Id>,
+        reason: NackReason,
+    ) -> impl Future<Output = Result<()>> + Send {
+        async move {
+            for id in ids {
+                self.nack_with_reason(id, reason.clone()).await?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Negatively acknowledge a message, delaying its redelivery.
+    ///
+    /// Used by the message bus instead of `nack_with_reason` when a
+    /// [`BusConfig::nack_delay`](crate::config::BusConfig::nack_delay) is
+    /// configured. Brokers with native delayed requeue should override this
+    /// to avoid holding the consumer.
+    ///
+    /// The default implementation sleeps for `delay` and then calls
+    /// `nack_with_reason`.
+    fn nack_with_delay(
+        &self,
+        id: Self::Id,
+        reason: NackReason,
+        delay: Duration,
+    ) -> impl Future<Output = Result<()>> + Send {
+        async move {
+            tokio::time::sleep(delay).await;
+            self.nack_with_reason(id, reason).await
+        }
+    }
+
+    /// The delivery attempt of a received message, starting at `1`.
+    ///
+    /// Used by the message bus to decide whether a failed message has
+    /// exhausted its retries (see
+    /// [`BusConfig::max_retries`](crate::config::BusConfig::max_retries)).
+    /// Brokers that track redeliveries should override this.
+    ///
+    /// The default implementation always reports a first delivery, so
+    /// messages are never dead-lettered by the bus.
+    fn delivery_attempt(&self, _id: &Self::Id) -> u32 {
+        1
+    }
+
+    /// Dead-letter a message that has exhausted its retries.
+    ///
+    /// This signals to the broker that the message must not be redelivered.
+    /// Brokers with a dead-letter queue should override this to move the
+    /// message there.
+    ///
+    /// The default implementation acknowledges the message, removing it
+    /// from the queue.
+    fn dead_letter(&self, id: Self::Id) -> impl Future<Output = Result<()>> + Send {
+        self.ack(id)
+    }
+
+    /// Dead-letter a message, recording why it failed.
+    ///
+    /// Called by the message bus instead of `dead_letter` so that brokers
+    /// can store the failure in a structured form alongside the
+    /// dead-lettered message, e.g. as a JSON column that operators filter
+    /// by [`ErrorDetails::category`]. Brokers with a dead-letter queue
+    /// should override this.
+    ///
+    /// The default implementation ignores the details and calls
+    /// `dead_letter`.
+    fn dead_letter_with_reason(
+        &self,
+        id: Self::Id,
+        _details: ErrorDetails,
+    ) -> impl Future<Output = Result<()>> + Send {
+        self.dead_letter(id)
+    }
+
+    /// Look at the next message without consuming it.
+    ///
+    /// Returns the message at the head of the queue, if any, without
+    /// removing it, starting its visibility timeout, or counting it as a
+    /// delivery attempt. Intended for debugging and admin tooling, such as
+    /// inspecting which message is stuck at the front of a queue.
+    ///
+    /// The default implementation returns an error, as not every transport
+    /// can inspect its queue.
+    fn peek(&self) -> impl Future<Output = Result<Option<(Self::Id, Self::Message)>>> + Send {
+        future::ready(Err(anyhow::anyhow!("broker does not support peeking")))
+    }
+
+    /// Subscribe the broker to a subject pattern.
+    ///
+    /// Called by the message bus before [`receiver`](Self::receiver) when a
+    /// [`BusConfig::subscription`](crate::config::BusConfig::subscription)
+    /// is configured. Brokers that support subject patterns (e.g. NATS or
+    /// MQTT) should override this to route all matching messages to the
+    /// receiver.
+    ///
+    /// The default implementation accepts plain subjects as a no-op and
+    /// returns an error for any pattern containing wildcards.
+    fn subscribe(&self, pattern: &str) -> impl Future<Output = Result<()>> + Send {
+        let res = if pattern.contains(WILDCARDS) {
+            Err(anyhow::anyhow!(
+                "broker does not support pattern subscriptions: `{pattern}`"
+            ))
+        } else {
+            Ok(())
+        };
+        async move { res }
+    }
+}
+
+/// A broker able to acknowledge messages within a unit of work's transaction.
+///
+/// For DB-backed brokers (e.g. a Postgres queue whose ack is a row update),
+/// acknowledging a command in the same transaction as its changes gives
+/// exactly-once processing: a crash can neither leave a committed command
+/// unacknowledged (and so processed again) nor an acknowledged command
+/// uncommitted (and so lost).
+///
+/// The message bus uses this through
+/// [`MessageBusDriver::ack_in_transaction`](crate::driver::MessageBusDriver::ack_in_transaction),
+/// which a driver whose broker implements this trait overrides:
+///
+/// ```rust,ignore
+/// fn ack_in_transaction(
+///     &self,
+///     broker: &PgBroker,
+///     uow: &mut PgUnitOfWork,
+///     id: PgMessageId,
+/// ) -> impl Future<Output = Result<bool>> + Send {
+///     async move {
+///         broker.ack_in(uow, id).await?;
+///         Ok(true)
+///     }
+/// }
+/// ```
+pub trait TransactionalBroker<U: UnitOfWork>: MessageBroker {
+    /// Acknowledge a message as part of the unit of work's transaction.
+    ///
+    /// The acknowledgement must only take effect if the unit of work
+    /// commits, and must be discarded if it rolls back.
+    fn ack_in(&self, uow: &mut U, id: Self::Id) -> impl Future<Output = Result<()>> + Send;
+}
+
+/// Identifies the queue, shard, or partition a message is published to.
+///
+/// Returned by [`MessageBusDriver::route`](crate::driver::MessageBusDriver::route)
+/// and passed to [`MessageBroker::publish_to`]. How a key maps to a target
+/// is up to the broker, e.g. a queue name or a partition key.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RouteKey(pub String);
+
+impl From<String> for RouteKey {
+    fn from(key: String) -> Self {
+        Self(key)
+    }
+}
+
+impl From<&str> for RouteKey {
+    fn from(key: &str) -> Self {
+        Self(key.to_owned())
+    }
+}
+
+/// Why a message was negatively acknowledged.
+///
+/// Passed to [`MessageBroker::nack_with_reason`] so the broker can record
+/// the failure alongside the message.
+#[derive(Clone, Debug)]
+pub struct NackReason {
+    /// The error that caused the message to fail, including its causes.
+    pub error: String,
+
+    /// Whether redelivering the message could succeed.
+    ///
+    /// This is `false` for failures the bus knows are permanent, such as a
+    /// command rejected by a sealed aggregate. Brokers may use it to
+    /// dead-letter the message immediately instead of redelivering it.
+    pub retryable: bool,
+}
+
+/// The reason a single message failed to publish.
+///
+/// Returned per message from [`MessageBroker::publish_batch_detailed`].
+/// Cloning a `PublishError` shares the underlying error, so one transport
+/// failure can be reported for several messages.
+#[derive(Clone, Debug)]
+pub struct PublishError(Arc<anyhow::Error>);
+
+impl PublishError {
+    /// Creates a publish error from the given cause.
+    pub fn new(err: impl Into<anyhow::Error>) -> Self {
+        Self(Arc::new(err.into()))
+    }
+}
+
+impl fmt::Display for PublishError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#}", self.0)
+    }
+}
+
+impl Error for PublishError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.0.source()
+    }
 }
@@ -0,0 +1,129 @@
+use std::time::Duration;
+
+use anyhow::Result;
+
+/// How a broker re-establishes a lost connection.
+///
+/// `ConnectionPolicy` gives broker implementations one shared description of
+/// their reconnect behavior, so every broker reacts to network blips the same
+/// way and users tune resilience in one place. Brokers typically take it in
+/// their constructor and wrap their connect calls in
+/// [`reconnect`](Self::reconnect):
+///
+/// ```rust,ignore
+/// let conn = self.policy.reconnect(|| Client::connect(&self.url)).await?;
+/// ```
+///
+/// The delay before each attempt starts at [`backoff`](Self::backoff) and
+/// doubles after every failed attempt, up to [`max_backoff`](Self::max_backoff),
+/// with a random [`jitter`](Self::jitter) applied so that many clients do not
+/// reconnect in lockstep.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ConnectionPolicy {
+    /// The maximum number of reconnect attempts, or `None` to retry forever.
+    ///
+    /// Defaults to `None`.
+    pub max_attempts: Option<u32>,
+
+    /// The delay before the first reconnect attempt.
+    ///
+    /// Defaults to 100 milliseconds.
+    pub backoff: Duration,
+
+    /// The upper bound on the delay between reconnect attempts.
+    ///
+    /// Defaults to 30 seconds.
+    pub max_backoff: Duration,
+
+    /// The fraction of each delay, between `0.0` and `1.0`, that is
+    /// randomized.
+    ///
+    /// A jitter of `0.2` varies each delay by up to 20% in either direction.
+    /// Defaults to `0.2`.
+    pub jitter: f64,
+}
+
+impl Default for ConnectionPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: None,
+            backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(30),
+            jitter: 0.2,
+        }
+    }
+}
+
+impl ConnectionPolicy {
+    /// Give up after the given number of reconnect attempts.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+
+    /// Back off from `backoff`, doubling up to `max_backoff`, between attempts.
+    pub fn with_backoff(mut self, backoff: Duration, max_backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Randomize the given fraction of each delay.
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// The delay before the given reconnect attempt, counting from `1`.
+    ///
+    /// Returns `None` once the attempt exceeds
+    /// [`max_attempts`](Self::max_attempts).
+    pub fn delay(&self, attempt: u32) -> Option<Duration> {
+        if self.max_attempts.is_some_and(|max| attempt > max) {
+            return None;
+        }
+        let exponent = attempt.saturating_sub(1).min(31);
+        let delay = self
+            .backoff
+            .saturating_mul(1 << exponent)
+            .min(self.max_backoff);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        let factor = 1.0 + jitter * (2.0 * unit_random() - 1.0);
+        Some(delay.mul_f64(factor))
+    }
+
+    /// Runs `connect` until it succeeds, sleeping between failed attempts.
+    ///
+    /// Returns the last error once [`max_attempts`](Self::max_attempts)
+    /// reconnects have failed.
+    pub async fn reconnect<T, F, Fut>(&self, mut connect: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            let err = match connect().await {
+                Ok(conn) => return Ok(conn),
+                Err(err) => err,
+            };
+            attempt += 1;
+            let Some(delay) = self.delay(attempt) else {
+                return Err(err);
+            };
+            tracing::warn!(
+                attempt,
+                delay_ms = delay.as_millis() as u64,
+                "broker connection failed, reconnecting: {err:#}"
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+/// A random number in `[0, 1)`, drawn from the v4 UUID generator.
+fn unit_random() -> f64 {
+    // The low 53 bits hold no version or variant bits.
+    let bits = uuid::Uuid::new_v4().as_u128() & ((1 << 53) - 1);
+    bits as f64 / (1u64 << 53) as f64
+}
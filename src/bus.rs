@@ -1,12 +1,32 @@
-use std::any::type_name;
+use std::{
+    any::type_name,
+    error::Error as StdError,
+    fmt, mem,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant, SystemTime},
+};
 
-use anyhow::Result;
-use futures::{StreamExt, pin_mut};
+use anyhow::{Result, anyhow};
+use futures::{
+    FutureExt, Stream, StreamExt,
+    channel::mpsc,
+    future::{self, BoxFuture, Either, Shared},
+    pin_mut, stream,
+};
+use serde::{Deserialize, Serialize};
+use tokio::{runtime::Handle, task::JoinHandle, time::MissedTickBehavior};
+use tracing::{Instrument, field::Empty};
 
 use crate::{
-    engine::MessageBusEngine,
+    engine::{Batched, MessageBusEngine},
+    lock::KeyedGuard,
     prelude::*,
-    view::{Query, View, Viewer},
+    trace::{TraceContext, dispatch_span, in_span, message_span},
+    view::{ContentType, Query, Served, View, Viewer, ViewerFallback},
 };
 
 /// A runtime processor for command, event, and projection messages.
@@ -22,7 +42,7 @@ use crate::{
 ///
 /// # Example
 ///
-/// ```rust
+/// ```rust,ignore
 /// let bus = MessageBus::from(&MyAppDriver);
 /// bus.start().await?;
 /// ```
@@ -33,9 +53,9 @@ use crate::{
 ///
 /// - **Command**: Triggers domain mutation inside a [`UnitOfWork`].
 /// - **Event**: Emitted by successful commands; passed to a [`Policy`] to derive
-/// follow-up actions.
+///   follow-up actions.
 /// - **Projection**: Infrastructure-facing side effect messages handled by a
-/// [`Projector`].
+///   [`Projector`].
 ///
 /// All message types are received via a [`MessageBroker`], dispatched internally,
 /// and acknowledged or retried based on their outcome.
@@ -48,7 +68,7 @@ use crate::{
 /// # Traits You Must Implement
 ///
 /// - [`MessageBusDriver`]: Defines your domain message types and supporting
-///     components.
+///   components.
 /// - [`CommandHandler<C, D>`]: Implements logic for your commands.
 /// - [`Policy<Event, D>`]: Maps events to follow-up commands and projections.
 /// - [`Projector<Projection, D>`]: Applies projections to external systems.
@@ -59,6 +79,16 @@ pub struct MessageBus<D: MessageBusDriver> {
     engine: MessageBusEngine<D>,
 }
 
+/// How long a projection waiting on its dependency is held back before it
+/// is redelivered, unless a [`BusConfig::nack_delay`] is set.
+const DEPENDENCY_DELAY: Duration = Duration::from_secs(1);
+
+/// The longest delay between publish retries, however many have failed.
+const MAX_PUBLISH_BACKOFF: Duration = Duration::from_secs(60);
+
+/// A signal to stop receiving messages, shared by the parts of a run.
+type Stop = Shared<BoxFuture<'static, ()>>;
+
 impl<D: MessageBusDriver> Clone for MessageBus<D> {
     fn clone(&self) -> Self {
         Self {
@@ -90,27 +120,289 @@ impl<D: MessageBusDriver> MessageBus<D> {
     /// The provided command is handled by the corresponding `CommandHandler`,
     /// using a fresh `UnitOfWork` for transaction isolation. On success,
     /// any captured domain events are published to the message bus. If
-    /// command handling or commit fails, the unit of work is rolled back
-    /// and the error is returned.
+    /// command handling or commit fails, or the command touched a sealed
+    /// aggregate, the unit of work is rolled back and the error is returned.
     ///
     /// This method is primarily used to execute commands from within an
     /// application service, CLI, or HTTP controller.
-    pub async fn dispatch<C: Command>(&self, cmd: C) -> Result<Option<D::Identifier>>
+    ///
+    /// If the handler assigns the command a
+    /// [`concurrency_key`](CommandHandler::concurrency_key), the dispatch
+    /// waits until no other command with the same key is running.
+    ///
+    /// When called within [`with_deadline`], the unit of work is rolled back
+    /// with [`BusError::DeadlineExceeded`] if the deadline passes before it
+    /// commits.
+    ///
+    /// If the handler returns [`HandlerOutcome::NoChange`], the unit of work
+    /// is rolled back instead, nothing is published, and the result is not
+    /// [`changed`](DispatchResult::changed).
+    ///
+    /// If the handler declares
+    /// [`required_permissions`](CommandHandler::required_permissions) for the
+    /// command, the principal set with [`as_principal`] must have all of
+    /// them, or [`BusError::Unauthorized`] is returned before a unit of work
+    /// is created.
+    ///
+    /// Once the unit of work has committed, publishing its events is shielded
+    /// from cancellation: dropping the returned future will not abandon the
+    /// publish half way. Must be called from within a Tokio runtime.
+    pub async fn dispatch<C: Command>(&self, cmd: C) -> Result<DispatchResult<D::Identifier>>
+    where
+        D::Handler: CommandHandler<C, D>,
+    {
+        println!("User provided command: {}", type_name::<C>());
+        let (outcome, _) = self
+            .traced(type_name::<C>(), async move {
+                self.authorize(&cmd)?;
+                self.execute(cmd, None).await
+            })
+            .await?;
+        Ok(DispatchResult::executed(outcome))
+    }
+
+    /// Dispatch a command at most once for a client-supplied command id.
+    ///
+    /// Lets clients retry a command safely, e.g. after a timeout on a flaky
+    /// network, by sending the same id with each attempt. The command is
+    /// authorized and, if no result is recorded for `command_id` in the
+    /// driver's [`IdempotencyStore`], dispatched as with
+    /// [`dispatch`](Self::dispatch) and its result recorded. Otherwise the
+    /// recorded result is returned without running the command again.
+    /// Dispatches of the same id by this bus are serialized, so concurrent
+    /// duplicates also run the command once.
+    ///
+    /// The command id identifies the dispatch in its trace, even when it is
+    /// dispatched while handling another message, so the events it
+    /// publishes carry it as their causation id.
+    ///
+    /// The result is recorded after the unit of work commits. If recording
+    /// it fails, the error is logged and the result still returned, so a
+    /// later retry may run the command again. Fails if the driver has no
+    /// [`idempotency_store`](MessageBusDriver::idempotency_store).
+    pub async fn dispatch_with_id<C: Command>(
+        &self,
+        command_id: impl Into<MessageId>,
+        cmd: C,
+    ) -> Result<DispatchResult<D::Identifier>>
+    where
+        D::Handler: CommandHandler<C, D>,
+    {
+        let command_id = command_id.into();
+        let store = self
+            .engine
+            .idempotency_store
+            .as_ref()
+            .ok_or_else(|| anyhow!("dispatch_with_id requires an idempotency store"))?;
+        self.authorize(&cmd)?;
+        let _guard = (self.engine.command_id_locks)
+            .lock(command_id.clone())
+            .await;
+        if let Some(recorded) = store.get(command_id.clone()).await? {
+            println!("Command {command_id} was already dispatched, returning its result.");
+            return Ok(recorded);
+        }
+        println!("User provided command: {}", type_name::<C>());
+        let (outcome, _) = self
+            .traced_as(
+                command_id.clone(),
+                type_name::<C>(),
+                self.execute(cmd, None),
+            )
+            .await?;
+        let res = DispatchResult::executed(outcome);
+        if let Err(e) = store.put(command_id.clone(), &res).await {
+            tracing::warn!(command_id, "failed to record dispatched command: {e:#}");
+        }
+        Ok(res)
+    }
+
+    /// Dispatch a command, streaming back the progress its handler reports.
+    ///
+    /// The command is dispatched as with [`dispatch`](Self::dispatch), with
+    /// a [`ProgressSink`] available to its handler through
+    /// [`report_progress`]. Each report is yielded as a
+    /// [`Progress::Update`] while the command runs, and the stream ends
+    /// with a [`Progress::Done`] holding the dispatch's result as soon as
+    /// the command finishes, even if a copy of the sink outlives it. This lets
+    /// an API show a progress bar for an expensive command, e.g. by
+    /// forwarding the stream as server-sent events.
+    ///
+    /// The command only runs while the stream is polled, and is abandoned
+    /// if the stream is dropped before it ends, as a dispatch future would
+    /// be.
+    pub fn dispatch_streaming<'a, C: Command + 'a>(
+        &'a self,
+        cmd: C,
+    ) -> impl Stream<Item = Progress<D::Identifier>> + 'a
+    where
+        D::Handler: CommandHandler<C, D>,
+    {
+        let (sender, reports) = mpsc::unbounded();
+        let run = Box::pin(ProgressSink::new(sender).scope(self.dispatch(cmd)));
+        stream::unfold(Some((reports, Either::Left(run))), |state| async move {
+            let (mut reports, running) = state?;
+            let res = match running {
+                Either::Left(mut run) => {
+                    let next = match future::select(reports.next(), &mut run).await {
+                        Either::Left((report, _)) => Either::Left(report),
+                        Either::Right((res, _)) => Either::Right(res),
+                    };
+                    match next {
+                        Either::Left(Some(report)) => {
+                            let running = Either::Left(run);
+                            return Some((Progress::Update(report), Some((reports, running))));
+                        }
+                        Either::Left(None) => run.await,
+                        Either::Right(res) => res,
+                    }
+                }
+                Either::Right(res) => res,
+            };
+            // Reports made before the result are already buffered. Any made
+            // later, through a sink the handler kept, are discarded.
+            reports.close();
+            match reports.try_next() {
+                Ok(Some(report)) => Some((
+                    Progress::Update(report),
+                    Some((reports, Either::Right(res))),
+                )),
+                _ => Some((Progress::Done(res), None)),
+            }
+        })
+    }
+
+    /// Dispatch a command within a unit of work owned by the caller.
+    ///
+    /// The command is handled against `uow` exactly as in
+    /// [`dispatch`](Self::dispatch), but the unit of work is neither
+    /// committed nor rolled back: its events stay captured in it. This lets
+    /// an outer operation, such as an HTTP handler, compose several commands
+    /// and other repository work into one transaction that it controls.
+    ///
+    /// The caller should finish with [`commit`](Self::commit), which also
+    /// publishes the captured events, or roll the unit of work back if any
+    /// step fails. Concurrency keys are not honored, since the caller
+    /// decides when the transaction ends. A
+    /// [`HandlerOutcome::NoChange`] is reported as not
+    /// [`changed`](DispatchResult::changed), but is left to the caller to
+    /// act on.
+    pub async fn dispatch_in<C: Command>(
+        &self,
+        uow: &mut D::UnitOfWork,
+        cmd: C,
+    ) -> Result<DispatchResult<D::Identifier>>
     where
         D::Handler: CommandHandler<C, D>,
     {
         println!("User provided command: {}", type_name::<C>());
-        let mut uow = self.engine.uow_factory.create().await?;
-        match self.engine.handler.handle(&mut uow, cmd).await {
+        self.traced(type_name::<C>(), async move {
+            self.authorize(&cmd)?;
+            let outcome = self.run_handler(uow, cmd).await?;
+            check_deadline()?;
+            Ok(DispatchResult::executed(outcome))
+        })
+        .await
+    }
+
+    /// Commit a caller-owned unit of work and publish its events.
+    ///
+    /// The counterpart of [`dispatch_in`](Self::dispatch_in). Publishing
+    /// behaves as it does after [`dispatch`](Self::dispatch).
+    pub async fn commit(&self, uow: D::UnitOfWork) -> Result<()> {
+        let events = uow.commit().await?;
+        self.publish_committed(events, false).await
+    }
+
+    /// Submit a command for immediate or queued execution.
+    ///
+    /// If the handler's [`should_queue`](CommandHandler::should_queue)
+    /// returns `true`, the command is published to the broker, to be
+    /// handled later like any received command, and
+    /// [`DispatchStatus::Queued`] is returned without a result. Otherwise
+    /// the command is executed as with [`dispatch`](Self::dispatch) and
+    /// [`DispatchStatus::Executed`] is returned with the handler's result.
+    ///
+    /// This lets an API answer `200 OK` or `202 Accepted` depending on how
+    /// its command was handled. Queued commands are authorized as in
+    /// [`dispatch`](Self::dispatch) before they are published, and not again
+    /// when they are received.
+    pub async fn submit(&self, cmd: D::Command) -> Result<DispatchResult<D::Identifier>> {
+        if !self.engine.handler.should_queue(&cmd) {
+            return self.dispatch(cmd).await;
+        }
+        println!("Queueing command: {}", type_name::<D::Command>());
+        self.traced(type_name::<D::Command>(), async move {
+            self.authorize(&cmd)?;
+            let message = self.envelope(Message::Command(cmd), None);
+            throttle(&self.engine.config, 1).await;
+            match self.route(&message) {
+                Some(route) => {
+                    self.engine
+                        .broker
+                        .publish_to(&route, message.clone())
+                        .await?
+                }
+                None => self.engine.broker.publish(message.clone()).await?,
+            }
+            self.observe(&message);
+            Ok(DispatchResult {
+                status: DispatchStatus::Queued,
+                changed: false,
+                result: None,
+            })
+        })
+        .await
+    }
+
+    /// Executes a command in a fresh unit of work, as described in
+    /// [`dispatch`](Self::dispatch).
+    ///
+    /// If the command was `received` from the broker, the driver is given
+    /// the chance to acknowledge it within the unit of work's transaction
+    /// (see [`MessageBusDriver::ack_in_transaction`]). Returns whether it
+    /// was acknowledged, alongside the handler's outcome. A command that
+    /// made no change is rolled back, and so never acknowledged here.
+    async fn execute<C: Command>(
+        &self,
+        cmd: C,
+        received: Option<<D::Broker as MessageBroker>::Id>,
+    ) -> Result<(HandlerOutcome<D::Identifier>, bool)>
+    where
+        D::Handler: CommandHandler<C, D>,
+    {
+        let _guard = self.lock_command(&cmd).await;
+        let mut uow = create(&self.engine.uow_factory).await?;
+        let handled = async {
+            let outcome = self.run_handler(&mut uow, cmd).await?;
+            check_deadline()?;
+            let acked = match received {
+                Some(_) if !outcome.is_changed() => false,
+                Some(id) => {
+                    let driver = &self.engine.driver;
+                    driver
+                        .ack_in_transaction(&self.engine.broker, &mut uow, id)
+                        .await?
+                }
+                None => false,
+            };
+            Ok::<_, anyhow::Error>((outcome, acked))
+        }
+        .await;
+        match handled {
+            Ok((HandlerOutcome::NoChange, _)) => {
+                println!("Command made no change, rolling back.");
+                uow.rollback().await?;
+                Ok((HandlerOutcome::NoChange, false))
+            }
             Ok(res) => {
-                let events = uow
-                    .commit()
-                    .await?
-                    .into_iter()
-                    .map(DriverMessage::<D>::Event)
-                    .collect();
-                self.engine.broker.publish_batch(events).await?;
-                Ok(res)
+                let events = uow.commit().await?;
+                match self.publish_committed(events, false).await {
+                    Ok(()) => Ok(res),
+                    // The received message is already settled.
+                    Err(e) if res.1 => Err(AckedFailure(e).into()),
+                    Err(e) => Err(e),
+                }
             }
             Err(e) => {
                 uow.rollback().await?;
@@ -119,99 +411,2617 @@ impl<D: MessageBusDriver> MessageBus<D> {
         }
     }
 
-    pub async fn view<Q: Query>(&self, query: Q) -> Result<impl View>
+    /// Decode a command from raw JSON and dispatch it.
+    ///
+    /// The `type_tag` names the variant of the driver's `Command` type and
+    /// `body` holds that variant's JSON payload. Decoding relies on serde's
+    /// default, externally tagged enum representation: the pair is decoded
+    /// as `{ type_tag: body }`. The dispatch result is serialized back to
+    /// JSON.
+    ///
+    /// This lets a generic HTTP gateway drive the bus without per-command
+    /// glue code. Requires the `json` feature.
+    #[cfg(feature = "json")]
+    pub async fn dispatch_json(&self, type_tag: &str, body: &[u8]) -> Result<serde_json::Value>
     where
-        D::Viewer: Viewer<Q>,
+        D::Command: serde::de::DeserializeOwned,
+        D::Identifier: serde::Serialize,
     {
-        self.engine.viewer.view(query).await
+        let payload: serde_json::Value = serde_json::from_slice(body)?;
+        let tagged =
+            serde_json::Value::Object([(type_tag.to_owned(), payload)].into_iter().collect());
+        let cmd: D::Command = serde_json::from_value(tagged)?;
+        let res = self.dispatch(cmd).await?;
+        Ok(serde_json::to_value(res.result)?)
     }
 
-    /// Starts the message bus processing loop.
+    /// Dispatch a command, applying inline projections in its transaction.
     ///
-    /// This continuously receives messages from the message broker, routes
-    /// them to the appropriate handler (command, event, or projection), and
-    /// acknowledges them based on the result.
+    /// Behaves like [`dispatch`](Self::dispatch), except that before the unit
+    /// of work commits, the policy is evaluated against every captured event
+    /// and any projection the [`Projector`] marks as inline is applied via
+    /// [`InlineProjection::project_inline`]. The read model is therefore
+    /// committed atomically with the command. The published events are
+    /// marked as [projected inline](Envelope::projected_inline), so their
+    /// inline projections are skipped when the bus later handles them.
     ///
-    /// This function should be run for the duration of the application
-    /// lifecycle — typically as a background task or top-level service.
-    pub async fn start(self) -> Result<()>
+    /// If policy evaluation or an inline projection fails, the unit of work
+    /// is rolled back and the error is returned. A command that made no
+    /// change is rolled back without applying any projection.
+    pub async fn dispatch_consistent<C: Command>(
+        &self,
+        cmd: C,
+    ) -> Result<DispatchResult<D::Identifier>>
     where
-        D::Handler: CommandHandler<D::Command, D>,
+        D::Handler: CommandHandler<C, D>,
         D::Policy: Policy<D::Event, D, Output = SideEffect<D::Command, D::Projection>>,
+        D::UnitOfWork: InlineProjection<D::Projection>,
     {
-        let stream = self.engine.broker.receiver();
-        pin_mut!(stream);
-        while let Some((id, msg)) = stream.next().await {
-            match self.handle_message(msg).await {
-                Ok(_) => {
-                    self.engine.broker.ack(id).await?;
-                    println!("Handled message successfully.");
+        println!("User provided command: {}", type_name::<C>());
+        self.traced(type_name::<C>(), async move {
+            self.authorize(&cmd)?;
+            let _guard = self.lock_command(&cmd).await;
+            let mut uow = create(&self.engine.uow_factory).await?;
+            let outcome = match self.run_handler(&mut uow, cmd).await {
+                Ok(HandlerOutcome::NoChange) => {
+                    uow.rollback().await?;
+                    return Ok(DispatchResult::executed(HandlerOutcome::NoChange));
                 }
+                Ok(outcome) => outcome,
                 Err(e) => {
-                    self.engine.broker.nack(id).await?;
-                    println!("Handled message unsuccessfully: {e:#?}");
+                    uow.rollback().await?;
+                    return Err(e);
                 }
             };
-        }
-        Ok(())
+            let projected = self.project_inline(&mut uow).await;
+            if let Err(e) = projected.and_then(|()| check_deadline()) {
+                uow.rollback().await?;
+                return Err(e);
+            }
+            let events = uow.commit().await?;
+            self.publish_committed(events, true).await?;
+            Ok(DispatchResult::executed(outcome))
+        })
+        .await
     }
 
-    /// Routes an incoming message to its corresponding handler.
+    /// Dispatch a command whose input is processed in committed sub-batches.
     ///
-    /// This internal function dispatches commands, executes projections, or
-    /// applies event policies depending on the message variant.
-    async fn handle_message(&self, msg: DriverMessage<D>) -> Result<()>
+    /// The command's input stream is split into batches of at most
+    /// [`StreamingCommandHandler::batch_size`] items. Each batch is handled
+    /// in a fresh `UnitOfWork`, which is committed and has its events
+    /// published before the next batch begins. If a batch fails, its unit of
+    /// work is rolled back and the error is returned; previously committed
+    /// batches are kept, so the command can be resumed from its last
+    /// checkpoint.
+    ///
+    /// The command is authorized against the handler's
+    /// [`required_permissions`](StreamingCommandHandler::required_permissions)
+    /// before its input is read, failing with [`BusError::Unauthorized`].
+    ///
+    /// Returns the number of input items processed.
+    pub async fn dispatch_chunked<C: Command + Sync>(&self, cmd: C) -> Result<u64>
     where
-        D::Handler: CommandHandler<D::Command, D>,
-        D::Policy: Policy<D::Event, D, Output = SideEffect<D::Command, D::Projection>>,
+        D::Handler: StreamingCommandHandler<C, D>,
     {
-        match msg {
-            Message::Command(cmd) => {
-                println!("Executing command");
-                self.dispatch(cmd).await?;
-            }
-            Message::Event(event) => {
-                println!("Executing event");
-                self.handle_event(event).await?;
+        println!("User provided streaming command: {}", type_name::<C>());
+        self.traced(type_name::<C>(), async move {
+            let handler = &self.engine.handler;
+            check_permissions(&StreamingCommandHandler::required_permissions(
+                handler, &cmd,
+            ))?;
+            let batch_size = handler.batch_size(&cmd).max(1);
+            let batches = handler.input(&cmd).await?.chunks(batch_size);
+            pin_mut!(batches);
+
+            let mut processed = 0;
+            while let Some(batch) = batches.next().await {
+                check_deadline()?;
+                let batch = batch.into_iter().collect::<Result<Vec<_>>>()?;
+                let len = batch.len() as u64;
+                let mut uow = create(&self.engine.uow_factory).await?;
+                let handled = handler.handle_batch(&mut uow, &cmd, batch).await;
+                match Self::reject_sealed(&uow, handled) {
+                    Ok(()) => {
+                        let events = uow.commit().await?;
+                        self.publish_committed(events, false).await?;
+                        processed += len;
+                        println!("Committed batch of {len} items ({processed} total).");
+                    }
+                    Err(e) => {
+                        uow.rollback().await?;
+                        return Err(e);
+                    }
+                }
             }
-            Message::Projection(projection) => {
-                println!("Executing projection");
-                self.engine.projector.project(projection).await?;
+            Ok(processed)
+        })
+        .await
+    }
+
+    /// Dispatch a command that fans out into independent sub-commands.
+    ///
+    /// The command is split with [`FanOutCommand::split`] using a fresh
+    /// `PolicyContext`, which is closed afterwards. The resulting commands
+    /// are published to the broker rather than executed directly, so each
+    /// is later handled in its own `UnitOfWork` and retried independently.
+    /// Publishing follows [`BusConfig::publish_retries`] and
+    /// [`BusConfig::publish_concurrency`].
+    ///
+    /// If splitting fails, nothing is published and the error is returned.
+    /// If publishing fails, some sub-commands may already have been
+    /// published, so sub-commands should be safe to apply more than once.
+    ///
+    /// The command and each of its sub-commands are authorized as in
+    /// [`dispatch`](Self::dispatch), so that permissions cannot be bypassed
+    /// by fanning out; if any fails, nothing is published and
+    /// [`BusError::Unauthorized`] is returned. Sub-commands are not
+    /// authorized again when they are received.
+    ///
+    /// Returns the number of sub-commands published.
+    pub async fn dispatch_fan_out<C: FanOutCommand<D>>(&self, cmd: C) -> Result<usize> {
+        println!("User provided fan-out command: {}", type_name::<C>());
+        self.traced(type_name::<C>(), async move {
+            check_permissions(&cmd.required_permissions())?;
+            let mut ctx = create(&self.engine.policy_context_factory).await?;
+            let split = cmd.split(&mut ctx).await;
+            ctx.close().await?;
+            let commands = split?;
+            check_deadline()?;
+            for sub in &commands {
+                self.authorize(sub)?;
             }
+
+            let messages = commands
+                .into_iter()
+                .map(|cmd| self.envelope(Message::Command(cmd), None))
+                .collect::<Vec<_>>();
+            let count = messages.len();
+            let config = &self.engine.config;
+            let observe = |message: &_| self.observe(message);
+            let route = |message: &_| self.route(message);
+            publish_with_retries(&self.engine.broker, messages, false, config, observe, route)
+                .await
+                .map_err(|(_, err)| err)?;
+            println!("Published {count} sub-commands.");
+            Ok(count)
+        })
+        .await
+    }
+
+    /// Wraps a message to be published by the bus.
+    ///
+    /// The envelope is stamped with the current time of the configured
+    /// [`Clock`](crate::clock::Clock), and with `id`, or a new id from the
+    /// configured [`IdGenerator`] if `id` is `None`. It carries the current
+    /// task's deadline, if any, and is caused by the message the current
+    /// task is processing, if any.
+    fn envelope<M>(&self, message: M, id: Option<MessageId>) -> Envelope<M> {
+        let id = id.unwrap_or_else(|| self.engine.config.generate_id());
+        let mut envelope = Envelope::new(message)
+            .with_id(id)
+            .with_occurred_at(self.engine.config.now());
+        if let Some(deadline) = current_deadline() {
+            envelope = envelope.with_deadline(deadline);
+        }
+        if let Some(ctx) = TraceContext::current() {
+            envelope = envelope.with_causation(ctx.message_id, ctx.correlation_id);
+        }
+        envelope
+    }
+
+    /// The route of a message published by the bus, if it is a command the
+    /// driver routes.
+    fn route(&self, envelope: &DriverEnvelope<D>) -> Option<RouteKey> {
+        match &envelope.message {
+            Message::Command(cmd) => self.engine.driver.route(cmd),
+            _ => None,
+        }
+    }
+
+    /// Runs a command dispatched by the application as the root of a trace,
+    /// within a `dispatch` tracing span (see [`record_attr`]).
+    ///
+    /// If the current task is already processing a message, e.g. a command
+    /// received by the bus, `fut` runs as part of that message instead.
+    async fn traced<F: Future>(&self, type_name: &'static str, fut: F) -> F::Output {
+        if TraceContext::current().is_some() {
+            return fut.await;
+        }
+        let message_id = self.engine.config.generate_id();
+        self.traced_as(message_id, type_name, fut).await
+    }
+
+    /// Runs a command dispatched by the application, identified by
+    /// `message_id`, within a `dispatch` tracing span.
+    ///
+    /// Unlike [`traced`](Self::traced), `fut` always runs as its own
+    /// message. If the current task is already processing a message, the
+    /// command is recorded as caused by it and shares its correlation id;
+    /// otherwise it is the root of a new trace.
+    async fn traced_as<F: Future>(
+        &self,
+        message_id: MessageId,
+        type_name: &'static str,
+        fut: F,
+    ) -> F::Output {
+        let parent = TraceContext::current();
+        let correlation_id = match &parent {
+            Some(parent) => parent.correlation_id.clone(),
+            None => message_id.clone(),
         };
-        Ok(())
+        self.record_trace(TraceEntry {
+            message_id: message_id.clone(),
+            causation_id: parent.map(|parent| parent.message_id),
+            correlation_id: correlation_id.clone(),
+            kind: MessageKind::Command,
+            type_name,
+        });
+        let ctx = TraceContext {
+            correlation_id,
+            message_id,
+            projected_inline: false,
+        };
+        let span = dispatch_span(type_name, &ctx);
+        ctx.scope(in_span(span, fut)).await
     }
 
-    /// Handles a domain event by applying the associated policy.
+    /// Notifies the driver's [`PublishObserver`]s of a published message.
+    fn observe(&self, envelope: &DriverEnvelope<D>) {
+        notify(&self.engine.publish_observers, envelope);
+    }
+
+    /// Records a trace entry to the configured [`TraceSink`], if any.
+    fn record_trace(&self, entry: TraceEntry) {
+        if let Some(sink) = &self.engine.config.trace_sink {
+            sink.record(entry);
+        }
+    }
+
+    /// Checks that the current principal may dispatch the command.
     ///
-    /// A new `PolicyContext` is created for the event, and the policy is
-    /// applied using the event data. The resulting side effects (commands
-    /// and/or projections) are then published back to the message bus.
+    /// Only commands dispatched by the application are authorized; see
+    /// [`as_principal`].
+    fn authorize<C: Command>(&self, cmd: &C) -> Result<()>
+    where
+        D::Handler: CommandHandler<C, D>,
+    {
+        check_permissions(&self.engine.handler.required_permissions(cmd))
+    }
+
+    /// Acquires the lock for the command's concurrency key, if it has one.
+    async fn lock_command<C: Command>(&self, cmd: &C) -> Option<KeyedGuard>
+    where
+        D::Handler: CommandHandler<C, D>,
+    {
+        let key = self.engine.handler.concurrency_key(cmd)?;
+        Some(self.engine.command_locks.lock(key).await)
+    }
+
+    /// Runs the command handler and its lifecycle hooks against a unit of work.
     ///
-    /// The context is closed after handling, even if the policy fails.
-    async fn handle_event(&self, event: D::Event) -> Result<()>
+    /// [`CommandHandler::after_handle`] is called whenever
+    /// [`CommandHandler::before_handle`] succeeded, even if `handle` fails or
+    /// panics. A panic is resumed once `after_handle` has run.
+    async fn run_handler<C: Command>(
+        &self,
+        uow: &mut D::UnitOfWork,
+        cmd: C,
+    ) -> Result<HandlerOutcome<D::Identifier>>
+    where
+        D::Handler: CommandHandler<C, D>,
+    {
+        let handler = &self.engine.handler;
+        handler.before_handle(uow, &cmd).await?;
+        let handled = AssertUnwindSafe(handler.handle(uow, cmd))
+            .catch_unwind()
+            .await;
+        let released = handler.after_handle(uow).await;
+        let handled = match handled {
+            Ok(handled) => handled.and_then(|res| released.map(|()| res)),
+            Err(panic) => panic::resume_unwind(panic),
+        };
+        Self::reject_sealed(uow, handled)
+    }
+
+    /// Replaces a handler result with [`BusError::AggregateSealed`] if the
+    /// unit of work loaded a sealed aggregate.
+    fn reject_sealed<T>(uow: &D::UnitOfWork, handled: Result<T>) -> Result<T> {
+        match uow.sealed() {
+            Some(aggregate) => Err(BusError::AggregateSealed {
+                aggregate: aggregate.to_owned(),
+            }
+            .into()),
+            None => handled,
+        }
+    }
+
+    /// Applies the inline projections derived from a unit of work's events.
+    async fn project_inline(&self, uow: &mut D::UnitOfWork) -> Result<()>
     where
         D::Policy: Policy<D::Event, D, Output = SideEffect<D::Command, D::Projection>>,
+        D::UnitOfWork: InlineProjection<D::Projection>,
     {
-        let mut ctx = self.engine.policy_context_factory.create().await?;
-        let res = match self.engine.policy.apply(&mut ctx, event).await {
-            Ok(events) => {
-                let messages = events
+        let events = uow
+            .pending_events()
+            .iter()
+            .filter(|event| self.engine.policy.subscribes_to(event))
+            .cloned()
+            .collect::<Vec<_>>();
+        if events.is_empty() {
+            return Ok(());
+        }
+        let mut ctx = create(&self.engine.policy_context_factory).await?;
+        let mut projections = Vec::new();
+        let mut res = Ok(());
+        for event in events {
+            let applied = match self.enrich(&mut ctx, &event).await {
+                Ok(()) => self.engine.policy.apply(&mut ctx, event).await,
+                Err(e) => Err(e),
+            };
+            match applied {
+                Ok(side_effects) => {
+                    let side_effects = side_effects.into_policy_output();
+                    projections.extend(side_effects.into_iter().filter_map(|s| match s {
+                        SideEffect::Projection(p) if self.engine.projector.is_inline(&p) => Some(p),
+                        _ => None,
+                    }))
+                }
+                Err(e) => {
+                    res = Err(e);
+                    break;
+                }
+            }
+        }
+        ctx.close().await?;
+        res?;
+
+        for projection in projections {
+            uow.project_inline(projection).await?;
+        }
+        Ok(())
+    }
+
+    /// Publishes the events of a committed unit of work.
+    ///
+    /// Each event's envelope is stamped with its position in the commit, so
+    /// consumers can preserve the order in which the events were captured.
+    /// With [`BusConfig::batch_committed_events`], the batch holding them
+    /// is stamped as the first position.
+    ///
+    /// The publish runs on a spawned task, on [`BusConfig::runtime`] if
+    /// set, so it completes even if the caller is cancelled while awaiting
+    /// it. Without this, a committed transaction could silently lose its
+    /// events.
+    ///
+    /// Events that fail to publish are retried up to
+    /// [`BusConfig::publish_retries`] times, doubling
+    /// [`BusConfig::publish_backoff`] between attempts. Any events that
+    /// still fail are handed to [`MessageBusDriver::store_unpublished`]; if
+    /// they cannot be stored either, [`BusError::PublishAfterCommit`] is
+    /// returned.
+    async fn publish_committed(&self, events: Vec<D::Event>, projected_inline: bool) -> Result<()> {
+        let broker = self.engine.broker.clone();
+        let driver = self.engine.driver.clone();
+        let config = self.engine.config.clone();
+        let observers = self.engine.publish_observers.clone();
+        let envelope = |message| {
+            let envelope = self.envelope(message, None);
+            match projected_inline {
+                true => envelope.with_projected_inline(),
+                false => envelope,
+            }
+        };
+        let messages = if self.engine.config.batch_committed_events && events.len() > 1 {
+            vec![envelope(Message::EventBatch(events)).with_sequence(0)]
+        } else {
+            events
+                .into_iter()
+                .zip(0..)
+                .map(|(event, seq)| envelope(Message::Event(event)).with_sequence(seq))
+                .collect()
+        };
+        self.engine
+            .config
+            .spawn(async move {
+                let observe = |message: &DriverEnvelope<D>| notify(&observers, message);
+                let published =
+                    publish_with_retries(&broker, messages, false, &config, observe, |_| None);
+                let (unpublished, err) = match published.await {
+                    Ok(()) => return Ok(()),
+                    Err(failure) => failure,
+                };
+
+                let events = unpublished
                     .into_iter()
-                    .map(|side_effect| match side_effect {
-                        SideEffect::Command(cmd) => Message::Command(cmd),
-                        SideEffect::Projection(proj) => Message::Projection(proj),
+                    .flat_map(|envelope| match envelope.message {
+                        Message::Event(event) => vec![event],
+                        Message::EventBatch(events) => events,
+                        _ => Vec::new(),
                     })
                     .collect::<Vec<_>>();
-                let num_events = messages.len();
-                self.engine.broker.publish_batch(messages).await?;
-                println!("Published {num_events} events.");
-                Ok(())
+                let count = events.len();
+                match driver.store_unpublished(events).await {
+                    Ok(()) => {
+                        println!("Stored {count} unpublished events after commit.");
+                        Ok(())
+                    }
+                    Err(store_err) => {
+                        println!("Failed to store unpublished events: {store_err:#}");
+                        Err(BusError::PublishAfterCommit {
+                            events: count,
+                            source: err.into(),
+                        }
+                        .into())
+                    }
+                }
+            })
+            .await?
+    }
+
+    /// Describe the components wired into this message bus.
+    ///
+    /// Returns the type names of the driver, its message types, and each
+    /// component, along with a summary of the runtime configuration. The
+    /// components themselves are not exposed. This is intended for
+    /// diagnostics, such as a `/debug/config` endpoint used to verify that
+    /// the expected implementations are deployed.
+    pub fn describe(&self) -> BusDescription {
+        BusDescription {
+            driver: type_name::<D>(),
+            command: type_name::<D::Command>(),
+            event: type_name::<D::Event>(),
+            projection: type_name::<D::Projection>(),
+            broker: type_name::<D::Broker>(),
+            unit_of_work: type_name::<D::UnitOfWork>(),
+            policy_context: type_name::<D::PolicyContext>(),
+            handler: type_name::<D::Handler>(),
+            policy: type_name::<D::Policy>(),
+            projector: type_name::<D::Projector>(),
+            viewer: type_name::<D::Viewer>(),
+            config: format!("{:?}", self.engine.config),
+        }
+    }
+
+    /// The retry limits currently deciding when failed messages are
+    /// dead-lettered.
+    ///
+    /// Initially the policy described by [`BusConfig::max_retries`] and
+    /// [`BusConfig::context_failure`].
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.engine.retry_policy.read().unwrap().clone()
+    }
+
+    /// Replaces the retry limits of this bus and all its clones.
+    ///
+    /// Takes effect for the next failure of any message, without a restart.
+    /// See [`RetryPolicy`].
+    pub fn set_retry_policy(&self, policy: RetryPolicy) {
+        tracing::info!(?policy, "replacing the retry policy");
+        *self.engine.retry_policy.write().unwrap() = policy;
+    }
+
+    /// A snapshot of the bus's runtime gauges.
+    ///
+    /// Operators can sample this, e.g. from a `/debug/stats` endpoint, to
+    /// see how much work the bus holds at a given moment while tuning
+    /// [`BusConfig::receive_batching`] and related settings. When a
+    /// [`BusConfig::heartbeat_interval`] and metrics observer are
+    /// configured, the same snapshot is reported to [`Metrics::on_stats`]
+    /// with every heartbeat.
+    pub fn stats(&self) -> BusStats {
+        let engine = &self.engine;
+        let buffered_projections = engine
+            .projection_batches
+            .lock()
+            .unwrap()
+            .values()
+            .map(Vec::len)
+            .sum();
+        let pending_acks = self.pending_confirmations();
+        BusStats {
+            in_flight: engine.in_flight.load(Ordering::Relaxed),
+            pending_acks,
+            expired_acks: engine.expired_acks.load(Ordering::Relaxed),
+            buffered_projections,
+            locked_command_keys: engine.command_locks.active_keys(),
+            locked_event_keys: engine.event_locks.active_keys(),
+        }
+    }
+
+    /// Answer a query using the read side's `Viewer`.
+    ///
+    /// The query runs inside a `view` tracing span that records the query
+    /// type and how long the viewer took. The same measurements are reported
+    /// to [`Metrics::on_query`] when a metrics observer is configured.
+    pub async fn view<Q: Query>(&self, query: Q) -> Result<impl View>
+    where
+        D::Viewer: Viewer<Q>,
+    {
+        let query_type = type_name::<Q>();
+        let span = tracing::info_span!("view", query = query_type, duration_ms = Empty);
+        let started = Instant::now();
+        let res = self
+            .engine
+            .viewer
+            .view(query)
+            .instrument(span.clone())
+            .await;
+        let elapsed = started.elapsed();
+        span.record("duration_ms", elapsed.as_millis() as u64);
+        if let Some(metrics) = &self.engine.config.metrics {
+            metrics.on_query(query_type, elapsed, res.is_ok());
+        }
+        res
+    }
+
+    /// Answer a query, falling back to a possibly stale view if the viewer
+    /// fails.
+    ///
+    /// Behaves like [`view`](Self::view), but when the viewer returns an
+    /// error the query is passed to [`ViewerFallback::fallback`] instead of
+    /// failing. If the fallback has no view either, fails with
+    /// [`BusError::ReadUnavailable`] carrying the viewer's error. Use
+    /// [`Served::is_stale`] to tell the caller that the data may be out of
+    /// date.
+    pub async fn view_with_fallback<Q: Query + Clone>(
+        &self,
+        query: Q,
+    ) -> Result<Served<impl View, impl View>>
+    where
+        D::Viewer: ViewerFallback<Q>,
+    {
+        let error = match self.view(query.clone()).await {
+            Ok(view) => return Ok(Served::Fresh(view)),
+            Err(e) => e,
+        };
+        tracing::warn!(
+            query = type_name::<Q>(),
+            "viewer failed, trying its fallback: {error:#}"
+        );
+        match self.engine.viewer.fallback(query).await {
+            Some(view) => Ok(Served::Stale(view)),
+            None => Err(BusError::ReadUnavailable {
+                query: type_name::<Q>(),
+                source: error,
             }
-            Err(e) => Err(e),
+            .into()),
+        }
+    }
+
+    /// Answer a query, serializing the view in the given format.
+    ///
+    /// Behaves like [`view`](Self::view), then serializes the result with
+    /// [`ContentType::serialize`]. This lets an API negotiate the response
+    /// format (e.g. from an `Accept` header, see [`ContentType::from_mime`])
+    /// in one place rather than in every caller.
+    pub async fn view_as<Q: Query>(&self, query: Q, format: ContentType) -> Result<Vec<u8>>
+    where
+        D::Viewer: Viewer<Q>,
+    {
+        let view = self.view(query).await?;
+        format.serialize(&view)
+    }
+
+    /// Look at the next message in the broker without consuming it.
+    ///
+    /// See [`MessageBroker::peek`]. The message is not handled, and remains
+    /// at the head of the queue. Useful for diagnosing what is stuck at the
+    /// front of a queue during an incident.
+    pub async fn peek_next(
+        &self,
+    ) -> Result<Option<(<D::Broker as MessageBroker>::Id, DriverEnvelope<D>)>> {
+        self.engine.broker.peek().await
+    }
+
+    /// Acknowledges a projection that was awaiting downstream confirmation.
+    ///
+    /// Resolves a projection whose [`Projector::project`] returned
+    /// [`ProjectionOutcome::Pending`] with the given token, acknowledging its
+    /// message with the broker. Fails if no projection is pending with the
+    /// token, such as when it was already confirmed or rejected.
+    pub async fn confirm(&self, token: &ConfirmationToken) -> Result<()> {
+        let id = self.take_pending(token)?;
+        self.engine.broker.ack(id).await
+    }
+
+    /// Negatively acknowledges a projection that was awaiting downstream
+    /// confirmation, so that the broker redelivers it.
+    ///
+    /// Use this when the downstream system reports that it failed to apply
+    /// the projection. Fails if no projection is pending with the token.
+    pub async fn reject(&self, token: &ConfirmationToken, reason: NackReason) -> Result<()> {
+        let id = self.take_pending(token)?;
+        let broker = &self.engine.broker;
+        match self.engine.config.nack_delay {
+            Some(delay) => broker.nack_with_delay(id, reason, delay).await,
+            None => broker.nack_with_reason(id, reason).await,
+        }
+    }
+
+    /// The number of projections awaiting downstream confirmation.
+    ///
+    /// Projections that have outlived [`BusConfig::pending_ack_ttl`] are
+    /// redelivered first, and are never counted.
+    pub fn pending_confirmations(&self) -> usize {
+        self.expire_pending_acks();
+        let now = self.engine.config.now();
+        let pending = self.engine.pending_acks.lock().unwrap();
+        pending
+            .values()
+            .filter(|(_, since)| !self.ack_expired(*since, now))
+            .count()
+    }
+
+    /// Re-applies historical projections, e.g. to rebuild a read model.
+    ///
+    /// Each projection is validated and applied in turn through the
+    /// [`Projector`], in [replay mode](crate::replay::is_replay) so that
+    /// projectors can skip side effects that are not idempotent. Replayed
+    /// projections bypass the broker, and their results are discarded:
+    /// receipts are not recorded, follow-ups are not published, and pending
+    /// outcomes are not awaited. Stops at the first projection that fails,
+    /// and returns the number of projections applied.
+    pub async fn replay(
+        &self,
+        projections: impl IntoIterator<Item = D::Projection>,
+    ) -> Result<usize> {
+        replaying(async {
+            let mut applied = 0;
+            for projection in projections {
+                if let Err(source) = self.engine.projector.validate(&projection) {
+                    return Err(BusError::ProjectionInvalid {
+                        projection: type_name::<D::Projection>(),
+                        source,
+                    }
+                    .into());
+                }
+                self.engine.projector.project(projection).await?;
+                applied += 1;
+            }
+            println!("Replayed {applied} projections.");
+            Ok(applied)
+        })
+        .await
+    }
+
+    /// Removes the broker id of the projection pending with `token`.
+    fn take_pending(&self, token: &ConfirmationToken) -> Result<<D::Broker as MessageBroker>::Id> {
+        self.expire_pending_acks();
+        self.engine
+            .pending_acks
+            .lock()
+            .unwrap()
+            .remove(token)
+            .map(|(id, _)| id)
+            .ok_or_else(|| anyhow!("no projection is pending confirmation of {:?}", token.0))
+    }
+
+    /// Whether a projection pending since `since` has outlived
+    /// [`BusConfig::pending_ack_ttl`].
+    fn ack_expired(&self, since: SystemTime, now: SystemTime) -> bool {
+        self.engine
+            .config
+            .pending_ack_ttl
+            .is_some_and(|ttl| now.duration_since(since).is_ok_and(|age| age >= ttl))
+    }
+
+    /// Negatively acknowledges the projections that have outlived
+    /// [`BusConfig::pending_ack_ttl`], so that the broker redelivers them.
+    ///
+    /// The projections are nacked on the bus's runtime. Without one, as
+    /// when called from outside any runtime, they are left for the next
+    /// call.
+    fn expire_pending_acks(&self) {
+        let config = &self.engine.config;
+        let Some(ttl) = config.pending_ack_ttl else {
+            return;
+        };
+        if config.runtime.is_none() && Handle::try_current().is_err() {
+            return;
+        }
+        let now = config.now();
+        let expired: Vec<_> = {
+            let mut pending = self.engine.pending_acks.lock().unwrap();
+            let tokens: Vec<_> = pending
+                .iter()
+                .filter(|(_, (_, since))| self.ack_expired(*since, now))
+                .map(|(token, _)| token.clone())
+                .collect();
+            tokens
+                .into_iter()
+                .filter_map(|token| pending.remove(&token).map(|(id, _)| (token, id)))
+                .collect()
         };
+        for (token, id) in expired {
+            tracing::warn!(
+                token = token.0,
+                "projection was not confirmed in time, redelivering"
+            );
+            self.engine.expired_acks.fetch_add(1, Ordering::Relaxed);
+            let broker = self.engine.broker.clone();
+            let reason = NackReason {
+                error: format!("projection was not confirmed within {ttl:?}"),
+                retryable: true,
+            };
+            let delay = config.nack_delay;
+            config.spawn(async move {
+                let nacked = match delay {
+                    Some(delay) => broker.nack_with_delay(id, reason, delay).await,
+                    None => broker.nack_with_reason(id, reason).await,
+                };
+                if let Err(e) = nacked {
+                    tracing::error!(token = token.0, "failed to nack expired projection: {e:#}");
+                }
+            });
+        }
+    }
 
-        ctx.close().await?;
-        res
+    /// Starts the message bus processing loop.
+    ///
+    /// This continuously receives messages from the message broker, routes
+    /// them to the appropriate handler (command, event, or projection), and
+    /// acknowledges them based on the result.
+    ///
+    /// If a [`BusConfig::subscription`] is configured, the broker is
+    /// subscribed to that pattern before any messages are received. Failed
+    /// messages that exceed [`BusConfig::max_retries`] are dead-lettered.
+    /// Messages carrying a deadline are handled within it, and are
+    /// dead-lettered without retry once it has passed, as are projections
+    /// rejected by [`Projector::validate`] and messages older than
+    /// [`BusConfig::max_message_age`]. Returns an error if
+    /// a factory fails with a fatal [`BusError::CreateFailed`].
+    ///
+    /// If a [`BusConfig::coordinator`] is configured, each message is
+    /// claimed before it is handled, so that several instances of the bus
+    /// process it only once between them.
+    ///
+    /// Polling brokers whose receiver ends when their queue is empty are
+    /// re-polled according to [`BusConfig::empty_poll_backoff`]. With
+    /// [`BusConfig::receive_batching`] set, messages are pulled with
+    /// [`MessageBroker::receive_batch`] and each batch is handled with the
+    /// configured concurrency. With [`BusConfig::heartbeat_interval`] set,
+    /// the loop reports that it is alive at that interval while it runs.
+    ///
+    /// This function should be run for the duration of the application
+    /// lifecycle — typically as a background task or top-level service.
+    pub async fn start(self) -> Result<()>
+    where
+        D::Handler: CommandHandler<D::Command, D>,
+        D::Policy: Policy<D::Event, D, Output = SideEffect<D::Command, D::Projection>>,
+    {
+        self.run(None, future::pending().boxed().shared()).await?;
+        Ok(())
     }
+
+    /// Runs the message bus processing loop until `stop` completes.
+    ///
+    /// Behaves like [`start`](Self::start), but stops receiving messages
+    /// once `stop` completes, e.g. on a shutdown signal. Messages already
+    /// received are settled, and buffered projections are flushed, before it
+    /// returns; no message is abandoned mid-handling.
+    pub async fn start_until(self, stop: impl Future<Output = ()> + Send + 'static) -> Result<()>
+    where
+        D::Handler: CommandHandler<D::Command, D>,
+        D::Policy: Policy<D::Event, D, Output = SideEffect<D::Command, D::Projection>>,
+    {
+        self.run(None, stop.boxed().shared()).await?;
+        Ok(())
+    }
+
+    /// Runs the message bus processing loop for a fixed number of messages.
+    ///
+    /// Behaves like [`start`](Self::start), but returns once `max_messages`
+    /// messages have been received and acknowledged, negatively
+    /// acknowledged, or dead-lettered, or earlier if the broker's receiver
+    /// ends and no [`BusConfig::empty_poll_backoff`] is set. Returns a
+    /// summary of how the messages were settled. Projections buffered by
+    /// [`BusConfig::projection_batching`] are flushed and settled before it
+    /// returns.
+    ///
+    /// This is useful for integration tests and one-shot batch jobs that
+    /// need to drain a known number of queued messages and then stop.
+    pub async fn start_bounded(self, max_messages: usize) -> Result<RunSummary>
+    where
+        D::Handler: CommandHandler<D::Command, D>,
+        D::Policy: Policy<D::Event, D, Output = SideEffect<D::Command, D::Projection>>,
+    {
+        self.run(Some(max_messages), future::pending().boxed().shared())
+            .await
+    }
+
+    /// Receives and settles messages until the receiver ends, `limit`
+    /// messages have been processed, or `stop` completes.
+    ///
+    /// With [`BusConfig::empty_poll_backoff`] set, an ended receiver is
+    /// re-opened, immediately if it yielded messages and after a growing
+    /// delay otherwise. With [`BusConfig::receive_batching`] set, an empty
+    /// batch counts as an ended receiver. Projections still buffered for
+    /// batched writes are flushed before the run returns.
+    async fn run(&self, limit: Option<usize>, stop: Stop) -> Result<RunSummary>
+    where
+        D::Handler: CommandHandler<D::Command, D>,
+        D::Policy: Policy<D::Event, D, Output = SideEffect<D::Command, D::Projection>>,
+    {
+        let mut summary = RunSummary::default();
+        if limit == Some(0) {
+            return Ok(summary);
+        }
+        if let Some(pattern) = &self.engine.config.subscription {
+            self.engine.broker.subscribe(pattern).await?;
+        }
+        let _heartbeat = self.heartbeat();
+        let _ack_expiry = self.ack_expiry();
+        let mut empty_delay = None;
+        loop {
+            let received = match self.engine.config.receive_batching {
+                Some(batching) => self.run_batch(batching, limit, &mut summary, &stop).await?,
+                None => self.run_stream(limit, &mut summary, &stop).await?,
+            };
+            if limit.is_some_and(|limit| summary.processed() >= limit) || stop.peek().is_some() {
+                break;
+            }
+            // Unlike a receiver, a non-empty batch is never the last one.
+            if received && self.engine.config.receive_batching.is_some() {
+                empty_delay = None;
+                continue;
+            }
+            let Some(backoff) = self.engine.config.empty_poll_backoff else {
+                break;
+            };
+            if received {
+                empty_delay = None;
+            } else {
+                let delay = backoff.next(empty_delay);
+                empty_delay = Some(delay);
+                let sleep = tokio::time::sleep(delay);
+                pin_mut!(sleep);
+                if let Either::Left(_) = future::select(stop.clone(), sleep).await {
+                    break;
+                }
+            }
+        }
+        self.drain_batches(&mut summary).await?;
+        Ok(summary)
+    }
+
+    /// Settles messages from the broker's receiver until it ends, `limit`
+    /// messages have been processed, or `stop` completes, returning whether
+    /// any were received.
+    async fn run_stream(
+        &self,
+        limit: Option<usize>,
+        summary: &mut RunSummary,
+        stop: &Stop,
+    ) -> Result<bool>
+    where
+        D::Handler: CommandHandler<D::Command, D>,
+        D::Policy: Policy<D::Event, D, Output = SideEffect<D::Command, D::Projection>>,
+    {
+        let stream = self.engine.broker.receiver();
+        pin_mut!(stream);
+        let mut received = false;
+        while let Either::Right((Some((id, envelope)), _)) =
+            future::select(stop.clone(), stream.next()).await
+        {
+            received = true;
+            let processed = self.process(id, envelope, BatchAck::PerMessage).await?;
+            summary.record(processed.settled);
+            self.collect_flushes(summary)?;
+            if limit.is_some_and(|limit| summary.processed() >= limit) {
+                break;
+            }
+        }
+        Ok(received)
+    }
+
+    /// Settles one batch of messages from the broker, returning whether it
+    /// held any. No batch is received once `stop` completes.
+    ///
+    /// The batch is never larger than what remains of `limit`. A fatal error
+    /// from one message is returned once the rest of the batch is settled.
+    /// With [`BatchAck::Atomic`], the successful messages are settled
+    /// together, as described there.
+    async fn run_batch(
+        &self,
+        batching: ReceiveBatching,
+        limit: Option<usize>,
+        summary: &mut RunSummary,
+        stop: &Stop,
+    ) -> Result<bool>
+    where
+        D::Handler: CommandHandler<D::Command, D>,
+        D::Policy: Policy<D::Event, D, Output = SideEffect<D::Command, D::Projection>>,
+    {
+        let max = limit.map_or(batching.max_size, |limit| {
+            batching.max_size.min(limit - summary.processed())
+        });
+        let receiving = self.engine.broker.receive_batch(max.max(1));
+        pin_mut!(receiving);
+        let batch = match future::select(stop.clone(), receiving).await {
+            Either::Left(_) => return Ok(false),
+            Either::Right((batch, _)) => batch?,
+        };
+        if batch.is_empty() {
+            return Ok(false);
+        }
+        let results: Vec<_> = stream::iter(batch)
+            .map(|(id, envelope)| async {
+                let pending = id.clone();
+                (pending, self.process(id, envelope, batching.ack).await)
+            })
+            .buffer_unordered(batching.concurrency.max(1))
+            .collect()
+            .await;
+        let atomic = batching.ack == BatchAck::Atomic;
+        let mut fatal = None;
+        let (mut pending, mut failed) = (Vec::new(), false);
+        for (id, result) in results {
+            match result {
+                Ok(result) if atomic => match result.settled {
+                    settled @ (Settled::Acked | Settled::Skipped | Settled::HandedOff) => {
+                        pending.push((id, settled));
+                    }
+                    // Failed members are left for the batch to nack.
+                    Settled::Nacked => {
+                        failed = true;
+                        pending.push((id, Settled::Nacked));
+                    }
+                    // The rest were settled on their own, or will be.
+                    settled => summary.record(settled),
+                },
+                Ok(result) => summary.record(result.settled),
+                Err(e) => {
+                    failed = true;
+                    if atomic {
+                        pending.push((id, Settled::Nacked));
+                    }
+                    fatal.get_or_insert(e);
+                }
+            }
+        }
+        if !pending.is_empty() {
+            let (ids, outcomes): (Vec<_>, Vec<_>) = pending.into_iter().unzip();
+            let broker = &self.engine.broker;
+            if failed {
+                let count = ids.len();
+                println!("Batch member failed, negatively acknowledging {count} messages.");
+                let reason = NackReason {
+                    error: "another message in the batch failed".to_owned(),
+                    retryable: true,
+                };
+                broker.nack_batch(ids, reason).await?;
+                for _ in 0..count {
+                    summary.record(Settled::Nacked);
+                }
+            } else {
+                broker.ack_batch(ids).await?;
+                for settled in outcomes {
+                    summary.record(settled);
+                }
+            }
+        }
+        self.collect_flushes(summary)?;
+        match fatal {
+            Some(e) => Err(e),
+            None => Ok(true),
+        }
+    }
+
+    /// Pull a single message from the broker, handle it, and settle it.
+    ///
+    /// An alternative to [`start`](Self::start) for environments that
+    /// process messages on invocation rather than in a long-lived loop,
+    /// such as serverless functions or cron-triggered workers. The message
+    /// is routed, acknowledged, negatively acknowledged, or dead-lettered
+    /// exactly as in `start`.
+    ///
+    /// Waits for the broker's receiver to yield a message, and returns
+    /// `None` if it ends without one. Brokers used this way should yield a
+    /// receiver that ends once the queue is empty. A projection buffered by
+    /// [`BusConfig::projection_batching`] is reported as
+    /// [`Settled::Buffered`], and settled when its batch is flushed.
+    pub async fn process_once(&self) -> Result<Option<ProcessingResult>>
+    where
+        D::Handler: CommandHandler<D::Command, D>,
+        D::Policy: Policy<D::Event, D, Output = SideEffect<D::Command, D::Projection>>,
+    {
+        if let Some(pattern) = &self.engine.config.subscription {
+            self.engine.broker.subscribe(pattern).await?;
+        }
+        let stream = self.engine.broker.receiver();
+        pin_mut!(stream);
+        match stream.next().await {
+            Some((id, envelope)) => {
+                let processed = self.process(id, envelope, BatchAck::PerMessage).await?;
+                Ok(Some(processed))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Processes a message received from the broker.
+    ///
+    /// With [`BatchAck::Atomic`], a successful message is left for its
+    /// batch to acknowledge.
+    async fn process(
+        &self,
+        id: <D::Broker as MessageBroker>::Id,
+        envelope: DriverEnvelope<D>,
+        ack: BatchAck,
+    ) -> Result<ProcessingResult>
+    where
+        D::Handler: CommandHandler<D::Command, D>,
+        D::Policy: Policy<D::Event, D, Output = SideEffect<D::Command, D::Projection>>,
+    {
+        let Envelope {
+            message: msg,
+            id: message_id,
+            causation_id,
+            correlation_id,
+            occurred_at,
+            deadline,
+            projected_inline,
+            ..
+        } = envelope;
+        let (kind, type_name) = (msg.kind(), msg.type_name());
+        let _in_flight = InFlight::enter(&self.engine.in_flight);
+        let started = Instant::now();
+        let latency = occurred_at.and_then(|at| self.engine.config.now().duration_since(at).ok());
+        if let (Some(metrics), Some(latency)) = (&self.engine.config.metrics, latency) {
+            metrics.on_queue_latency(kind, type_name, latency);
+        }
+
+        let trace_id = message_id
+            .clone()
+            .unwrap_or_else(|| self.engine.config.generate_id());
+        let correlation_id = correlation_id.unwrap_or_else(|| trace_id.clone());
+        self.record_trace(TraceEntry {
+            message_id: trace_id.clone(),
+            causation_id: causation_id.clone(),
+            correlation_id: correlation_id.clone(),
+            kind,
+            type_name,
+        });
+        let ctx = TraceContext {
+            message_id: trace_id,
+            correlation_id,
+            projected_inline,
+        };
+        let span = message_span(kind, type_name, &ctx);
+        let settling = async {
+            if let Some(stale) = self.check_age(occurred_at) {
+                println!("Message is too old to handle, dead-lettering.");
+                let failed = Failed {
+                    id,
+                    kind,
+                    type_name,
+                    max_retries: None,
+                    projection: self.failure_copy(&msg),
+                };
+                return self.fail(failed, ack, stale.into()).await;
+            }
+            self.settle(id, msg, message_id, causation_id, deadline, ack)
+                .await
+        };
+        let settled = ctx.scope(in_span(span, settling)).await;
+        if let Some(metrics) = &self.engine.config.metrics {
+            metrics.on_processing_latency(kind, type_name, started.elapsed());
+        }
+        Ok(ProcessingResult {
+            settled: settled?,
+            kind,
+            type_name,
+        })
+    }
+
+    /// The error a message that occurred at `occurred_at` fails with, if it
+    /// is older than [`BusConfig::max_message_age`].
+    fn check_age(&self, occurred_at: Option<SystemTime>) -> Option<BusError> {
+        let max_age = self.engine.config.max_message_age?;
+        let now = self.engine.config.now();
+        let age = now.duration_since(occurred_at?).ok()?;
+        (age > max_age).then_some(BusError::StaleMessage { age, max_age })
+    }
+
+    /// Handles a received message and settles it with the broker.
+    ///
+    /// Successful messages are acknowledged, unless their acknowledgement is
+    /// deferred until downstream confirmation. Failed messages are negatively
+    /// acknowledged, or dead-lettered once they have exhausted their retries.
+    /// A message failing with a fatal [`BusError::CreateFailed`] is
+    /// negatively acknowledged and the error returned, stopping the bus.
+    async fn settle(
+        &self,
+        id: <D::Broker as MessageBroker>::Id,
+        msg: DriverMessage<D>,
+        message_id: Option<String>,
+        causation_id: Option<MessageId>,
+        deadline: Option<SystemTime>,
+        ack: BatchAck,
+    ) -> Result<Settled>
+    where
+        D::Handler: CommandHandler<D::Command, D>,
+        D::Policy: Policy<D::Event, D, Output = SideEffect<D::Command, D::Projection>>,
+    {
+        let (kind, type_name) = (msg.kind(), msg.type_name());
+        let max_retries = self.engine.driver.max_retries(&msg);
+        let projection = self.failure_copy(&msg);
+        let claim = self.claim(message_id.as_deref()).await;
+        if matches!(claim, Ok(Claim::Taken)) {
+            println!("Message is claimed by another instance, skipping.");
+            if ack == BatchAck::PerMessage {
+                self.engine.broker.ack(id).await?;
+            }
+            return Ok(Settled::Skipped);
+        }
+        let handled = match claim {
+            Ok(claim) => {
+                let watchdog = self.watch_slow(kind, type_name);
+                let message_id = message_id.as_deref();
+                let handling =
+                    self.handle_message(msg, &id, message_id, causation_id, max_retries, ack);
+                let handled = match deadline {
+                    Some(deadline) => with_deadline(deadline, handling).await,
+                    None => handling.await,
+                };
+                drop(watchdog);
+                let handled = handled.or_else(|e| match e.downcast::<AckedFailure>() {
+                    Ok(AckedFailure(e)) => {
+                        tracing::error!(
+                            message_type = type_name,
+                            "failed to publish events of an acknowledged command: {e:#}"
+                        );
+                        Ok(Handled::Acked)
+                    }
+                    Err(e) => Err(e),
+                });
+                match (claim, handled) {
+                    // The claim is held until the batch is written.
+                    (Claim::Owned(lease), Ok(Handled::Buffered(key, mut batched, batching))) => {
+                        batched.lease = Some(lease);
+                        Ok(Handled::Buffered(key, batched, batching))
+                    }
+                    (Claim::Owned(lease), handled) => {
+                        lease.release(handled.is_ok()).await;
+                        handled
+                    }
+                    (_, handled) => handled,
+                }
+            }
+            Err(e) => Err(e),
+        };
+        let e = match handled {
+            Ok(handled) => {
+                println!("Handled message successfully.");
+                return match handled {
+                    Handled::Done => {
+                        if ack == BatchAck::PerMessage {
+                            self.engine.broker.ack(id).await?;
+                        }
+                        Ok(Settled::Acked)
+                    }
+                    Handled::Acked => Ok(Settled::Acked),
+                    Handled::Deferred => Ok(Settled::Deferred),
+                    Handled::Buffered(key, batched, batching) => {
+                        self.buffer_projection(key, *batched, batching);
+                        Ok(Settled::Buffered)
+                    }
+                };
+            }
+            Err(e) => e,
+        };
+        println!("Handled message unsuccessfully: {e:#?}");
+        let failed = Failed {
+            id,
+            kind,
+            type_name,
+            max_retries,
+            projection,
+        };
+        self.fail(failed, ack, e).await
+    }
+
+    /// A copy of a received projection, kept in case it permanently fails
+    /// and must be handed to the [`ProjectionErrorHandler`].
+    ///
+    /// Nothing is copied when no handler is configured.
+    fn failure_copy(&self, msg: &DriverMessage<D>) -> Option<D::Projection> {
+        match msg {
+            Message::Projection(projection) if self.engine.projection_error_handler.is_some() => {
+                Some(projection.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// Settles a message that failed with `e`.
+    ///
+    /// The message is negatively acknowledged, or dead-lettered once it has
+    /// exhausted its retries or right away if `e` is a [`BusError`] that is
+    /// not [retryable](BusError::is_retryable). An exhausted `projection` is handed to the
+    /// [`ProjectionErrorHandler`] instead, if one is configured.
+    ///
+    /// With [`BatchAck::Atomic`], a message that is to be negatively
+    /// acknowledged is left for its batch to settle, and reported as
+    /// [`Settled::Nacked`], or as the returned error if it is fatal. So is
+    /// a projection handed to the error handler, which is left for its
+    /// batch to acknowledge.
+    async fn fail(&self, failed: Failed<D>, ack: BatchAck, e: anyhow::Error) -> Result<Settled> {
+        let Failed {
+            id,
+            kind,
+            type_name,
+            max_retries,
+            projection,
+        } = failed;
+        let bus_err = bus_error(&e);
+        // A fatal factory failure is not the message's fault, so it is
+        // redelivered rather than dead-lettered, and the bus stops.
+        let fatal = matches!(
+            bus_err,
+            Some(BusError::CreateFailed {
+                retryable: false,
+                ..
+            })
+        );
+        let attempts = self.engine.broker.delivery_attempt(&id);
+        // Errors that would recur on every redelivery fail the message for
+        // good.
+        let poisoned = !fatal && bus_err.is_some_and(|e| !e.is_retryable());
+        // A projection waiting on its dependency has not really failed.
+        let pending = matches!(bus_err, Some(BusError::DependencyPending { .. }));
+        let exhausted = poisoned
+            || !fatal
+                && !pending
+                && self
+                    .max_retries_for(&e, max_retries)
+                    .is_some_and(|max| attempts > max);
+        if exhausted {
+            let details = self.engine.driver.error_details(&e);
+            if let (Some(handler), Some(projection)) =
+                (&self.engine.projection_error_handler, projection)
+            {
+                let failure = ProjectionFailure {
+                    projection,
+                    attempts,
+                    error: format!("{e:#}"),
+                    details: details.clone(),
+                };
+                match handler.handle(failure).await {
+                    Ok(()) => {
+                        println!("Handed failed {type_name} to the projection error handler.");
+                        if ack == BatchAck::PerMessage {
+                            self.engine.broker.ack(id).await?;
+                        }
+                        return Ok(Settled::HandedOff);
+                    }
+                    Err(err) => {
+                        tracing::warn!(
+                            projection = type_name,
+                            "projection error handler failed: {err:#}"
+                        );
+                    }
+                }
+            }
+            let notice = MessageDeadLettered {
+                id,
+                kind,
+                type_name,
+                attempts,
+                error: format!("{e:#}"),
+                details,
+            };
+            self.dead_letter(notice).await?;
+            return Ok(Settled::DeadLettered);
+        }
+        if ack == BatchAck::Atomic {
+            return if fatal { Err(e) } else { Ok(Settled::Nacked) };
+        }
+        let broker = &self.engine.broker;
+        let reason = NackReason {
+            error: format!("{e:#}"),
+            retryable: fatal || bus_err.is_none_or(BusError::is_retryable),
+        };
+        let delay = match pending {
+            true => Some(self.engine.config.nack_delay.unwrap_or(DEPENDENCY_DELAY)),
+            false => self.engine.config.nack_delay,
+        };
+        match delay {
+            Some(delay) => broker.nack_with_delay(id, reason, delay).await?,
+            None => broker.nack_with_reason(id, reason).await?,
+        }
+        if fatal {
+            return Err(e);
+        }
+        Ok(Settled::Nacked)
+    }
+
+    /// Claims a received message through the configured [`Coordinator`].
+    ///
+    /// Messages are not coordinated if no coordinator is configured or their
+    /// envelope has no id. A claimed message's lease is renewed until the
+    /// returned lease is released.
+    async fn claim(&self, message_id: Option<&str>) -> Result<Claim> {
+        let (Some(coordinator), Some(message_id)) = (&self.engine.config.coordinator, message_id)
+        else {
+            return Ok(Claim::Uncoordinated);
+        };
+        let lease = coordinator.lease();
+        if !coordinator.claim(message_id.to_owned(), lease).await? {
+            return Ok(Claim::Taken);
+        }
+        let renewal = {
+            let coordinator = coordinator.clone();
+            let message_id = message_id.to_owned();
+            self.engine.config.spawn(async move {
+                loop {
+                    tokio::time::sleep(lease / 2).await;
+                    if let Err(e) = coordinator.renew(message_id.clone(), lease).await {
+                        tracing::warn!(message_id, "failed to renew message claim: {e:#}");
+                    }
+                }
+            })
+        };
+        Ok(Claim::Owned(Lease {
+            coordinator: coordinator.clone(),
+            message_id: message_id.to_owned(),
+            renewal: Watchdog(renewal),
+        }))
+    }
+
+    /// Starts a watchdog reporting the message if it is still being handled
+    /// after [`BusConfig::slow_threshold`].
+    ///
+    /// The watchdog is cancelled when the returned guard is dropped.
+    fn watch_slow(&self, kind: MessageKind, type_name: &'static str) -> Option<Watchdog> {
+        let threshold = self.engine.config.slow_threshold?;
+        let metrics = self.engine.config.metrics.clone();
+        let task = self.engine.config.spawn(async move {
+            tokio::time::sleep(threshold).await;
+            tracing::warn!(
+                %kind,
+                message_type = type_name,
+                threshold_ms = threshold.as_millis() as u64,
+                "message is taking longer than its slow threshold"
+            );
+            if let Some(metrics) = metrics {
+                metrics.on_slow_message(kind, type_name, threshold);
+            }
+        });
+        Some(Watchdog(task))
+    }
+
+    /// Starts a watchdog reporting every [`BusConfig::heartbeat_interval`]
+    /// that the processing loop is alive, along with the bus's
+    /// [`stats`](Self::stats).
+    ///
+    /// The watchdog is cancelled when the returned guard is dropped.
+    fn heartbeat(&self) -> Option<Watchdog> {
+        let interval = self.engine.config.heartbeat_interval?;
+        let bus = self.clone();
+        let task = self.engine.config.spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                ticks.tick().await;
+                tracing::debug!("message bus processing loop is alive");
+                if let Some(metrics) = &bus.engine.config.metrics {
+                    metrics.on_heartbeat();
+                    metrics.on_stats(&bus.stats());
+                }
+            }
+        });
+        Some(Watchdog(task))
+    }
+
+    /// Starts a watchdog redelivering projections that outlive
+    /// [`BusConfig::pending_ack_ttl`], checking at half that interval.
+    ///
+    /// The watchdog is cancelled when the returned guard is dropped.
+    fn ack_expiry(&self) -> Option<Watchdog> {
+        let ttl = self.engine.config.pending_ack_ttl?;
+        let bus = self.clone();
+        let task = self.engine.config.spawn(async move {
+            let period = (ttl / 2).max(Duration::from_millis(1));
+            let mut ticks = tokio::time::interval(period);
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                ticks.tick().await;
+                bus.expire_pending_acks();
+            }
+        });
+        Some(Watchdog(task))
+    }
+
+    /// The number of retries allowed for a message that failed with `err`,
+    /// according to the current [`RetryPolicy`].
+    fn max_retries_for(&self, err: &anyhow::Error, message_max: Option<u32>) -> Option<u32> {
+        let category = bus_error(err).map_or("application", BusError::category);
+        let policy = self.engine.retry_policy.read().unwrap();
+        policy.max_retries_for(category, message_max)
+    }
+
+    /// Dead-letters a message that has exhausted its retries.
+    ///
+    /// The message is handed to the broker's dead-letter path, and the
+    /// driver is given the chance to turn the notice into a domain event,
+    /// which is then published back to the message bus. The message is
+    /// dead-lettered even if that event fails to publish, so the failure is
+    /// only logged.
+    async fn dead_letter(
+        &self,
+        notice: MessageDeadLettered<<D::Broker as MessageBroker>::Id>,
+    ) -> Result<()> {
+        println!(
+            "Dead-lettering {} {} after {} attempts.",
+            notice.kind, notice.type_name, notice.attempts
+        );
+        let broker = &self.engine.broker;
+        broker
+            .dead_letter_with_reason(notice.id.clone(), notice.details.clone())
+            .await?;
+        let notice_type = notice.type_name;
+        if let Some(event) = self.engine.driver.dead_lettered(notice) {
+            let message = self.envelope(Message::Event(event), None);
+            throttle(&self.engine.config, 1).await;
+            match self.engine.broker.publish(message.clone()).await {
+                Ok(()) => self.observe(&message),
+                Err(e) => tracing::warn!(
+                    message_type = notice_type,
+                    "failed to publish dead-letter event: {e:#}"
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    /// Routes an incoming message to its corresponding handler.
+    ///
+    /// This internal function dispatches commands, executes projections, or
+    /// applies event policies depending on the message variant. `id` is the
+    /// broker's id for the message and `message_id` the id of the received
+    /// envelope, if any. Returns whether the message still needs to be
+    /// acknowledged.
+    ///
+    /// Projections with a [`Projector::batch_key`] are buffered rather than
+    /// applied when [`BusConfig::projection_batching`] is set. `max_retries`
+    /// is kept with them for when their batch fails. Projections whose
+    /// [`Projector::depends_on`] dependency has not been applied for the
+    /// same `causation_id` fail with [`BusError::DependencyPending`], to be
+    /// redelivered later. With
+    /// [`BatchAck::Atomic`], commands are never acknowledged within their
+    /// transaction.
+    async fn handle_message(
+        &self,
+        msg: DriverMessage<D>,
+        id: &<D::Broker as MessageBroker>::Id,
+        message_id: Option<&str>,
+        causation_id: Option<MessageId>,
+        max_retries: Option<u32>,
+        ack: BatchAck,
+    ) -> Result<Handled<D>>
+    where
+        D::Handler: CommandHandler<D::Command, D>,
+        D::Policy: Policy<D::Event, D, Output = SideEffect<D::Command, D::Projection>>,
+    {
+        println!("Executing {}: {}", msg.kind(), msg.type_name());
+        check_deadline()?;
+        match msg {
+            Message::Command(cmd) => {
+                let received = (ack == BatchAck::PerMessage).then(|| id.clone());
+                let (_, acked) = self.execute(cmd, received).await?;
+                if acked {
+                    return Ok(Handled::Acked);
+                }
+            }
+            Message::Event(event) => {
+                self.handle_event(event, message_id).await?;
+            }
+            Message::EventBatch(events) => {
+                self.handle_event_batch(events, message_id).await?;
+            }
+            Message::Projection(projection) => {
+                if let Err(source) = self.engine.projector.validate(&projection) {
+                    return Err(BusError::ProjectionInvalid {
+                        projection: type_name::<D::Projection>(),
+                        source,
+                    }
+                    .into());
+                }
+                let checkpoint = self.check_dependency(&projection, causation_id).await?;
+                let batching = self.engine.config.projection_batching;
+                let key = batching.and_then(|_| self.engine.projector.batch_key(&projection));
+                if let (Some(batching), Some(key)) = (batching, key) {
+                    let batched = Batched {
+                        id: id.clone(),
+                        message_id: message_id.map(ToOwned::to_owned),
+                        max_retries,
+                        checkpoint,
+                        projection,
+                        lease: None,
+                    };
+                    return Ok(Handled::Buffered(key, Box::new(batched), batching));
+                }
+                let result = self.engine.projector.project(projection).await?;
+                let finished = self.finish_projection(result, message_id, checkpoint);
+                if let Some(token) = finished.await? {
+                    self.defer_ack(token, id.clone())?;
+                    return Ok(Handled::Deferred);
+                }
+            }
+        };
+        Ok(Handled::Done)
+    }
+
+    /// Checks that the projection's [`Projector::depends_on`] dependency
+    /// has been applied for the event that caused it.
+    ///
+    /// Returns the checkpoint to record once the projection is applied, if
+    /// it has a [`Projector::projection_id`]. Dependencies are only tracked
+    /// when [`BusConfig::projection_checkpoints`] is set and the projection
+    /// has a causation id.
+    async fn check_dependency(
+        &self,
+        projection: &D::Projection,
+        causation_id: Option<MessageId>,
+    ) -> Result<Option<(ProjectionId, MessageId)>> {
+        let (Some(checkpoints), Some(event)) =
+            (&self.engine.config.projection_checkpoints, causation_id)
+        else {
+            return Ok(None);
+        };
+        let projector = &self.engine.projector;
+        if let Some(dependency) = projector.depends_on(projection)
+            && !checkpoints.reached(&dependency, &event).await?
+        {
+            return Err(BusError::DependencyPending { dependency, event }.into());
+        }
+        Ok(projector.projection_id(projection).map(|id| (id, event)))
+    }
+
+    /// Completes a successfully applied projection.
+    ///
+    /// Records its receipt and checkpoint, if any, and publishes its
+    /// follow-ups. Returns the token to await confirmation of if the
+    /// projection is pending.
+    async fn finish_projection(
+        &self,
+        result: ProjectionResult<DriverSideEffect<D>>,
+        message_id: Option<&str>,
+        checkpoint: Option<(ProjectionId, MessageId)>,
+    ) -> Result<Option<ConfirmationToken>> {
+        if let (Some(store), Some(receipt)) = (&self.engine.config.receipt_store, result.receipt) {
+            store.record(type_name::<D::Projection>(), receipt).await?;
+        }
+        let checkpoints = &self.engine.config.projection_checkpoints;
+        if let (Some(checkpoints), Some((projection, event))) = (checkpoints, checkpoint) {
+            checkpoints.record(&projection, &event).await?;
+        }
+        let follow_ups = result.follow_ups.into_iter().map(|side_effect| {
+            Some(match side_effect {
+                SideEffect::Command(cmd) => Message::Command(cmd),
+                SideEffect::Projection(proj) => Message::Projection(proj),
+            })
+        });
+        self.publish_side_effects(follow_ups, message_id).await?;
+        match result.outcome {
+            ProjectionOutcome::Complete => Ok(None),
+            ProjectionOutcome::Pending(token) => Ok(Some(token)),
+        }
+    }
+
+    /// Defers acknowledging a projection message until `token` is confirmed.
+    ///
+    /// Fails if another projection is already pending with the same token,
+    /// which keeps its place.
+    fn defer_ack(
+        &self,
+        token: ConfirmationToken,
+        id: <D::Broker as MessageBroker>::Id,
+    ) -> Result<()> {
+        self.expire_pending_acks();
+        let since = self.engine.config.now();
+        let mut pending = self.engine.pending_acks.lock().unwrap();
+        if pending.contains_key(&token) {
+            return Err(anyhow!(
+                "a projection is already pending confirmation of {:?}",
+                token.0
+            ));
+        }
+        pending.insert(token, (id, since));
+        Ok(())
+    }
+
+    /// Buffers a projection in the batch for `key`.
+    ///
+    /// The batch is flushed right away once it is full. Otherwise, the
+    /// first projection of a batch starts a timer that flushes it after
+    /// [`ProjectionBatching::max_delay`].
+    fn buffer_projection(&self, key: String, batched: Batched<D>, batching: ProjectionBatching) {
+        let len = {
+            let mut batches = self.engine.projection_batches.lock().unwrap();
+            let batch = batches.entry(key.clone()).or_default();
+            batch.push(batched);
+            batch.len()
+        };
+        if len >= batching.max_size {
+            self.spawn_flush(key);
+        } else if len == 1 {
+            let bus = self.clone();
+            self.engine.config.spawn(async move {
+                tokio::time::sleep(batching.max_delay).await;
+                bus.spawn_flush(key);
+            });
+        }
+    }
+
+    /// Flushes the batch for `key` on one of the bus's tasks.
+    ///
+    /// The flush runs apart from the message that filled the batch, so
+    /// that message's deadline does not cut it short. Its outcome is
+    /// collected by [`collect_flushes`](Self::collect_flushes).
+    fn spawn_flush(&self, key: String) {
+        let bus = self.clone();
+        let flush = async move { bus.flush_batch(&key).await };
+        let mut flushes = self.engine.batch_flushes.lock().unwrap();
+        match &self.engine.config.runtime {
+            Some(runtime) => flushes.spawn_on(flush, runtime),
+            None => flushes.spawn(flush),
+        };
+    }
+
+    /// Records the flushes of projection batches that have finished in
+    /// `summary`, returning the first error any of them failed with.
+    fn collect_flushes(&self, summary: &mut RunSummary) -> Result<()> {
+        let mut flushes = self.engine.batch_flushes.lock().unwrap();
+        while let Some(flushed) = flushes.try_join_next() {
+            summary.record_flushed(flushed??);
+        }
+        Ok(())
+    }
+
+    /// Flushes every buffered projection batch and waits for all flushes
+    /// to finish, recording them in `summary`.
+    ///
+    /// Bounded runs end with this, so that no message they received is
+    /// left buffered. The first error a flush failed with is returned once
+    /// every flush has finished.
+    async fn drain_batches(&self, summary: &mut RunSummary) -> Result<()> {
+        let keys: Vec<_> = (self.engine.projection_batches.lock().unwrap())
+            .keys()
+            .cloned()
+            .collect();
+        for key in keys {
+            self.spawn_flush(key);
+        }
+        let mut flushes = mem::take(&mut *self.engine.batch_flushes.lock().unwrap());
+        let mut first_err = None;
+        while let Some(flushed) = flushes.join_next().await {
+            match flushed
+                .map_err(anyhow::Error::from)
+                .and_then(|flushed| flushed)
+            {
+                Ok(flushed) => summary.record_flushed(flushed),
+                Err(e) => {
+                    first_err.get_or_insert(e);
+                }
+            }
+        }
+        first_err.map_or(Ok(()), Err)
+    }
+
+    /// Applies the buffered projections for `key` with
+    /// [`Projector::project_batch`] and settles their messages, returning
+    /// how each was settled.
+    ///
+    /// If the batch fails, each of its messages is negatively acknowledged
+    /// or dead-lettered like any failed message, classified by the error
+    /// the batch failed with. Every message is settled even if settling
+    /// one of them fails; the first such error is returned.
+    async fn flush_batch(&self, key: &str) -> Result<Vec<Settled>> {
+        let batch = self.engine.projection_batches.lock().unwrap().remove(key);
+        let Some(batch) = batch.filter(|batch| !batch.is_empty()) else {
+            return Ok(Vec::new());
+        };
+        println!("Applying batch of {} projections for {key}.", batch.len());
+        let (projections, batch): (Vec<_>, Vec<_>) = batch
+            .into_iter()
+            .map(|batched| {
+                let Batched {
+                    id,
+                    message_id,
+                    max_retries,
+                    checkpoint,
+                    projection,
+                    lease,
+                } = batched;
+                (projection, (id, message_id, max_retries, checkpoint, lease))
+            })
+            .unzip();
+        let count = projections.len();
+        let mut copies = match self.engine.projection_error_handler {
+            Some(_) => projections.clone(),
+            None => Vec::new(),
+        }
+        .into_iter();
+        let results = match self.engine.projector.project_batch(projections).await {
+            Ok(results) if results.len() == count => Ok(results),
+            Ok(results) => Err(Arc::new(anyhow!(
+                "project_batch returned {} results for {count} projections",
+                results.len()
+            ))),
+            Err(e) => Err(Arc::new(e)),
+        };
+        let (kind, type_name) = (MessageKind::Projection, type_name::<D::Projection>());
+        let mut results = results.map(Vec::into_iter);
+        let (mut settled, mut first_err) = (Vec::with_capacity(count), None);
+        for (id, message_id, max_retries, checkpoint, lease) in batch {
+            let projection = copies.next();
+            let finished = match &mut results {
+                Ok(results) => {
+                    let result = results.next().expect("one result per projection");
+                    let finished =
+                        self.finish_projection(result, message_id.as_deref(), checkpoint);
+                    finished.await
+                }
+                Err(e) => Err(BatchFailed(e.clone()).into()),
+            };
+            let finished = finished.and_then(|token| match token {
+                Some(token) => self.defer_ack(token, id.clone()).map(|()| true),
+                None => Ok(false),
+            });
+            if let Some(lease) = lease {
+                lease.release(finished.is_ok()).await;
+            }
+            let outcome = match finished {
+                Ok(true) => Ok(Settled::Deferred),
+                Ok(false) => self.engine.broker.ack(id).await.map(|()| Settled::Acked),
+                Err(e) => {
+                    let failed = Failed {
+                        id,
+                        kind,
+                        type_name,
+                        max_retries,
+                        projection,
+                    };
+                    self.fail(failed, BatchAck::PerMessage, e).await
+                }
+            };
+            match outcome {
+                Ok(outcome) => settled.push(outcome),
+                Err(e) => {
+                    tracing::error!(key, "failed to settle batched projection: {e:#}");
+                    first_err.get_or_insert(e);
+                }
+            }
+        }
+        first_err.map_or(Ok(settled), Err)
+    }
+
+    /// Handles a domain event by applying the associated policy.
+    ///
+    /// A new `PolicyContext` is created for the event and enriched with any
+    /// shared reference data, then the policy is applied using the event
+    /// data. The resulting side effects (commands and/or projections) are
+    /// then published back to the message bus, retrying only those that
+    /// fail to publish.
+    /// Inline projections were already applied by the originating command
+    /// and are not published again.
+    ///
+    /// If the event has a `message_id`, each side effect is given the id
+    /// `{message_id}/{index}`, where `index` is its position in the policy's
+    /// output, and the side effects are published with
+    /// [`MessageBroker::publish_idempotent`]. Re-running the policy for a
+    /// redelivered event therefore yields the same ids.
+    ///
+    /// Events the policy does not subscribe to are settled before a context
+    /// is created, as are events duplicating the content of a recently
+    /// handled event under [`DedupBy::ContentHash`]. Events sharing a
+    /// [`MessageBusDriver::ordering_key`] are handled one at a time.
+    ///
+    /// If the context cannot be created, [`BusError::PolicyContextUnavailable`]
+    /// is returned so the failure can be handled according to
+    /// [`BusConfig::context_failure`]. Once created, the context is always
+    /// closed, even if enrichment or the policy fails or panics.
+    async fn handle_event(&self, event: D::Event, message_id: Option<&str>) -> Result<()>
+    where
+        D::Policy: Policy<D::Event, D, Output = SideEffect<D::Command, D::Projection>>,
+    {
+        if !self.engine.policy.subscribes_to(&event) {
+            return self.unmatched_event();
+        }
+        let content_hash = self.content_hash(&event);
+        if let Some((hash, window)) = content_hash
+            && self.engine.recent_events.contains(hash, window)
+        {
+            tracing::debug!(hash, "event duplicates a recently handled event, skipping");
+            return Ok(());
+        }
+        let _guard = match self.engine.driver.ordering_key(&event) {
+            Some(key) => Some(self.engine.event_locks.lock(key).await),
+            None => None,
+        };
+        let mut ctx = self.create_policy_context().await?;
+        let applied = AssertUnwindSafe(async {
+            self.enrich(&mut ctx, &event).await?;
+            let side_effects = self.engine.policy.apply(&mut ctx, event).await?;
+            Ok::<_, anyhow::Error>(side_effects.into_policy_output())
+        })
+        .catch_unwind()
+        .await;
+        let closed = ctx.close().await;
+        let side_effects = match applied {
+            Ok(applied) => {
+                closed?;
+                applied?
+            }
+            Err(panic) => panic::resume_unwind(panic),
+        };
+
+        check_deadline()?;
+        self.publish_policy_output(side_effects, message_id).await?;
+        if let Some((hash, window)) = content_hash {
+            self.engine.recent_events.insert(hash, window);
+        }
+        Ok(())
+    }
+
+    /// The hash of an event's content and how long to remember it, under
+    /// [`DedupBy::ContentHash`].
+    fn content_hash(&self, event: &D::Event) -> Option<(u64, Duration)> {
+        let DedupBy::ContentHash { window } = self.engine.config.dedup_by else {
+            return None;
+        };
+        let hasher = self.engine.content_hasher.as_ref()?;
+        Some((hasher.hash(event)?, window))
+    }
+
+    /// Handles the events committed together by one command.
+    ///
+    /// Each event the policy does not subscribe to is first settled as an
+    /// unmatched event, and the batch is done if no event is left. Unless the
+    /// policy
+    /// [applies batches](Policy::applies_batches), each subscribed event is
+    /// then handled as in [`handle_event`](Self::handle_event), its side
+    /// effects' ids scoped by its position in the batch.
+    ///
+    /// Otherwise the whole batch is applied with [`Policy::apply_batch`],
+    /// once every ordering key of its events is locked. It is skipped under
+    /// [`DedupBy::ContentHash`] if every one of its events duplicates a
+    /// recently handled event.
+    async fn handle_event_batch(
+        &self,
+        events: Vec<D::Event>,
+        message_id: Option<&str>,
+    ) -> Result<()>
+    where
+        D::Policy: Policy<D::Event, D, Output = SideEffect<D::Command, D::Projection>>,
+    {
+        let policy = &self.engine.policy;
+        for _ in events.iter().filter(|event| !policy.subscribes_to(event)) {
+            self.unmatched_event()?;
+        }
+        if !events.iter().any(|event| policy.subscribes_to(event)) {
+            return Ok(());
+        }
+        if !policy.applies_batches() {
+            let subscribed =
+                (events.into_iter().enumerate()).filter(|(_, event)| policy.subscribes_to(event));
+            for (index, event) in subscribed {
+                let message_id = message_id.map(|parent| format!("{parent}/{index}"));
+                self.handle_event(event, message_id.as_deref()).await?;
+            }
+            return Ok(());
+        }
+        let hashes = (events.iter())
+            .map(|event| self.content_hash(event))
+            .collect::<Option<Vec<_>>>()
+            .unwrap_or_default();
+        if !hashes.is_empty()
+            && (hashes.iter())
+                .all(|&(hash, window)| self.engine.recent_events.contains(hash, window))
+        {
+            tracing::debug!("event batch duplicates recently handled events, skipping");
+            return Ok(());
+        }
+        // Keys are locked in order, so that batches sharing several keys
+        // cannot deadlock.
+        let mut keys = events
+            .iter()
+            .filter_map(|event| self.engine.driver.ordering_key(event))
+            .collect::<Vec<_>>();
+        keys.sort();
+        keys.dedup();
+        let mut guards = Vec::with_capacity(keys.len());
+        for key in keys {
+            guards.push(self.engine.event_locks.lock(key).await);
+        }
+        let mut ctx = self.create_policy_context().await?;
+        let applied = AssertUnwindSafe(async {
+            for event in events.iter().filter(|event| policy.subscribes_to(event)) {
+                self.enrich(&mut ctx, event).await?;
+            }
+            policy.apply_batch(&mut ctx, events).await
+        })
+        .catch_unwind()
+        .await;
+        let closed = ctx.close().await;
+        let side_effects = match applied {
+            Ok(applied) => {
+                closed?;
+                applied?
+            }
+            Err(panic) => panic::resume_unwind(panic),
+        };
+
+        check_deadline()?;
+        self.publish_policy_output(side_effects, message_id).await?;
+        for (hash, window) in hashes {
+            self.engine.recent_events.insert(hash, window);
+        }
+        Ok(())
+    }
+
+    /// Settles an event the policy does not subscribe to, as configured by
+    /// [`BusConfig::unmatched_events`].
+    fn unmatched_event(&self) -> Result<()> {
+        let event = type_name::<D::Event>();
+        match self.engine.config.unmatched_events {
+            UnmatchedEventStrategy::Ignore => {
+                println!("Policy is not subscribed to event, skipping.");
+                Ok(())
+            }
+            UnmatchedEventStrategy::Log => {
+                tracing::warn!(event, "no policy subscribes to event");
+                Ok(())
+            }
+            UnmatchedEventStrategy::DeadLetter => Err(BusError::UnmatchedEvent { event }.into()),
+        }
+    }
+
+    /// Creates the context a policy is applied with.
+    ///
+    /// Transient factory failures, and errors that are not a [`BusError`],
+    /// are reported as [`BusError::PolicyContextUnavailable`]. Any other
+    /// `BusError` is returned unchanged.
+    async fn create_policy_context(&self) -> Result<D::PolicyContext> {
+        match self.engine.policy_context_factory.create().await {
+            Ok(ctx) => Ok(ctx),
+            Err(err) => Err(match err.into() {
+                BusError::CreateFailed {
+                    retryable: true,
+                    source,
+                }
+                | BusError::Application { source } => BusError::PolicyContextUnavailable { source },
+                err => err,
+            }
+            .into()),
+        }
+    }
+
+    /// Enriches the policy context for an event with the driver's
+    /// [`EventEnricher`], if any.
+    async fn enrich(&self, ctx: &mut D::PolicyContext, event: &D::Event) -> Result<()> {
+        match &self.engine.event_enricher {
+            Some(enricher) => enricher.enrich(ctx, event).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Publishes the side effects a policy derived from a received message.
+    ///
+    /// Inline projections are skipped if the message's were already applied
+    /// by [`dispatch_consistent`](Self::dispatch_consistent), and published
+    /// like any projection otherwise.
+    async fn publish_policy_output(
+        &self,
+        side_effects: Vec<SideEffect<D::Command, D::Projection>>,
+        message_id: Option<&str>,
+    ) -> Result<()> {
+        let projected_inline = TraceContext::current().is_some_and(|ctx| ctx.projected_inline);
+        let messages = side_effects
+            .into_iter()
+            .map(|side_effect| match side_effect {
+                SideEffect::Command(cmd) => Some(Message::Command(cmd)),
+                SideEffect::Projection(proj)
+                    if projected_inline && self.engine.projector.is_inline(&proj) =>
+                {
+                    None
+                }
+                SideEffect::Projection(proj) => Some(Message::Projection(proj)),
+            });
+        self.publish_side_effects(messages, message_id).await
+    }
+
+    /// Publishes the side effects derived from a received message.
+    ///
+    /// `None` entries are skipped but keep their index. If the received
+    /// message has a `message_id`, each side effect is given the id
+    /// `{message_id}/{index}` and published with
+    /// [`MessageBroker::publish_idempotent`], so redeliveries of the message
+    /// yield the same ids.
+    ///
+    /// If the broker [supports transactions](MessageBroker::supports_transactions),
+    /// the side effects are instead published all or nothing with
+    /// [`MessageBroker::publish_transactional`], without retrying, so a
+    /// failed publish leaves nothing behind when the message is handled
+    /// again. Side effects with ids are then published with
+    /// [`MessageBroker::publish_transactional_idempotent`], so a message
+    /// handled again after its transaction committed does not duplicate
+    /// them either. Side effects including a routed command are never published
+    /// transactionally, since each routed command is published on its own.
+    async fn publish_side_effects(
+        &self,
+        messages: impl IntoIterator<Item = Option<DriverMessage<D>>>,
+        message_id: Option<&str>,
+    ) -> Result<()> {
+        let messages = messages
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, message)| {
+                let id = message_id.map(|parent| format!("{parent}/{index}"));
+                Some(self.envelope(message?, id))
+            })
+            .collect::<Vec<_>>();
+        if messages.is_empty() {
+            return Ok(());
+        }
+        let num_events = messages.len();
+        let config = &self.engine.config;
+        let broker = &self.engine.broker;
+        let routed = messages.iter().any(|message| self.route(message).is_some());
+        let idempotent = message_id.is_some();
+        if broker.supports_transactions() && !routed {
+            throttle(config, num_events).await;
+            if idempotent {
+                let messages = messages.clone();
+                broker.publish_transactional_idempotent(messages).await?;
+            } else {
+                broker.publish_transactional(messages.clone()).await?;
+            }
+            messages.iter().for_each(|message| self.observe(message));
+            println!("Published {num_events} events in a transaction.");
+            return Ok(());
+        }
+        let observe = |message: &_| self.observe(message);
+        let route = |message: &_| self.route(message);
+        publish_with_retries(
+            &self.engine.broker,
+            messages,
+            idempotent,
+            config,
+            observe,
+            route,
+        )
+        .await
+        .map_err(|(_, err)| err)?;
+        println!("Published {num_events} events.");
+        Ok(())
+    }
+}
+
+/// Notifies each observer of a published message.
+fn notify<D: MessageBusDriver>(
+    observers: &[Arc<dyn PublishObserver<D>>],
+    envelope: &DriverEnvelope<D>,
+) {
+    for observer in observers {
+        observer.observed(&envelope.message);
+    }
+}
+
+/// Creates a factory's output, converting its error into a [`BusError`].
+async fn create<F: Factory>(factory: &F) -> Result<F::Output> {
+    factory.create().await.map_err(|err| {
+        let err: BusError = err.into();
+        err.into_anyhow()
+    })
+}
+
+/// Publishes a batch of messages, retrying only the ones that fail.
+///
+/// Uses [`MessageBroker::publish_batch_detailed`] so that a partial failure
+/// only re-publishes the failed messages, or
+/// [`MessageBroker::publish_idempotent`] if `idempotent` is set. If
+/// [`BusConfig::publish_concurrency`] is set, each message is instead
+/// published on its own, that many at a time, with
+/// [`MessageBroker::publish`] or, if `idempotent` is set,
+/// [`MessageBroker::publish_idempotent`]. Every
+/// attempt is paced by [`BusConfig::max_publish_rate`], if set.
+///
+/// Failed messages are retried up to [`BusConfig::publish_retries`] times,
+/// doubling [`BusConfig::publish_backoff`] between attempts, up to
+/// [`MAX_PUBLISH_BACKOFF`]. If some messages still fail, they are returned
+/// along with the last error.
+///
+/// Messages for which `route` returns a key are published individually with
+/// [`MessageBroker::publish_to`] instead, or
+/// [`MessageBroker::publish_to_idempotent`] if `idempotent` is set.
+///
+/// `observe` is called with each message once it has been published.
+async fn publish_with_retries<B>(
+    broker: &B,
+    mut messages: Vec<B::Message>,
+    idempotent: bool,
+    config: &BusConfig,
+    observe: impl Fn(&B::Message),
+    route: impl Fn(&B::Message) -> Option<RouteKey>,
+) -> Result<(), (Vec<B::Message>, PublishError)>
+where
+    B: MessageBroker,
+    B::Message: Clone,
+{
+    let retries = config.publish_retries;
+    let mut backoff = config.publish_backoff;
+    let mut attempt = 0;
+    loop {
+        let mut routed = Vec::new();
+        let mut unrouted = Vec::new();
+        for (index, message) in messages.iter().enumerate() {
+            match route(message) {
+                Some(key) => routed.push((index, key, message.clone())),
+                None => unrouted.push(index),
+            }
+        }
+        let batch = unrouted
+            .iter()
+            .map(|&index| messages[index].clone())
+            .collect();
+        let mut results: Vec<_> = messages.iter().map(|_| Ok(())).collect();
+        let batch_results = publish_attempt(broker, batch, idempotent, config).await;
+        for (index, result) in unrouted.into_iter().zip(batch_results) {
+            results[index] = result;
+        }
+        let routed_results: Vec<_> = stream::iter(routed)
+            .map(|(index, key, message)| async move {
+                throttle(config, 1).await;
+                let published = if idempotent {
+                    broker.publish_to_idempotent(&key, message).await
+                } else {
+                    broker.publish_to(&key, message).await
+                };
+                (index, published.map_err(PublishError::new))
+            })
+            .buffered(config.publish_concurrency.unwrap_or(1).max(1))
+            .collect()
+            .await;
+        for (index, result) in routed_results {
+            results[index] = result;
+        }
+        let mut last_err = None;
+        let failed = messages
+            .into_iter()
+            .zip(results)
+            .filter_map(|(message, result)| {
+                let Err(err) = result else {
+                    observe(&message);
+                    return None;
+                };
+                last_err = Some(err);
+                Some(message)
+            })
+            .collect::<Vec<_>>();
+        let Some(err) = last_err else {
+            return Ok(());
+        };
+        if attempt >= retries {
+            return Err((failed, err));
+        }
+        attempt += 1;
+        println!(
+            "Failed to publish {} message(s), retrying ({attempt}/{retries}): {err}",
+            failed.len()
+        );
+        tokio::time::sleep(backoff).await;
+        backoff = backoff.saturating_mul(2).min(MAX_PUBLISH_BACKOFF);
+        messages = failed;
+    }
+}
+
+/// Makes one attempt at publishing a batch of messages, as described in
+/// [`publish_with_retries`], returning the outcome of each message.
+async fn publish_attempt<B: MessageBroker>(
+    broker: &B,
+    messages: Vec<B::Message>,
+    idempotent: bool,
+    config: &BusConfig,
+) -> Vec<Result<(), PublishError>> {
+    if messages.is_empty() {
+        return Vec::new();
+    }
+    match config.publish_concurrency {
+        Some(limit) => {
+            stream::iter(messages)
+                .map(|message| async {
+                    throttle(config, 1).await;
+                    if !idempotent {
+                        return broker.publish(message).await.map_err(PublishError::new);
+                    }
+                    let mut published = broker.publish_idempotent(vec![message]).await;
+                    published.pop().unwrap_or(Ok(()))
+                })
+                .buffered(limit.max(1))
+                .collect()
+                .await
+        }
+        None if idempotent => {
+            throttle(config, messages.len()).await;
+            broker.publish_idempotent(messages).await
+        }
+        None => {
+            throttle(config, messages.len()).await;
+            broker.publish_batch_detailed(messages).await
+        }
+    }
+}
+
+/// How a handled message remains to be acknowledged.
+enum Handled<D: MessageBusDriver> {
+    /// The message should be acknowledged now.
+    Done,
+
+    /// The message was acknowledged within its command's transaction.
+    Acked,
+
+    /// The message's acknowledgement awaits downstream confirmation.
+    Deferred,
+
+    /// The projection is to be buffered in the batch for the given key,
+    /// and settled once the batch is written.
+    Buffered(String, Box<Batched<D>>, ProjectionBatching),
+}
+
+/// A failure after a received command was acknowledged within its unit of
+/// work's transaction, typically a [`BusError::PublishAfterCommit`].
+///
+/// The message must not be settled again, so the failure is only logged.
+#[derive(Debug)]
+struct AckedFailure(anyhow::Error);
+
+impl fmt::Display for AckedFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#}", self.0)
+    }
+}
+
+impl StdError for AckedFailure {}
+
+/// The error a projection batch failed with, shared by each of its
+/// messages.
+#[derive(Debug)]
+struct BatchFailed(Arc<anyhow::Error>);
+
+impl fmt::Display for BatchFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("projection batch failed")
+    }
+}
+
+impl StdError for BatchFailed {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(self.0.as_ref().as_ref())
+    }
+}
+
+/// The [`BusError`] a message failed with, if any, looking through the
+/// failure of its projection batch.
+fn bus_error(e: &anyhow::Error) -> Option<&BusError> {
+    e.downcast_ref::<BusError>()
+        .or_else(|| e.downcast_ref::<BatchFailed>()?.0.downcast_ref())
+}
+
+/// The result of claiming a received message through a [`Coordinator`].
+enum Claim {
+    /// No coordinator is configured, or the message has no id.
+    Uncoordinated,
+
+    /// This instance claimed the message.
+    Owned(Lease),
+
+    /// Another instance holds or completed the message.
+    Taken,
+}
+
+/// This instance's claim on a message, renewed until released.
+pub(crate) struct Lease {
+    coordinator: Arc<dyn Coordinator>,
+    message_id: MessageId,
+    renewal: Watchdog,
+}
+
+impl Lease {
+    /// Stops renewing the claim and releases it.
+    ///
+    /// A failed release is only logged: the claim then lapses when its
+    /// lease expires.
+    async fn release(self, completed: bool) {
+        let Lease {
+            coordinator,
+            message_id,
+            renewal,
+        } = self;
+        drop(renewal);
+        if let Err(e) = coordinator.release(message_id.clone(), completed).await {
+            tracing::warn!(message_id, "failed to release message claim: {e:#}");
+        }
+    }
+}
+
+/// Waits for [`BusConfig::max_publish_rate`], if set, to allow publishing
+/// `messages` messages.
+async fn throttle(config: &BusConfig, messages: usize) {
+    if let Some(limiter) = &config.max_publish_rate {
+        limiter.acquire(messages).await;
+    }
+}
+
+/// Counts a received message as in flight until dropped.
+struct InFlight(Arc<AtomicUsize>);
+
+impl InFlight {
+    fn enter(counter: &Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Self(counter.clone())
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Aborts a watchdog task, such as a slow-message warning or a lease
+/// renewal, when dropped.
+struct Watchdog(JoinHandle<()>);
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Whether a submitted command was executed or queued.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DispatchStatus {
+    /// The command was executed and its unit of work committed.
+    Executed,
+
+    /// The command was published to the broker for later execution.
+    Queued,
+}
+
+/// The outcome of a dispatched or submitted command.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DispatchResult<T> {
+    /// Whether the command was executed or queued.
+    pub status: DispatchStatus,
+
+    /// Whether the command was executed and changed state.
+    ///
+    /// This is `false` when the handler returned
+    /// [`HandlerOutcome::NoChange`], letting an API answer with
+    /// `304 Not Modified`-like semantics, and for queued commands, whose
+    /// outcome is not yet known.
+    pub changed: bool,
+
+    /// The handler's result, if the command was executed and returned one.
+    pub result: Option<T>,
+}
+
+impl<T> DispatchResult<T> {
+    /// The result of a command that was executed with the given outcome.
+    fn executed(outcome: HandlerOutcome<T>) -> Self {
+        Self {
+            status: DispatchStatus::Executed,
+            changed: outcome.is_changed(),
+            result: outcome.into_result(),
+        }
+    }
+}
+
+/// A received message that failed, awaiting settlement by
+/// [`MessageBus::fail`].
+struct Failed<D: MessageBusDriver> {
+    /// The broker's id for the message.
+    id: <D::Broker as MessageBroker>::Id,
+
+    /// The kind of the message.
+    kind: MessageKind,
+
+    /// The type name of the message's payload.
+    type_name: &'static str,
+
+    /// The number of retries allowed for the message, if limited.
+    max_retries: Option<u32>,
+
+    /// A copy of the message, if it is a projection to hand to the
+    /// [`ProjectionErrorHandler`] once it permanently fails.
+    projection: Option<D::Projection>,
+}
+
+/// How a received message was settled with the broker.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Settled {
+    /// The message was handled successfully and acknowledged.
+    Acked,
+
+    /// The message failed and was negatively acknowledged for retry.
+    Nacked,
+
+    /// The message failed after exhausting its retries and was
+    /// dead-lettered.
+    DeadLettered,
+
+    /// The projection failed after exhausting its retries and was handed
+    /// to the driver's [`ProjectionErrorHandler`], then acknowledged.
+    HandedOff,
+
+    /// The message was handled successfully, but its acknowledgement awaits
+    /// downstream confirmation through [`MessageBus::confirm`].
+    Deferred,
+
+    /// The message was claimed by another instance through the
+    /// [`BusConfig::coordinator`], and was acknowledged without being
+    /// handled.
+    Skipped,
+
+    /// The projection was buffered for a batched write, and is settled
+    /// once its batch is flushed.
+    Buffered,
+}
+
+/// The outcome of a message processed by [`MessageBus::process_once`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProcessingResult {
+    /// The kind of the message.
+    pub kind: MessageKind,
+
+    /// The type name of the message, as reported by
+    /// [`Message::type_name`](crate::message::Message::type_name).
+    pub type_name: &'static str,
+
+    /// How the message was settled with the broker.
+    pub settled: Settled,
+}
+
+/// A summary of the messages processed by [`MessageBus::start_bounded`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RunSummary {
+    /// Messages that were handled successfully and acknowledged.
+    pub acked: usize,
+
+    /// Messages that failed and were negatively acknowledged for retry.
+    pub nacked: usize,
+
+    /// Messages that failed after exhausting their retries and were
+    /// dead-lettered.
+    pub dead_lettered: usize,
+
+    /// Projections that failed after exhausting their retries and were
+    /// handed to the driver's [`ProjectionErrorHandler`].
+    pub handed_off: usize,
+
+    /// Messages that were handled successfully, but whose acknowledgement
+    /// awaits downstream confirmation.
+    pub deferred: usize,
+
+    /// Messages that were claimed by another instance and skipped.
+    pub skipped: usize,
+
+    /// Projections buffered for batched writes that are not yet flushed.
+    buffered: usize,
+}
+
+impl RunSummary {
+    /// Counts a message settled in the given way.
+    fn record(&mut self, settled: Settled) {
+        match settled {
+            Settled::Acked => self.acked += 1,
+            Settled::Nacked => self.nacked += 1,
+            Settled::DeadLettered => self.dead_lettered += 1,
+            Settled::HandedOff => self.handed_off += 1,
+            Settled::Deferred => self.deferred += 1,
+            Settled::Skipped => self.skipped += 1,
+            Settled::Buffered => self.buffered += 1,
+        }
+    }
+
+    /// Counts the buffered projections of a flushed batch as settled.
+    fn record_flushed(&mut self, flushed: Vec<Settled>) {
+        for settled in flushed {
+            self.buffered = self.buffered.saturating_sub(1);
+            self.record(settled);
+        }
+    }
+
+    /// The total number of messages processed.
+    ///
+    /// Buffered projections count as processed, so that a bounded run
+    /// stops receiving once it reaches its limit, but are only reported as
+    /// settled once their batch is flushed.
+    pub fn processed(&self) -> usize {
+        self.acked
+            + self.nacked
+            + self.dead_lettered
+            + self.handed_off
+            + self.deferred
+            + self.skipped
+            + self.buffered
+    }
+}
+
+/// Diagnostic metadata describing how a [`MessageBus`] was wired.
+///
+/// Returned by [`MessageBus::describe`]. Every field is the type name of the
+/// corresponding driver type or component, as reported by
+/// [`std::any::type_name`]; the exact format is not guaranteed to be stable.
+#[derive(Clone, Debug, Serialize)]
+pub struct BusDescription {
+    /// The message bus driver.
+    pub driver: &'static str,
+
+    /// The driver's command type.
+    pub command: &'static str,
+
+    /// The driver's event type.
+    pub event: &'static str,
+
+    /// The driver's projection type.
+    pub projection: &'static str,
+
+    /// The message broker.
+    pub broker: &'static str,
+
+    /// The unit of work.
+    pub unit_of_work: &'static str,
+
+    /// The policy context.
+    pub policy_context: &'static str,
+
+    /// The command handler.
+    pub handler: &'static str,
+
+    /// The policy.
+    pub policy: &'static str,
+
+    /// The projector.
+    pub projector: &'static str,
+
+    /// The viewer.
+    pub viewer: &'static str,
+
+    /// A summary of the runtime configuration.
+    pub config: String,
+}
+
+/// A snapshot of a [`MessageBus`]'s runtime gauges.
+///
+/// Returned by [`MessageBus::stats`]. Each gauge counts work held by this
+/// bus instance and its clones at the moment of the snapshot.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct BusStats {
+    /// Received messages currently being handled or settled.
+    pub in_flight: usize,
+
+    /// Handled projection messages whose acknowledgement awaits downstream
+    /// confirmation through [`MessageBus::confirm`].
+    pub pending_acks: usize,
+
+    /// Projections redelivered since the bus was built because they were
+    /// not confirmed within [`BusConfig::pending_ack_ttl`].
+    pub expired_acks: usize,
+
+    /// Received projections buffered for a batched write (see
+    /// [`BusConfig::projection_batching`]).
+    pub buffered_projections: usize,
+
+    /// Concurrency keys currently held or awaited by commands (see
+    /// [`CommandHandler::concurrency_key`]).
+    pub locked_command_keys: usize,
+
+    /// Ordering keys currently held or awaited by events (see
+    /// [`MessageBusDriver::ordering_key`]).
+    pub locked_event_keys: usize,
 }
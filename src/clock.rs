@@ -0,0 +1,71 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+/// A source of the current wall-clock time.
+///
+/// `Clock` abstracts over `SystemTime::now()` so that time-dependent domain
+/// logic (TTLs, scheduling, expiry) can be exercised deterministically. A
+/// clock is exposed to command handlers through the [`UnitOfWork`] and to
+/// policies through the [`PolicyContext`], both of which default to the
+/// real [`SystemClock`].
+///
+/// [`UnitOfWork`]: crate::uow::UnitOfWork
+/// [`PolicyContext`]: crate::policy::PolicyContext
+pub trait Clock: Send + Sync {
+    /// Returns the current time according to this clock.
+    fn now(&self) -> SystemTime;
+}
+
+/// A [`Clock`] backed by the operating system's real time.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A [`Clock`] that only moves when told to.
+///
+/// `ManualClock` is intended for tests. Clones share the same underlying
+/// time, so a test can hand a clone to its unit of work or policy context
+/// and advance it from the outside.
+#[derive(Clone, Debug)]
+pub struct ManualClock {
+    now: Arc<Mutex<SystemTime>>,
+}
+
+impl ManualClock {
+    /// Creates a new clock frozen at the given instant.
+    pub fn new(start: SystemTime) -> Self {
+        Self {
+            now: Arc::new(Mutex::new(start)),
+        }
+    }
+
+    /// Moves the clock forward by the given duration.
+    pub fn advance(&self, by: Duration) {
+        let mut now = self.now.lock().unwrap_or_else(|e| e.into_inner());
+        *now += by;
+    }
+
+    /// Sets the clock to the given instant.
+    pub fn set(&self, to: SystemTime) {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner()) = to;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new(SystemTime::UNIX_EPOCH)
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
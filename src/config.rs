@@ -0,0 +1,752 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use tokio::{runtime::Handle, task::JoinHandle};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    coordinator::Coordinator,
+    id::{IdGenerator, MessageId, UuidV4Generator},
+    metrics::Metrics,
+    projector::{ProjectionCheckpoints, ReceiptStore},
+    rate::RateLimiter,
+    trace::TraceSink,
+};
+
+/// Runtime configuration for a message bus.
+///
+/// `BusConfig` collects the tunable, infrastructure-level behavior of the
+/// message bus that is independent of your domain types. It is supplied by
+/// the driver through [`MessageBusDriver::config`], which returns the
+/// default configuration unless overridden.
+///
+/// ```rust,ignore
+/// impl MessageBusDriver for MyDriver {
+///     // ...
+///
+///     fn config(&self) -> BusConfig {
+///         BusConfig::default().with_subscription("order.*")
+///     }
+/// }
+/// ```
+///
+/// [`MessageBusDriver::config`]: crate::driver::MessageBusDriver::config
+#[derive(Clone, Default)]
+pub struct BusConfig {
+    /// An optional subject pattern the broker should subscribe to.
+    ///
+    /// When set, the bus calls [`MessageBroker::subscribe`] with this
+    /// pattern before it starts receiving messages. When unset, the broker
+    /// consumes from whatever fixed queue it was constructed with.
+    ///
+    /// [`MessageBroker::subscribe`]: crate::broker::MessageBroker::subscribe
+    pub subscription: Option<String>,
+
+    /// An optional backoff for polling brokers whose receiver ends when
+    /// their queue is empty.
+    ///
+    /// When set, the bus re-opens the broker's receiver each time it ends
+    /// instead of stopping, sleeping between consecutive empty polls with a
+    /// delay that grows from [`PollBackoff::min`] to [`PollBackoff::max`].
+    /// The delay resets once a message is received. When unset, the bus
+    /// stops once the receiver ends.
+    pub empty_poll_backoff: Option<PollBackoff>,
+
+    /// An optional batch size and concurrency for receiving messages.
+    ///
+    /// When set, the bus pulls messages with
+    /// [`MessageBroker::receive_batch`] instead of reading the broker's
+    /// receiver one message at a time, and handles up to
+    /// [`ReceiveBatching::concurrency`] messages of each batch at once. When
+    /// unset, messages are received from the stream and handled serially.
+    ///
+    /// [`ReceiveBatching::ack`] decides whether the messages of a batch are
+    /// acknowledged individually or all together.
+    ///
+    /// [`MessageBroker::receive_batch`]: crate::broker::MessageBroker::receive_batch
+    pub receive_batching: Option<ReceiveBatching>,
+
+    /// An optional delay enforced before a failed message is redelivered.
+    ///
+    /// When set, the bus negatively acknowledges failed messages through
+    /// [`MessageBroker::nack_with_delay`], preventing tight retry loops on
+    /// brokers that requeue immediately. When unset, failed messages are
+    /// passed to [`MessageBroker::nack_with_reason`] as-is.
+    ///
+    /// [`MessageBroker::nack_with_reason`]: crate::broker::MessageBroker::nack_with_reason
+    /// [`MessageBroker::nack_with_delay`]: crate::broker::MessageBroker::nack_with_delay
+    pub nack_delay: Option<Duration>,
+
+    /// How long a projection may await downstream confirmation.
+    ///
+    /// Projections whose [`Projector::project`] returned
+    /// [`ProjectionOutcome::Pending`] and that are neither confirmed nor
+    /// rejected within this time are negatively acknowledged, so that the
+    /// broker redelivers them, and counted in
+    /// [`BusStats::expired_acks`]. They are checked for while the bus's
+    /// processing loop runs, at half this interval, and whenever its stats
+    /// or pending confirmations are read. Like other failed messages, they
+    /// are redelivered after [`nack_delay`](Self::nack_delay), if set. When unset, they await confirmation for
+    /// as long as the bus runs.
+    ///
+    /// [`Projector::project`]: crate::projector::Projector::project
+    /// [`ProjectionOutcome::Pending`]: crate::projector::ProjectionOutcome::Pending
+    /// [`BusStats::expired_acks`]: crate::bus::BusStats::expired_acks
+    pub pending_ack_ttl: Option<Duration>,
+
+    /// An optional store for receipts returned by projectors.
+    ///
+    /// When set, any [`ProjectionResult::receipt`] returned from a projection
+    /// is recorded before the projection message is acknowledged.
+    ///
+    /// [`ProjectionResult::receipt`]: crate::projector::ProjectionResult::receipt
+    pub receipt_store: Option<Arc<dyn ReceiptStore>>,
+
+    /// An optional store of projection checkpoints.
+    ///
+    /// When set, projections are applied in the order declared by
+    /// [`Projector::depends_on`], and a checkpoint is recorded for each
+    /// applied projection with a [`Projector::projection_id`]. When unset,
+    /// dependencies are ignored.
+    ///
+    /// [`Projector::depends_on`]: crate::projector::Projector::depends_on
+    /// [`Projector::projection_id`]: crate::projector::Projector::projection_id
+    pub projection_checkpoints: Option<Arc<dyn ProjectionCheckpoints>>,
+
+    /// The maximum number of times a failed message is retried.
+    ///
+    /// When set, a message that fails on a delivery attempt beyond this
+    /// number of retries is dead-lettered instead of negatively
+    /// acknowledged. When unset, failed messages are always retried.
+    ///
+    /// Drivers can override this per message with
+    /// [`MessageBusDriver::max_retries`](crate::driver::MessageBusDriver::max_retries).
+    /// This is the initial [`RetryPolicy`] of the bus, which can be changed
+    /// at runtime.
+    pub max_retries: Option<u32>,
+
+    /// An optional limit on the age of the messages the bus handles.
+    ///
+    /// When set, a received message whose envelope
+    /// [occurred](crate::message::Envelope::occurred_at) longer ago than
+    /// this is dead-lettered with [`BusError::StaleMessage`] before it is
+    /// handled, enforcing a freshness bound on all processed work. Messages
+    /// without an occurrence time are always handled. When unset, messages
+    /// are handled however old they are. Ages are measured with the
+    /// configured [`clock`](Self::clock).
+    ///
+    /// [`BusError::StaleMessage`]: crate::error::BusError::StaleMessage
+    pub max_message_age: Option<Duration>,
+
+    /// An optional clock the bus reads the current time from.
+    ///
+    /// Defaults to [`SystemClock`] when unset. Tests can supply a
+    /// [`ManualClock`](crate::clock::ManualClock) to control time-based
+    /// behavior such as [`max_message_age`](Self::max_message_age).
+    pub clock: Option<Arc<dyn Clock>>,
+
+    /// How failures to create a policy context are retried.
+    ///
+    /// Like `max_retries`, this seeds the initial [`RetryPolicy`] of the
+    /// bus. Defaults to [`ContextFailurePolicy::Retry`].
+    pub context_failure: ContextFailurePolicy,
+
+    /// How events that the driver's policy does not subscribe to are
+    /// handled.
+    ///
+    /// This applies to each such event in a [`Message::EventBatch`] too; a
+    /// dead-lettered one fails the whole batch.
+    ///
+    /// See [`Policy::subscribes_to`](crate::policy::Policy::subscribes_to).
+    /// Defaults to [`UnmatchedEventStrategy::Ignore`].
+    ///
+    /// [`Message::EventBatch`]: crate::message::Message::EventBatch
+    pub unmatched_events: UnmatchedEventStrategy,
+
+    /// How received events are recognized as duplicates.
+    ///
+    /// Defaults to [`DedupBy::IdOnly`].
+    pub dedup_by: DedupBy,
+
+    /// Whether the events committed by one command are published together.
+    ///
+    /// When set, a unit of work that commits more than one event publishes
+    /// them as a single [`Message::EventBatch`], and policies see the whole
+    /// set through [`Policy::apply_batch`]. Defaults to `false`, publishing
+    /// each event on its own.
+    ///
+    /// [`Message::EventBatch`]: crate::message::Message::EventBatch
+    /// [`Policy::apply_batch`]: crate::policy::Policy::apply_batch
+    pub batch_committed_events: bool,
+
+    /// How many times to retry publishing events after a successful commit.
+    ///
+    /// Defaults to `0`, meaning a failed publish is not retried before
+    /// falling back to
+    /// [`MessageBusDriver::store_unpublished`](crate::driver::MessageBusDriver::store_unpublished).
+    pub publish_retries: u32,
+
+    /// The delay before the first post-commit publish retry.
+    ///
+    /// The delay doubles after each subsequent retry, up to a minute.
+    pub publish_backoff: Duration,
+
+    /// An optional limit on concurrent publishes when publishing a batch.
+    ///
+    /// When set, the bus publishes each message of a batch (such as the
+    /// side effects of a policy) with its own
+    /// [`MessageBroker::publish`] call, running up to this many at once,
+    /// instead of calling the broker's batch methods. Set this for brokers
+    /// whose `publish` is a network call per message and that have no
+    /// efficient native batch. Messages with derived ids are still
+    /// deduplicated, each published with its own
+    /// [`MessageBroker::publish_idempotent`] call.
+    ///
+    /// [`MessageBroker::publish`]: crate::broker::MessageBroker::publish
+    /// [`MessageBroker::publish_idempotent`]: crate::broker::MessageBroker::publish_idempotent
+    pub publish_concurrency: Option<usize>,
+
+    /// An optional limit on the rate at which the bus publishes messages.
+    ///
+    /// When set, every publish waits for the [`RateLimiter`] to allow it,
+    /// protecting a shared broker from being saturated by a single runaway
+    /// policy. When unset, publishes are not limited.
+    pub max_publish_rate: Option<RateLimiter>,
+
+    /// An optional policy for batching projections that share a
+    /// [`Projector::batch_key`].
+    ///
+    /// When unset, every projection is applied on its own.
+    ///
+    /// [`Projector::batch_key`]: crate::projector::Projector::batch_key
+    pub projection_batching: Option<ProjectionBatching>,
+
+    /// An optional observer notified of runtime measurements.
+    pub metrics: Option<Arc<dyn Metrics>>,
+
+    /// An optional soft limit on how long a received message may take.
+    ///
+    /// When set, a message still being handled after this long is reported
+    /// with a `warn` log and [`Metrics::on_slow_message`], but is left to
+    /// finish. This gives early warning of degradation without cancelling
+    /// any work.
+    ///
+    /// [`Metrics::on_slow_message`]: crate::metrics::Metrics::on_slow_message
+    pub slow_threshold: Option<Duration>,
+
+    /// An optional interval at which the processing loop reports that it is
+    /// alive.
+    ///
+    /// When set, the bus emits a `debug` log and calls
+    /// [`Metrics::on_heartbeat`] at this interval for as long as
+    /// [`MessageBus::start`] runs, including while no messages arrive.
+    ///
+    /// [`Metrics::on_heartbeat`]: crate::metrics::Metrics::on_heartbeat
+    /// [`MessageBus::start`]: crate::bus::MessageBus::start
+    pub heartbeat_interval: Option<Duration>,
+
+    /// An optional generator for the ids of published messages.
+    ///
+    /// Defaults to [`UuidV4Generator`] when unset.
+    pub id_generator: Option<Arc<dyn IdGenerator>>,
+
+    /// An optional sink recording the causation of processed messages.
+    pub trace_sink: Option<Arc<dyn TraceSink>>,
+
+    /// An optional runtime on which the bus spawns its internal tasks.
+    ///
+    /// When set, background work such as post-commit publishes and
+    /// slow-message watchdogs runs on this runtime, isolating it from the
+    /// rest of the application (e.g. an HTTP server). When unset, tasks are
+    /// spawned on the ambient Tokio runtime.
+    pub runtime: Option<Handle>,
+
+    /// An optional coordinator ensuring each message is processed by only
+    /// one of several bus instances.
+    pub coordinator: Option<Arc<dyn Coordinator>>,
+}
+
+impl BusConfig {
+    /// Subscribe the bus to the given subject pattern (e.g. `order.*`).
+    pub fn with_subscription(mut self, pattern: impl Into<String>) -> Self {
+        self.subscription = Some(pattern.into());
+        self
+    }
+
+    /// Re-poll the broker when its receiver ends, backing off from `min` to
+    /// `max` while it stays empty.
+    pub fn with_empty_poll_backoff(mut self, min: Duration, max: Duration) -> Self {
+        self.empty_poll_backoff = Some(PollBackoff { min, max });
+        self
+    }
+
+    /// Receive up to `max_size` messages at a time, handling up to
+    /// `concurrency` of them at once.
+    pub fn with_receive_batching(mut self, max_size: usize, concurrency: usize) -> Self {
+        self.receive_batching = Some(ReceiveBatching {
+            max_size,
+            concurrency,
+            ack: BatchAck::PerMessage,
+        });
+        self
+    }
+
+    /// Receive up to `max_size` messages at a time, handling up to
+    /// `concurrency` of them at once, and acknowledge each batch only if
+    /// every message in it succeeded.
+    ///
+    /// See [`BatchAck::Atomic`].
+    pub fn with_atomic_receive_batching(mut self, max_size: usize, concurrency: usize) -> Self {
+        self.receive_batching = Some(ReceiveBatching {
+            max_size,
+            concurrency,
+            ack: BatchAck::Atomic,
+        });
+        self
+    }
+
+    /// Delay redelivery of failed messages by at least the given duration.
+    pub fn with_nack_delay(mut self, delay: Duration) -> Self {
+        self.nack_delay = Some(delay);
+        self
+    }
+
+    /// Redeliver projections that are not confirmed within `ttl`.
+    pub fn with_pending_ack_ttl(mut self, ttl: Duration) -> Self {
+        self.pending_ack_ttl = Some(ttl);
+        self
+    }
+
+    /// Dead-letter failed messages after the given number of retries.
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = Some(max_retries);
+        self
+    }
+
+    /// Dead-letter received messages older than `max_age` without handling
+    /// them.
+    pub fn with_max_message_age(mut self, max_age: Duration) -> Self {
+        self.max_message_age = Some(max_age);
+        self
+    }
+
+    /// Read the current time from the given clock.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    /// Handle policy context creation failures with the given policy.
+    pub fn with_context_failure(mut self, policy: ContextFailurePolicy) -> Self {
+        self.context_failure = policy;
+        self
+    }
+
+    /// Handle events the policy does not subscribe to with the given
+    /// strategy.
+    pub fn with_unmatched_events(mut self, strategy: UnmatchedEventStrategy) -> Self {
+        self.unmatched_events = strategy;
+        self
+    }
+
+    /// Recognize duplicate events with the given strategy.
+    pub fn with_dedup_by(mut self, dedup_by: DedupBy) -> Self {
+        self.dedup_by = dedup_by;
+        self
+    }
+
+    /// Publish the events committed by one command as a single batch.
+    pub fn with_batched_committed_events(mut self) -> Self {
+        self.batch_committed_events = true;
+        self
+    }
+
+    /// Retry failed post-commit publishes with exponential backoff.
+    pub fn with_publish_retries(mut self, retries: u32, backoff: Duration) -> Self {
+        self.publish_retries = retries;
+        self.publish_backoff = backoff;
+        self
+    }
+
+    /// Publish batch members individually, up to `limit` at a time.
+    pub fn with_publish_concurrency(mut self, limit: usize) -> Self {
+        self.publish_concurrency = Some(limit);
+        self
+    }
+
+    /// Publish at most `per_second` messages per second.
+    pub fn with_max_publish_rate(mut self, per_second: u32) -> Self {
+        self.max_publish_rate = Some(RateLimiter::new(per_second));
+        self
+    }
+
+    /// Batch projections sharing a key, up to `max_size` at a time or for
+    /// at most `max_delay`.
+    pub fn with_projection_batching(mut self, max_size: usize, max_delay: Duration) -> Self {
+        self.projection_batching = Some(ProjectionBatching {
+            max_size,
+            max_delay,
+        });
+        self
+    }
+
+    /// Report runtime measurements to the given metrics observer.
+    pub fn with_metrics(mut self, metrics: impl Metrics + 'static) -> Self {
+        self.metrics = Some(Arc::new(metrics));
+        self
+    }
+
+    /// Warn about received messages still being handled after `threshold`.
+    pub fn with_slow_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_threshold = Some(threshold);
+        self
+    }
+
+    /// Report that the processing loop is alive every `interval`.
+    pub fn with_heartbeat_interval(mut self, interval: Duration) -> Self {
+        self.heartbeat_interval = Some(interval);
+        self
+    }
+
+    /// Stamp published messages with ids from the given generator.
+    pub fn with_id_generator(mut self, generator: impl IdGenerator + 'static) -> Self {
+        self.id_generator = Some(Arc::new(generator));
+        self
+    }
+
+    /// Record the causation of processed messages to the given sink.
+    pub fn with_trace_sink(mut self, sink: impl TraceSink + 'static) -> Self {
+        self.trace_sink = Some(Arc::new(sink));
+        self
+    }
+
+    /// Spawn the bus's internal tasks on the given runtime.
+    pub fn with_runtime(mut self, runtime: Handle) -> Self {
+        self.runtime = Some(runtime);
+        self
+    }
+
+    /// Claim messages through the given coordinator before handling them.
+    pub fn with_coordinator(mut self, coordinator: impl Coordinator + 'static) -> Self {
+        self.coordinator = Some(Arc::new(coordinator));
+        self
+    }
+
+    /// Spawns an internal task on the configured runtime, or on the ambient
+    /// runtime if none is configured.
+    pub(crate) fn spawn<F>(&self, task: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        match &self.runtime {
+            Some(runtime) => runtime.spawn(task),
+            None => tokio::spawn(task),
+        }
+    }
+
+    /// Generates a message id with the configured [`IdGenerator`].
+    pub fn generate_id(&self) -> MessageId {
+        match &self.id_generator {
+            Some(generator) => generator.generate(),
+            None => UuidV4Generator.generate(),
+        }
+    }
+
+    /// The current time according to the configured [`Clock`].
+    pub fn now(&self) -> SystemTime {
+        match &self.clock {
+            Some(clock) => clock.now(),
+            None => SystemClock.now(),
+        }
+    }
+
+    /// Record projection receipts to the given store.
+    pub fn with_receipt_store(mut self, store: impl ReceiptStore + 'static) -> Self {
+        self.receipt_store = Some(Arc::new(store));
+        self
+    }
+
+    /// Order dependent projections using the given checkpoint store.
+    pub fn with_projection_checkpoints(
+        mut self,
+        checkpoints: impl ProjectionCheckpoints + 'static,
+    ) -> Self {
+        self.projection_checkpoints = Some(Arc::new(checkpoints));
+        self
+    }
+}
+
+impl fmt::Debug for BusConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BusConfig")
+            .field("subscription", &self.subscription)
+            .field("empty_poll_backoff", &self.empty_poll_backoff)
+            .field("receive_batching", &self.receive_batching)
+            .field("nack_delay", &self.nack_delay)
+            .field("pending_ack_ttl", &self.pending_ack_ttl)
+            .field("receipt_store", &self.receipt_store.is_some())
+            .field(
+                "projection_checkpoints",
+                &self.projection_checkpoints.is_some(),
+            )
+            .field("max_retries", &self.max_retries)
+            .field("max_message_age", &self.max_message_age)
+            .field("clock", &self.clock.is_some())
+            .field("context_failure", &self.context_failure)
+            .field("unmatched_events", &self.unmatched_events)
+            .field("dedup_by", &self.dedup_by)
+            .field("batch_committed_events", &self.batch_committed_events)
+            .field("publish_retries", &self.publish_retries)
+            .field("publish_backoff", &self.publish_backoff)
+            .field("publish_concurrency", &self.publish_concurrency)
+            .field(
+                "max_publish_rate",
+                &self.max_publish_rate.as_ref().map(RateLimiter::per_second),
+            )
+            .field("projection_batching", &self.projection_batching)
+            .field("metrics", &self.metrics.is_some())
+            .field("slow_threshold", &self.slow_threshold)
+            .field("heartbeat_interval", &self.heartbeat_interval)
+            .field("id_generator", &self.id_generator.is_some())
+            .field("trace_sink", &self.trace_sink.is_some())
+            .field("runtime", &self.runtime.is_some())
+            .field("coordinator", &self.coordinator.is_some())
+            .finish()
+    }
+}
+
+/// How long the bus waits before re-polling an empty broker.
+///
+/// The delay starts at `min` and doubles after each consecutive poll that
+/// yields no messages, up to `max`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PollBackoff {
+    /// The delay after the first empty poll.
+    pub min: Duration,
+
+    /// The longest delay between empty polls.
+    pub max: Duration,
+}
+
+impl PollBackoff {
+    /// The delay after an empty poll, given the delay after the previous
+    /// one, if it was also empty.
+    pub fn next(&self, previous: Option<Duration>) -> Duration {
+        previous
+            .map_or(self.min, |delay| delay.saturating_mul(2))
+            .min(self.max)
+    }
+}
+
+/// How many messages the bus receives and handles at a time.
+///
+/// A concurrency of `1` handles each batch's messages serially, in the
+/// order the broker delivered them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReceiveBatching {
+    /// The most messages requested from the broker at once.
+    pub max_size: usize,
+
+    /// The most messages of a batch handled at the same time.
+    pub concurrency: usize,
+
+    /// How the messages of a batch are acknowledged.
+    pub ack: BatchAck,
+}
+
+/// How the messages of a received batch are acknowledged.
+///
+/// See [`BusConfig::receive_batching`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BatchAck {
+    /// Each message is acknowledged, negatively acknowledged, or
+    /// dead-lettered on its own, as soon as it is handled.
+    #[default]
+    PerMessage,
+
+    /// The batch is acknowledged with [`MessageBroker::ack_batch`] only if
+    /// every message in it succeeded. Otherwise every message that
+    /// succeeded is negatively acknowledged with
+    /// [`MessageBroker::nack_batch`], so the whole batch is redelivered.
+    ///
+    /// This suits consumers that checkpoint by offset and cannot
+    /// acknowledge part of a batch. Since successful messages may be
+    /// redelivered, their handling must be idempotent. A failed message is
+    /// still dead-lettered once it exhausts its retries, so that it cannot
+    /// hold back its batch forever. Commands are never acknowledged within
+    /// their unit of work's transaction (see
+    /// [`MessageBusDriver::ack_in_transaction`]). Messages skipped because
+    /// another instance claimed them, and projections handed to the
+    /// driver's error handler, are settled with the batch. Messages whose
+    /// acknowledgement is deferred, projections buffered for a batched
+    /// write, and dead-lettered messages are settled on their own, and do
+    /// not fail the batch.
+    ///
+    /// [`MessageBroker::ack_batch`]: crate::broker::MessageBroker::ack_batch
+    /// [`MessageBroker::nack_batch`]: crate::broker::MessageBroker::nack_batch
+    /// [`MessageBusDriver::ack_in_transaction`]: crate::driver::MessageBusDriver::ack_in_transaction
+    Atomic,
+}
+
+/// When buffered projections sharing a batch key are applied.
+///
+/// A batch is applied once it holds `max_size` projections, or `max_delay`
+/// after its first projection was received, whichever comes first. Buffered
+/// projections are acknowledged after their batch is applied, so a bus that
+/// stops with projections buffered has them redelivered. Batches are
+/// applied on the bus's own tasks, outside any message's deadline, and a
+/// bounded run applies the batches it buffered before it returns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProjectionBatching {
+    /// The number of projections that triggers applying a batch.
+    pub max_size: usize,
+
+    /// The longest a projection waits in a batch before it is applied.
+    pub max_delay: Duration,
+}
+
+/// How the message bus handles a failure to create a `PolicyContext`.
+///
+/// Context creation usually fails because of a transient infrastructure
+/// issue (e.g. an exhausted connection pool). Retrying is often correct,
+/// but an outage that outlasts the broker's redelivery can otherwise cause
+/// endless retries.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ContextFailurePolicy {
+    /// Retry the event like any other failure, subject to
+    /// [`BusConfig::max_retries`].
+    #[default]
+    Retry,
+
+    /// Dead-letter the event once context creation has failed on more than
+    /// the given number of retries.
+    DeadLetterAfter(u32),
+}
+
+/// The retry limits deciding when a failed message is dead-lettered.
+///
+/// A message bus starts with the policy described by its [`BusConfig`],
+/// and can have it replaced at runtime with
+/// [`MessageBus::set_retry_policy`], e.g. from an admin endpoint that
+/// tightens the limits to stop a retry storm during an incident:
+///
+/// ```rust,ignore
+/// let policy = bus.retry_policy().with_category_limit("application", Some(0));
+/// bus.set_retry_policy(policy);
+/// ```
+///
+/// A message failing with an error whose category has a limit follows that
+/// limit. Otherwise it follows its own limit from
+/// [`MessageBusDriver::max_retries`], if any, and then `max_retries`.
+/// A bus error's category is its [`BusError::category`], and every other
+/// error's is `"application"`.
+///
+/// [`MessageBus::set_retry_policy`]: crate::bus::MessageBus::set_retry_policy
+/// [`MessageBusDriver::max_retries`]: crate::driver::MessageBusDriver::max_retries
+/// [`BusError::category`]: crate::error::BusError::category
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The number of retries after which a failed message is dead-lettered,
+    /// or `None` to always retry.
+    pub max_retries: Option<u32>,
+
+    /// Limits for errors of specific categories, overriding every other
+    /// limit. A `None` limit always retries errors of its category.
+    pub category_limits: BTreeMap<String, Option<u32>>,
+}
+
+impl RetryPolicy {
+    /// Sets the number of retries for errors without a category limit.
+    pub fn with_max_retries(mut self, max_retries: Option<u32>) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Sets the number of retries for errors of a category.
+    pub fn with_category_limit(
+        mut self,
+        category: impl Into<String>,
+        max_retries: Option<u32>,
+    ) -> Self {
+        self.category_limits.insert(category.into(), max_retries);
+        self
+    }
+
+    /// The number of retries allowed for a message whose own limit is
+    /// `message_max` and which failed with an error of `category`.
+    pub fn max_retries_for(&self, category: &str, message_max: Option<u32>) -> Option<u32> {
+        match self.category_limits.get(category) {
+            Some(&limit) => limit,
+            None => message_max.or(self.max_retries),
+        }
+    }
+}
+
+/// The policy described by [`BusConfig::max_retries`] and
+/// [`BusConfig::context_failure`].
+impl From<&BusConfig> for RetryPolicy {
+    fn from(config: &BusConfig) -> Self {
+        let policy = RetryPolicy::default().with_max_retries(config.max_retries);
+        match config.context_failure {
+            ContextFailurePolicy::Retry => policy,
+            ContextFailurePolicy::DeadLetterAfter(max) => {
+                policy.with_category_limit("policy_context_unavailable", Some(max))
+            }
+        }
+    }
+}
+
+/// How the message bus recognizes a received event as a duplicate.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DedupBy {
+    /// Only by the id of its envelope, through the configured
+    /// [`BusConfig::coordinator`] and
+    /// [`MessageBroker::publish_idempotent`].
+    ///
+    /// [`MessageBroker::publish_idempotent`]: crate::broker::MessageBroker::publish_idempotent
+    #[default]
+    IdOnly,
+
+    /// Also by its content: an event whose hash by the driver's
+    /// [`MessageBusDriver::content_hasher`] matches that of an event handled
+    /// successfully within the last `window` is acknowledged without
+    /// applying the policy. Creating a message bus with this set panics if
+    /// the driver has no content hasher.
+    ///
+    /// This catches a producer that re-emits the same logical event under
+    /// a new id, at the cost of hashing every event. Hashes are remembered
+    /// by each process, so duplicates handled by different instances, or
+    /// at the same time, are not caught.
+    ///
+    /// [`MessageBusDriver::content_hasher`]: crate::driver::MessageBusDriver::content_hasher
+    ContentHash {
+        /// How long a handled event's hash is remembered.
+        window: Duration,
+    },
+}
+
+/// How the message bus handles an event that no policy subscribes to.
+///
+/// Such events are never applied, so no `PolicyContext` is created for
+/// them. Whether an event nobody reacts to is expected or a wiring mistake
+/// depends on the application, so the decision is made explicit here.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnmatchedEventStrategy {
+    /// Acknowledge the event silently.
+    #[default]
+    Ignore,
+
+    /// Acknowledge the event, logging a warning.
+    Log,
+
+    /// Dead-letter the event with [`BusError::UnmatchedEvent`] without
+    /// retrying it.
+    ///
+    /// [`BusError::UnmatchedEvent`]: crate::error::BusError::UnmatchedEvent
+    DeadLetter,
+}
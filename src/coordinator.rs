@@ -0,0 +1,48 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use futures::future::BoxFuture;
+
+use crate::id::MessageId;
+
+/// Coordinates message processing across several instances of a bus.
+///
+/// Brokers with native consumer groups deliver each message to a single
+/// instance. On simpler transports every instance may receive the same
+/// message, and a `Coordinator` ensures it is still processed only once
+/// across the group. Before handling a message, the bus claims its id for a
+/// lease, renews the lease while the message is being handled, and releases
+/// it once the message is settled. An instance that fails to claim a message
+/// acknowledges its copy without handling it.
+///
+/// Implementations are backed by shared state, such as Postgres advisory
+/// locks or a distributed lease in Redis. A claim on a message that was
+/// completed must keep failing for as long as copies of it may still be
+/// delivered. Only messages whose envelope carries an id are coordinated.
+///
+/// A coordinator is configured via
+/// [`BusConfig::with_coordinator`](crate::config::BusConfig::with_coordinator).
+pub trait Coordinator: Send + Sync {
+    /// Attempt to claim a message for `lease`.
+    ///
+    /// Returns `false` if another instance holds an unexpired claim on the
+    /// message, or has completed it.
+    fn claim(&self, message_id: MessageId, lease: Duration) -> BoxFuture<'_, Result<bool>>;
+
+    /// Extend this instance's claim on a message by `lease`.
+    fn renew(&self, message_id: MessageId, lease: Duration) -> BoxFuture<'_, Result<()>>;
+
+    /// Release this instance's claim on a message.
+    ///
+    /// `completed` is whether the message was handled successfully. Claims
+    /// on messages that failed should be freed so that the retry can be
+    /// claimed again.
+    fn release(&self, message_id: MessageId, completed: bool) -> BoxFuture<'_, Result<()>>;
+
+    /// How long a claim lasts before it must be renewed.
+    ///
+    /// The bus renews claims at half this interval. Defaults to 30 seconds.
+    fn lease(&self) -> Duration {
+        Duration::from_secs(30)
+    }
+}
@@ -0,0 +1,38 @@
+use crate::{error::ErrorDetails, message::MessageKind};
+
+/// A notice that a message was dead-lettered after exhausting its retries.
+///
+/// When a message fails and its delivery attempt exceeds
+/// [`BusConfig::max_retries`], the message bus dead-letters it through
+/// [`MessageBroker::dead_letter`] and passes this notice to
+/// [`MessageBusDriver::dead_lettered`]. If the driver maps the notice to one
+/// of its events, that event is published back to the broker so policies can
+/// react to the failure (e.g. raising an alert or issuing a compensating
+/// command).
+///
+/// [`BusConfig::max_retries`]: crate::config::BusConfig::max_retries
+/// [`MessageBroker::dead_letter`]: crate::broker::MessageBroker::dead_letter
+/// [`MessageBusDriver::dead_lettered`]: crate::driver::MessageBusDriver::dead_lettered
+#[derive(Clone, Debug)]
+pub struct MessageDeadLettered<Id> {
+    /// The broker identifier of the dead-lettered message.
+    pub id: Id,
+
+    /// The kind of the dead-lettered message.
+    pub kind: MessageKind,
+
+    /// The type name of the dead-lettered message's payload.
+    pub type_name: &'static str,
+
+    /// The number of times the message was delivered before giving up.
+    pub attempts: u32,
+
+    /// The error from the final failed attempt.
+    pub error: String,
+
+    /// The structured form of [`error`](Self::error), as classified by
+    /// [`MessageBusDriver::error_details`].
+    ///
+    /// [`MessageBusDriver::error_details`]: crate::driver::MessageBusDriver::error_details
+    pub details: ErrorDetails,
+}
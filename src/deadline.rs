@@ -0,0 +1,51 @@
+use std::time::SystemTime;
+
+use anyhow::Result;
+
+use crate::error::BusError;
+
+tokio::task_local! {
+    static DEADLINE: SystemTime;
+}
+
+/// Runs `fut` with an end-to-end deadline.
+///
+/// The deadline applies to everything the message bus does within `fut`:
+/// each phase of a dispatch checks it with [`check_deadline`], and every
+/// message published carries it in its [`Envelope`], so downstream hops
+/// (the events' policies, and the commands and projections they produce)
+/// are bound by the same deadline when they are received.
+///
+/// Typically an HTTP handler wraps its dispatch with the request's budget:
+///
+/// ```rust,ignore
+/// let deadline = SystemTime::now() + Duration::from_secs(2);
+/// with_deadline(deadline, bus.dispatch(cmd)).await?;
+/// ```
+///
+/// Deadlines are absolute wall-clock times, so hosts exchanging messages
+/// should have reasonably synchronized clocks.
+///
+/// [`Envelope`]: crate::message::Envelope
+pub async fn with_deadline<F: Future>(deadline: SystemTime, fut: F) -> F::Output {
+    DEADLINE.scope(deadline, fut).await
+}
+
+/// The deadline of the current task, if it runs within [`with_deadline`].
+pub fn current_deadline() -> Option<SystemTime> {
+    DEADLINE.try_with(|deadline| *deadline).ok()
+}
+
+/// Fails with [`BusError::DeadlineExceeded`] if the current task's deadline
+/// has passed.
+///
+/// The message bus calls this at each phase boundary. Long-running handlers
+/// and projectors may call it too, to abandon work early.
+pub fn check_deadline() -> Result<()> {
+    match current_deadline() {
+        Some(deadline) if SystemTime::now() >= deadline => {
+            Err(BusError::DeadlineExceeded { deadline }.into())
+        }
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,56 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Content hashes of recently handled events.
+///
+/// Each hash is remembered for a sliding window after it was last
+/// inserted, and forgotten afterwards.
+#[derive(Clone, Default)]
+pub struct RecentHashes {
+    inner: Arc<Mutex<Recent>>,
+}
+
+#[derive(Default)]
+struct Recent {
+    /// When each hash was last inserted.
+    seen: HashMap<u64, Instant>,
+
+    /// Every insertion, oldest first.
+    order: VecDeque<(Instant, u64)>,
+}
+
+impl RecentHashes {
+    /// Whether `hash` was inserted within the last `window`.
+    pub fn contains(&self, hash: u64, window: Duration) -> bool {
+        let mut recent = self.inner.lock().unwrap();
+        recent.expire(window);
+        recent.seen.contains_key(&hash)
+    }
+
+    /// Remembers `hash` for the next `window`.
+    pub fn insert(&self, hash: u64, window: Duration) {
+        let mut recent = self.inner.lock().unwrap();
+        recent.expire(window);
+        let now = Instant::now();
+        recent.seen.insert(hash, now);
+        recent.order.push_back((now, hash));
+    }
+}
+
+impl Recent {
+    /// Forgets the hashes last inserted more than `window` ago.
+    fn expire(&mut self, window: Duration) {
+        while let Some(&(at, hash)) = self.order.front() {
+            if at.elapsed() < window {
+                break;
+            }
+            self.order.pop_front();
+            if self.seen.get(&hash) == Some(&at) {
+                self.seen.remove(&hash);
+            }
+        }
+    }
+}
@@ -1,10 +1,21 @@
+use std::{future, sync::Arc};
+
+use anyhow::{Result, anyhow};
+
 use crate::{
-    broker::MessageBroker,
+    broker::{MessageBroker, RouteKey},
+    config::BusConfig,
+    dead_letter::MessageDeadLettered,
+    enricher::EventEnricher,
+    error::ErrorDetails,
     handler::{Command, CommandHandler},
-    message::{DriverMessage, DriverSideEffect},
+    idempotency::IdempotencyStore,
+    message::{DriverEnvelope, DriverMessage, DriverSideEffect},
+    observer::PublishObserver,
     policy::{Policy, PolicyContext},
-    projector::Projector,
+    projector::{ProjectionErrorHandler, Projector},
     uow::UnitOfWork,
+    view::ContentHasher,
 };
 
 pub trait Projection: Send {}
@@ -21,6 +32,15 @@ impl<T: Send> Event for T {}
 /// projections are performed. Each type acts as a plug-in point for
 /// application-specific behavior, ensuring consistent orchestration of
 /// domain interactions across command, event, and projection flows.
+///
+/// # Breaking change: message types must be `Clone`
+///
+/// [`Command`](Self::Command), [`Event`](Self::Event) and
+/// [`Projection`](Self::Projection) now require `Clone`. The bus keeps a
+/// copy of every message it publishes, so that a failed publish can be
+/// retried, stored with [`store_unpublished`](Self::store_unpublished) or
+/// dead-lettered. Drivers whose message types are not `Clone` must derive or
+/// implement it, or wrap them in an `Arc`.
 pub trait MessageBusDriver: Clone + Sized + Send + Sync + 'static {
     // Used to identify something.
     type Identifier: Send;
@@ -35,7 +55,8 @@ pub trait MessageBusDriver: Clone + Sized + Send + Sync + 'static {
     ///
     /// Commands are dispatched by the message bus and routed to the
     /// corresponding handler that will apply changes to the domain model.
-    type Command: Command;
+    /// They must be `Clone` so that a failed publish can be retried.
+    type Command: Command + Clone;
 
     /// The domain-specific `Event` type for this message bus.
     ///
@@ -47,7 +68,10 @@ pub trait MessageBusDriver: Clone + Sized + Send + Sync + 'static {
     /// Each `Event` will be applied to a `Policy` defined on the message
     /// bus. Once applied, the `Policy` will determine what downstream side
     /// effects should occur.
-    type Event: Event;
+    ///
+    /// Like commands and projections, events must be `Clone` so that a
+    /// failed publish can be retried.
+    type Event: Event + Clone;
 
     /// The domain-specific `Projection` type for this message bus.
     ///
@@ -57,8 +81,9 @@ pub trait MessageBusDriver: Clone + Sized + Send + Sync + 'static {
     /// notifications, or syncing to an external system.
     ///
     /// Each `Projection` will be processed by a `Projector` implementation,
-    /// which performs the actual infrastructure-facing update logic.
-    type Projection: Projection;
+    /// which performs the actual infrastructure-facing update logic. They
+    /// must be `Clone` so that a failed publish can be retried.
+    type Projection: Projection + Clone;
 
     /// The concrete `MessageBroker` implementation for this message bus.
     ///
@@ -66,7 +91,7 @@ pub trait MessageBusDriver: Clone + Sized + Send + Sync + 'static {
     /// bus, publishing events, and exposing methods for acknowledging
     /// success or failuire after message processing. It serves as the
     /// transport layer between your application and the message pipeline.
-    type Broker: MessageBroker<Message = DriverMessage<Self>>;
+    type Broker: MessageBroker<Message = DriverEnvelope<Self>> + 'static;
 
     /// The concrete `UnitOfWork` implementation for this message bus.
     ///
@@ -98,11 +123,210 @@ pub trait MessageBusDriver: Clone + Sized + Send + Sync + 'static {
     ///
     /// This includes operations such as updating read models, search
     /// indexes, external integrations, or emitting telemetry events.
-    type Projector: Projector<Self::Projection>;
+    type Projector: Projector<Self::Projection, DriverSideEffect<Self>>;
 
     type Handler: CommandHandler<Self::Command, Self>;
 
     type Policy: Policy<Self::Event, Self, Output = DriverSideEffect<Self>>;
 
     type Viewer: Clone + Send + Sync;
+
+    /// The runtime configuration for a message bus built from this driver.
+    ///
+    /// Called once when the message bus is constructed. Override this to
+    /// tune infrastructure-level behavior; the default is
+    /// [`BusConfig::default`].
+    fn config(&self) -> BusConfig {
+        BusConfig::default()
+    }
+
+    /// Stores events that could not be published after a successful commit.
+    ///
+    /// Called when publishing a committed unit of work's events still fails
+    /// after [`BusConfig::publish_retries`]. Override this to write the events
+    /// to a local fallback store (e.g. an outbox table) from which a relay
+    /// can publish them later. Returning `Ok` tells the bus the events are
+    /// safe and the dispatch succeeds.
+    ///
+    /// The default implementation stores nothing and returns an error, in
+    /// which case the dispatch fails with
+    /// [`BusError::PublishAfterCommit`](crate::error::BusError::PublishAfterCommit).
+    fn store_unpublished(
+        &self,
+        _events: Vec<Self::Event>,
+    ) -> impl Future<Output = Result<()>> + Send {
+        future::ready(Err(anyhow!("no fallback store for unpublished events")))
+    }
+
+    /// The maximum number of times the given message is retried.
+    ///
+    /// Override this to give message types different retry tolerances, e.g.
+    /// many retries for an important but flaky command and few for a
+    /// best-effort projection. Once a message fails beyond its limit it is
+    /// dead-lettered. Returning `None` applies
+    /// [`BusConfig::max_retries`], which is also the default.
+    fn max_retries(&self, _message: &DriverMessage<Self>) -> Option<u32> {
+        None
+    }
+
+    /// The ordering key of the given event, if any.
+    ///
+    /// Events handled by the same message bus that share an ordering key,
+    /// such as events of the same aggregate, never have their policy
+    /// applied at the same time: each waits, in the order it arrived, for
+    /// the previous one to be applied and its side effects published. This
+    /// mirrors [`CommandHandler::concurrency_key`] for the event path, so
+    /// that a single aggregate's events are never applied out of order when
+    /// events are processed concurrently, while unrelated events proceed in
+    /// parallel.
+    ///
+    /// Keys only serialize events within a single process. Defaults to
+    /// `None`, allowing the event to be handled concurrently with any other.
+    fn ordering_key(&self, _event: &Self::Event) -> Option<String> {
+        None
+    }
+
+    /// The route of the given command, if any.
+    ///
+    /// Commands published by the message bus, whether queued with
+    /// [`MessageBus::submit`](crate::bus::MessageBus::submit), split by
+    /// [`MessageBus::dispatch_fan_out`](crate::bus::MessageBus::dispatch_fan_out),
+    /// or derived by a policy, are published with
+    /// [`MessageBroker::publish_to`] when they have a route, or with
+    /// [`MessageBroker::publish_to_idempotent`] when derived from an event
+    /// with an id. Use this in
+    /// sharded topologies to send each command to the queue or partition of
+    /// the shard that owns its aggregate:
+    ///
+    /// ```rust,ignore
+    /// fn route(&self, cmd: &MyCommand) -> Option<RouteKey> {
+    ///     Some(format!("accounts-{}", cmd.account_id() % 8).into())
+    /// }
+    /// ```
+    ///
+    /// Defaults to `None`, publishing the command to the broker's default
+    /// queue.
+    fn route(&self, _cmd: &Self::Command) -> Option<RouteKey> {
+        None
+    }
+
+    /// Acknowledges a received command within its unit of work.
+    ///
+    /// Called for commands received from the broker, after the handler
+    /// succeeded and before the unit of work commits. Override this when
+    /// the broker implements
+    /// [`TransactionalBroker`](crate::broker::TransactionalBroker), so that
+    /// the acknowledgement commits atomically with the command's changes,
+    /// and return `true`. The bus then does not acknowledge the command
+    /// again after the commit, nor settle it if its events then fail to
+    /// publish; that failure is logged instead.
+    ///
+    /// The default implementation returns `false`, and commands are
+    /// acknowledged after their unit of work commits.
+    fn ack_in_transaction(
+        &self,
+        _broker: &Self::Broker,
+        _uow: &mut Self::UnitOfWork,
+        _id: <Self::Broker as MessageBroker>::Id,
+    ) -> impl Future<Output = Result<bool>> + Send {
+        future::ready(Ok(false))
+    }
+
+    /// Describes the error that caused a message to be dead-lettered.
+    ///
+    /// The details are passed to
+    /// [`MessageBroker::dead_letter_with_reason`] and carried by the
+    /// [`MessageDeadLettered`] notice. Override this to classify the
+    /// application's own errors, so that the dead-letter queue can be
+    /// filtered by them:
+    ///
+    /// ```rust,ignore
+    /// fn error_details(&self, error: &anyhow::Error) -> ErrorDetails {
+    ///     let details = ErrorDetails::from(error);
+    ///     match error.downcast_ref::<PaymentError>() {
+    ///         Some(PaymentError::Timeout) => details.with_category("timeout"),
+    ///         _ => details,
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// The default implementation classifies bus errors by their
+    /// [`BusError::category`](crate::error::BusError::category), and every
+    /// other error as `"application"`.
+    fn error_details(&self, error: &anyhow::Error) -> ErrorDetails {
+        ErrorDetails::from(error)
+    }
+
+    /// Maps a dead-lettered message notice to a domain event.
+    ///
+    /// Called by the message bus whenever a message exhausts its retries.
+    /// If an event is returned, it is published back to the broker so that
+    /// policies can react to the failure like any other fact. The default
+    /// implementation returns `None`, publishing nothing.
+    fn dead_lettered(
+        &self,
+        _notice: MessageDeadLettered<<Self::Broker as MessageBroker>::Id>,
+    ) -> Option<Self::Event> {
+        None
+    }
+
+    /// The store recording the results of commands dispatched with a
+    /// client-supplied id, if any.
+    ///
+    /// Called once when the message bus is constructed. Required by
+    /// [`MessageBus::dispatch_with_id`](crate::bus::MessageBus::dispatch_with_id).
+    /// The default implementation returns `None`.
+    fn idempotency_store(&self) -> Option<Arc<dyn IdempotencyStore<Self::Identifier>>> {
+        None
+    }
+
+    /// The hasher recognizing duplicate events by their content, if any.
+    ///
+    /// Called once when the message bus is constructed. Required when
+    /// [`BusConfig::dedup_by`] is [`DedupBy::ContentHash`]. Serializable
+    /// events can be hashed by their serialization:
+    ///
+    /// ```rust,ignore
+    /// fn content_hasher(&self) -> Option<Arc<dyn ContentHasher<MyEvent>>> {
+    ///     Some(Arc::new(ContentType::Json))
+    /// }
+    /// ```
+    ///
+    /// The default implementation returns `None`.
+    ///
+    /// [`BusConfig::dedup_by`]: crate::config::BusConfig::dedup_by
+    /// [`DedupBy::ContentHash`]: crate::config::DedupBy::ContentHash
+    fn content_hasher(&self) -> Option<Arc<dyn ContentHasher<Self::Event>>> {
+        None
+    }
+
+    /// The enricher loading reference data for each event, if any.
+    ///
+    /// Called once when the message bus is constructed. An enricher runs
+    /// once per `Event`, before the `Policy` is applied, and stores shared
+    /// reference data on the `PolicyContext`. The default implementation
+    /// returns `None`, applying policies to events as they are.
+    fn event_enricher(&self) -> Option<Arc<dyn EventEnricher<Self::Event, Self>>> {
+        None
+    }
+
+    /// The handler of projections that permanently failed, if any.
+    ///
+    /// Called once when the message bus is constructed. With a handler,
+    /// projections that exhaust their retries are handed to it instead of
+    /// being dead-lettered. The default implementation returns `None`,
+    /// dead-lettering them like any other message.
+    fn projection_error_handler(
+        &self,
+    ) -> Option<Arc<dyn ProjectionErrorHandler<Self::Projection>>> {
+        None
+    }
+
+    /// The observers notified of every message the bus publishes.
+    ///
+    /// Called once when the message bus is constructed. The default
+    /// implementation returns no observers.
+    fn publish_observers(&self) -> Vec<Arc<dyn PublishObserver<Self>>> {
+        Vec::new()
+    }
 }
@@ -1,4 +1,55 @@
-use crate::prelude::*;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock, atomic::AtomicUsize},
+    time::SystemTime,
+};
+
+use anyhow::Result;
+use tokio::task::JoinSet;
+
+use crate::{bus::Lease, dedup::RecentHashes, lock::KeyedLocks, prelude::*, view::ContentHasher};
+
+/// Received projections buffered by batch key, awaiting a batched write.
+pub type ProjectionBatches<D> = Arc<Mutex<HashMap<String, Vec<Batched<D>>>>>;
+
+/// A received projection buffered for a batched write.
+pub struct Batched<D: MessageBusDriver> {
+    /// The broker's id for the projection message.
+    pub id: <D::Broker as MessageBroker>::Id,
+
+    /// The id of the received envelope, if any.
+    pub message_id: Option<MessageId>,
+
+    /// The number of retries allowed for the message, if limited.
+    pub max_retries: Option<u32>,
+
+    /// The checkpoint to record once the projection is applied, if any.
+    pub checkpoint: Option<(ProjectionId, MessageId)>,
+
+    /// The buffered projection.
+    pub projection: D::Projection,
+
+    /// This instance's claim on the message, released once it is written.
+    pub lease: Option<Lease>,
+}
+
+/// Flushes of projection batches running on the bus's tasks, each yielding
+/// how its messages were settled.
+pub type BatchFlushes = Arc<Mutex<JoinSet<Result<Vec<Settled>>>>>;
+
+/// Broker ids of projection messages awaiting downstream confirmation, with
+/// the time each started waiting.
+pub type PendingAcks<D> = Arc<
+    Mutex<
+        HashMap<
+            ConfirmationToken,
+            (
+                <<D as MessageBusDriver>::Broker as MessageBroker>::Id,
+                SystemTime,
+            ),
+        >,
+    >,
+>;
 
 /// Internal engine used to bootstrap and run a message bus.
 ///
@@ -30,11 +81,59 @@ pub struct MessageBusEngine<D: MessageBusDriver> {
 
     pub viewer: D::Viewer,
 
+    /// The runtime configuration provided by the driver.
+    pub config: BusConfig,
+
     /// Factory to create a new policy context for each domain event.
     pub policy_context_factory: <D::PolicyContext as PolicyContext>::Factory,
 
     /// Factory to create a new unit of work for each command.
     pub uow_factory: <D::UnitOfWork as UnitOfWork>::Factory,
+
+    /// Locks serializing commands that share a concurrency key.
+    pub command_locks: KeyedLocks,
+
+    /// Locks serializing dispatches of the same client-supplied command id.
+    pub command_id_locks: KeyedLocks,
+
+    /// Locks serializing events that share an ordering key.
+    pub event_locks: KeyedLocks,
+
+    /// Projection messages whose acknowledgement awaits confirmation.
+    pub pending_acks: PendingAcks<D>,
+
+    /// The number of projections that expired awaiting confirmation.
+    pub expired_acks: Arc<AtomicUsize>,
+
+    /// Projections buffered for batched writes.
+    pub projection_batches: ProjectionBatches<D>,
+
+    /// Flushes of projection batches that have not been collected yet.
+    pub batch_flushes: BatchFlushes,
+
+    /// The number of received messages currently being processed.
+    pub in_flight: Arc<AtomicUsize>,
+
+    /// Content hashes of recently handled events.
+    pub recent_events: RecentHashes,
+
+    /// The retry limits currently in force.
+    pub retry_policy: Arc<RwLock<RetryPolicy>>,
+
+    /// Observers notified of every published message.
+    pub publish_observers: Vec<Arc<dyn PublishObserver<D>>>,
+
+    /// The store of results of commands dispatched with a client id, if any.
+    pub idempotency_store: Option<Arc<dyn IdempotencyStore<D::Identifier>>>,
+
+    /// The hasher recognizing duplicate events by their content, if any.
+    pub content_hasher: Option<Arc<dyn ContentHasher<D::Event>>>,
+
+    /// The enricher applied to each domain event before its policy, if any.
+    pub event_enricher: Option<Arc<dyn EventEnricher<D::Event, D>>>,
+
+    /// The handler of projections that permanently failed, if any.
+    pub projection_error_handler: Option<Arc<dyn ProjectionErrorHandler<D::Projection>>>,
 }
 
 impl<D: MessageBusDriver> Clone for MessageBusEngine<D> {
@@ -46,8 +145,24 @@ impl<D: MessageBusDriver> Clone for MessageBusEngine<D> {
             handler: self.handler.clone(),
             policy: self.policy.clone(),
             viewer: self.viewer.clone(),
+            config: self.config.clone(),
             policy_context_factory: self.policy_context_factory.clone(),
             uow_factory: self.uow_factory.clone(),
+            command_locks: self.command_locks.clone(),
+            command_id_locks: self.command_id_locks.clone(),
+            event_locks: self.event_locks.clone(),
+            pending_acks: self.pending_acks.clone(),
+            expired_acks: self.expired_acks.clone(),
+            projection_batches: self.projection_batches.clone(),
+            batch_flushes: self.batch_flushes.clone(),
+            in_flight: self.in_flight.clone(),
+            recent_events: self.recent_events.clone(),
+            retry_policy: self.retry_policy.clone(),
+            publish_observers: self.publish_observers.clone(),
+            idempotency_store: self.idempotency_store.clone(),
+            content_hasher: self.content_hasher.clone(),
+            event_enricher: self.event_enricher.clone(),
+            projection_error_handler: self.projection_error_handler.clone(),
         }
     }
 }
@@ -63,6 +178,13 @@ where
     <D::PolicyContext as PolicyContext>::Factory: for<'a> From<&'a D>,
 {
     fn from(driver: &D) -> Self {
+        let config = driver.config();
+        let retry_policy = RetryPolicy::from(&config);
+        let content_hasher = driver.content_hasher();
+        assert!(
+            content_hasher.is_some() || config.dedup_by == DedupBy::IdOnly,
+            "`DedupBy::ContentHash` requires `MessageBusDriver::content_hasher`"
+        );
         Self {
             driver: driver.clone(),
             broker: From::from(driver),
@@ -70,8 +192,24 @@ where
             handler: From::from(driver),
             policy: From::from(driver),
             viewer: From::from(driver),
+            config,
             policy_context_factory: From::from(driver),
             uow_factory: From::from(driver),
+            command_locks: KeyedLocks::default(),
+            command_id_locks: KeyedLocks::default(),
+            event_locks: KeyedLocks::default(),
+            pending_acks: PendingAcks::<D>::default(),
+            expired_acks: Arc::default(),
+            projection_batches: ProjectionBatches::<D>::default(),
+            batch_flushes: BatchFlushes::default(),
+            in_flight: Arc::default(),
+            recent_events: RecentHashes::default(),
+            retry_policy: Arc::new(RwLock::new(retry_policy)),
+            publish_observers: driver.publish_observers(),
+            idempotency_store: driver.idempotency_store(),
+            content_hasher,
+            event_enricher: driver.event_enricher(),
+            projection_error_handler: driver.projection_error_handler(),
         }
     }
 }
@@ -0,0 +1,32 @@
+use anyhow::Result;
+use futures::future::BoxFuture;
+
+use crate::driver::MessageBusDriver;
+
+/// Loads shared reference data for a domain event before policies run.
+///
+/// `EventEnricher` is invoked once per event, after the `PolicyContext` has
+/// been created and before the `Policy` is applied. It is given mutable
+/// access to the context so it can store any reference data (e.g. customer
+/// tier, account settings) that the event payload does not carry. Policies
+/// then read that data from the context instead of each re-fetching it.
+///
+/// A driver opts in by returning an enricher from
+/// [`MessageBusDriver::event_enricher`].
+///
+/// Like policies, enrichers must not mutate domain state.
+pub trait EventEnricher<E: Send, D: MessageBusDriver>: Send + Sync {
+    /// Enrich the policy context with data related to the given event.
+    ///
+    /// If enrichment fails, the policy is not applied and the event is
+    /// treated as failed.
+    ///
+    /// Events are not required to be `Sync`, so implementations should read
+    /// what they need from `event` before awaiting rather than holding the
+    /// reference across an await point.
+    fn enrich<'a>(
+        &'a self,
+        ctx: &'a mut D::PolicyContext,
+        event: &'a E,
+    ) -> BoxFuture<'a, Result<()>>;
+}
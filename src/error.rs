@@ -0,0 +1,502 @@
+use std::{
+    backtrace::BacktraceStatus,
+    collections::BTreeMap,
+    convert::Infallible,
+    error::Error,
+    fmt,
+    time::{Duration, SystemTime},
+};
+
+use serde::Serialize;
+
+use crate::{
+    id::MessageId,
+    projector::{ProjectionId, ValidationError},
+};
+
+/// Errors raised by the message bus itself.
+///
+/// Errors returned from user components (handlers, policies, projectors)
+/// are passed through unchanged as `anyhow::Error`s. `BusError` covers the
+/// failures the bus detects on its own. It is returned inside an
+/// `anyhow::Error` and can be recovered with `downcast_ref::<BusError>()`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum BusError {
+    /// A command was rejected because it loaded a sealed aggregate.
+    ///
+    /// See [`UnitOfWork::sealed`](crate::uow::UnitOfWork::sealed).
+    AggregateSealed {
+        /// The identifier of the sealed aggregate.
+        aggregate: String,
+    },
+
+    /// A `PolicyContext` could not be created to handle an event.
+    ///
+    /// How the bus retries these failures is configured by
+    /// [`BusConfig::context_failure`](crate::config::BusConfig::context_failure).
+    PolicyContextUnavailable {
+        /// The error returned by the policy context factory.
+        source: anyhow::Error,
+    },
+
+    /// Events could not be published after their unit of work committed.
+    ///
+    /// The command's changes are durable, but its events were neither
+    /// published nor stored by
+    /// [`MessageBusDriver::store_unpublished`](crate::driver::MessageBusDriver::store_unpublished).
+    /// Downstream consumers will not observe them until they are recovered.
+    PublishAfterCommit {
+        /// The number of events that were not published.
+        events: usize,
+
+        /// The error from the final publish attempt.
+        source: anyhow::Error,
+    },
+
+    /// Work was abandoned because its end-to-end deadline passed.
+    ///
+    /// See [`with_deadline`](crate::deadline::with_deadline). Received
+    /// messages that fail with this error are dead-lettered rather than
+    /// retried, since their requester has already given up.
+    DeadlineExceeded {
+        /// The deadline that passed.
+        deadline: SystemTime,
+    },
+
+    /// A command was rejected because its input is invalid.
+    ///
+    /// Handlers return this, typically via [`FieldErrors::into_result`], so
+    /// that an API layer can map each message to the offending field. Like
+    /// any handler error it is returned from
+    /// [`MessageBus::dispatch`](crate::bus::MessageBus::dispatch). Received
+    /// commands that fail validation are dead-lettered without retry.
+    Validation {
+        /// The messages for each invalid field.
+        errors: FieldErrors,
+    },
+
+    /// A query could not be answered because the read-model store is
+    /// unavailable, and no fallback view was available either.
+    ///
+    /// Returned by
+    /// [`MessageBus::view_with_fallback`](crate::bus::MessageBus::view_with_fallback)
+    /// so that callers can tell an outage of the read side apart from an
+    /// invalid query, e.g. to respond with `503 Service Unavailable`.
+    ReadUnavailable {
+        /// The type name of the query.
+        query: &'static str,
+
+        /// The error returned by the viewer.
+        source: anyhow::Error,
+    },
+
+    /// A projection was rejected by
+    /// [`Projector::validate`](crate::projector::Projector::validate).
+    ///
+    /// Invalid projections are dead-lettered without retry.
+    ProjectionInvalid {
+        /// The type name of the projection.
+        projection: &'static str,
+
+        /// Why the projection was rejected.
+        source: ValidationError,
+    },
+
+    /// A command was rejected because the principal dispatching it lacks a
+    /// permission its handler requires.
+    ///
+    /// See [`as_principal`](crate::auth::as_principal). The check happens
+    /// before a unit of work is created, so rejected commands never touch
+    /// the database.
+    Unauthorized {
+        /// The identifier of the principal, or `None` if the command was
+        /// dispatched without one.
+        principal: Option<String>,
+
+        /// The required permissions the principal has not been granted.
+        missing: Vec<String>,
+    },
+
+    /// A received event was not subscribed to by any policy, and the bus is
+    /// configured to dead-letter such events.
+    ///
+    /// See [`UnmatchedEventStrategy`](crate::config::UnmatchedEventStrategy).
+    UnmatchedEvent {
+        /// The type name of the event.
+        event: &'static str,
+    },
+
+    /// A received message was older than
+    /// [`BusConfig::max_message_age`](crate::config::BusConfig::max_message_age).
+    ///
+    /// Stale messages are dead-lettered before they are handled, rather than
+    /// retried.
+    StaleMessage {
+        /// How long ago the message occurred.
+        age: Duration,
+
+        /// The maximum age allowed.
+        max_age: Duration,
+    },
+
+    /// A projection's [`Projector::depends_on`] dependency has not been
+    /// applied yet for the event that caused it.
+    ///
+    /// The projection is redelivered after a delay, without counting
+    /// towards its retries, since it will succeed once its dependency
+    /// catches up.
+    ///
+    /// [`Projector::depends_on`]: crate::projector::Projector::depends_on
+    DependencyPending {
+        /// The projection that must be applied first.
+        dependency: ProjectionId,
+
+        /// The id of the event both projections derive from.
+        event: MessageId,
+    },
+
+    /// A [`Factory`](crate::factory::Factory) failed to create a unit of
+    /// work, policy context, or other per-message resource.
+    ///
+    /// Retryable failures are transient, such as an exhausted connection
+    /// pool. Failures that are not retryable, such as failed
+    /// authentication, will not resolve on their own: a received message
+    /// failing with one is negatively acknowledged and the bus stops.
+    CreateFailed {
+        /// Whether creating the resource may succeed if retried.
+        retryable: bool,
+
+        /// The error returned by the factory.
+        source: anyhow::Error,
+    },
+
+    /// An error that did not come from the bus, such as a factory's own
+    /// `anyhow::Error`, carried where a `BusError` is expected.
+    ///
+    /// It is retried like any error from a handler, and is unwrapped back
+    /// into its source before the bus reports it.
+    Application {
+        /// The original error.
+        source: anyhow::Error,
+    },
+}
+
+impl BusError {
+    /// A factory failure that may succeed if retried.
+    pub fn transient(source: impl Into<anyhow::Error>) -> Self {
+        BusError::CreateFailed {
+            retryable: true,
+            source: source.into(),
+        }
+    }
+
+    /// A factory failure that will not succeed if retried.
+    pub fn fatal(source: impl Into<anyhow::Error>) -> Self {
+        BusError::CreateFailed {
+            retryable: false,
+            source: source.into(),
+        }
+    }
+
+    /// A short, stable name for this kind of error, such as
+    /// `"deadline_exceeded"`.
+    ///
+    /// Used as the [`ErrorDetails::category`] of failures raised by the bus.
+    pub fn category(&self) -> &'static str {
+        match self {
+            BusError::AggregateSealed { .. } => "aggregate_sealed",
+            BusError::PolicyContextUnavailable { .. } => "policy_context_unavailable",
+            BusError::PublishAfterCommit { .. } => "publish_after_commit",
+            BusError::DeadlineExceeded { .. } => "deadline_exceeded",
+            BusError::Validation { .. } => "validation",
+            BusError::ReadUnavailable { .. } => "read_unavailable",
+            BusError::ProjectionInvalid { .. } => "projection_invalid",
+            BusError::Unauthorized { .. } => "unauthorized",
+            BusError::UnmatchedEvent { .. } => "unmatched_event",
+            BusError::StaleMessage { .. } => "stale_message",
+            BusError::DependencyPending { .. } => "dependency_pending",
+            BusError::CreateFailed { .. } => "create_failed",
+            BusError::Application { .. } => "application",
+        }
+    }
+
+    /// Whether the failed work may succeed if retried.
+    ///
+    /// Sealed aggregates, passed deadlines, invalid commands and
+    /// projections, missing permissions, unmatched events, stale messages,
+    /// and fatal factory failures are not retryable.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            BusError::AggregateSealed { .. }
+            | BusError::DeadlineExceeded { .. }
+            | BusError::Validation { .. }
+            | BusError::ProjectionInvalid { .. }
+            | BusError::Unauthorized { .. }
+            | BusError::UnmatchedEvent { .. }
+            | BusError::StaleMessage { .. } => false,
+            BusError::CreateFailed { retryable, .. } => *retryable,
+            _ => true,
+        }
+    }
+}
+
+impl fmt::Display for BusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BusError::AggregateSealed { aggregate } => {
+                write!(
+                    f,
+                    "aggregate `{aggregate}` is sealed and accepts no commands"
+                )
+            }
+            BusError::PolicyContextUnavailable { .. } => {
+                write!(f, "failed to create a policy context")
+            }
+            BusError::PublishAfterCommit { events, .. } => {
+                write!(f, "failed to publish {events} event(s) after commit")
+            }
+            BusError::DeadlineExceeded { .. } => {
+                write!(f, "deadline exceeded")
+            }
+            BusError::Validation { errors } => {
+                write!(f, "invalid command: {errors}")
+            }
+            BusError::ReadUnavailable { query, .. } => {
+                write!(f, "read model unavailable for query `{query}`")
+            }
+            BusError::ProjectionInvalid { projection, .. } => {
+                write!(f, "projection `{projection}` is invalid")
+            }
+            BusError::Unauthorized { principal, missing } => {
+                let missing = missing.join(", ");
+                match principal {
+                    Some(principal) => {
+                        write!(f, "principal `{principal}` lacks permission(s): {missing}")
+                    }
+                    None => write!(f, "no principal for required permission(s): {missing}"),
+                }
+            }
+            BusError::UnmatchedEvent { event } => {
+                write!(f, "no policy subscribes to event `{event}`")
+            }
+            BusError::StaleMessage { age, max_age } => {
+                write!(
+                    f,
+                    "message is {}s old, past the maximum age of {}s",
+                    age.as_secs(),
+                    max_age.as_secs()
+                )
+            }
+            BusError::DependencyPending { dependency, event } => {
+                write!(
+                    f,
+                    "projection is waiting for `{dependency}` to be applied for {event}"
+                )
+            }
+            BusError::CreateFailed { retryable, .. } => {
+                let kind = if *retryable { "transient" } else { "fatal" };
+                write!(f, "failed to create a resource ({kind})")
+            }
+            BusError::Application { source } => fmt::Display::fmt(source, f),
+        }
+    }
+}
+
+impl Error for BusError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BusError::PolicyContextUnavailable { source }
+            | BusError::PublishAfterCommit { source, .. }
+            | BusError::ReadUnavailable { source, .. }
+            | BusError::CreateFailed { source, .. } => Some(source.as_ref()),
+            BusError::ProjectionInvalid { source, .. } => Some(source),
+            BusError::Application { source } => source.source(),
+            _ => None,
+        }
+    }
+}
+
+/// Recovers a `BusError` carried by an `anyhow::Error`, wrapping any other
+/// error as a [`BusError::Application`].
+impl From<anyhow::Error> for BusError {
+    fn from(source: anyhow::Error) -> Self {
+        match source.downcast::<BusError>() {
+            Ok(err) => err,
+            Err(source) => BusError::Application { source },
+        }
+    }
+}
+
+impl BusError {
+    /// Converts the error into an `anyhow::Error`, unwrapping a
+    /// [`BusError::Application`] into its original error.
+    pub(crate) fn into_anyhow(self) -> anyhow::Error {
+        match self {
+            BusError::Application { source } => source,
+            err => err.into(),
+        }
+    }
+}
+
+impl From<Infallible> for BusError {
+    fn from(never: Infallible) -> Self {
+        match never {}
+    }
+}
+
+impl From<FieldErrors> for BusError {
+    fn from(errors: FieldErrors) -> Self {
+        BusError::Validation { errors }
+    }
+}
+
+/// Validation messages keyed by the input field they apply to.
+///
+/// `FieldErrors` lets a command handler report every problem with its input
+/// at once, in a shape an API layer can render directly as form errors:
+///
+/// ```rust,ignore
+/// let mut errors = FieldErrors::new();
+/// if users.email_taken(&cmd.email).await? {
+///     errors.add("email", "already taken");
+/// }
+/// if cmd.name.is_empty() {
+///     errors.add("name", "is required");
+/// }
+/// errors.into_result()?;
+/// ```
+///
+/// Fields are kept in sorted order, and serialize as a map of field names to
+/// lists of messages.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct FieldErrors(BTreeMap<String, Vec<String>>);
+
+impl FieldErrors {
+    /// Creates an empty set of field errors.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a message for the given field.
+    pub fn add(&mut self, field: impl Into<String>, message: impl Into<String>) -> &mut Self {
+        self.0.entry(field.into()).or_default().push(message.into());
+        self
+    }
+
+    /// Adds a message for the given field, returning `self` for chaining.
+    pub fn with(mut self, field: impl Into<String>, message: impl Into<String>) -> Self {
+        self.add(field, message);
+        self
+    }
+
+    /// Whether no field has an error.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The messages for the given field.
+    pub fn get(&self, field: &str) -> &[String] {
+        self.0.get(field).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Iterates over each field and its messages.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[String])> {
+        self.0
+            .iter()
+            .map(|(field, messages)| (field.as_str(), messages.as_slice()))
+    }
+
+    /// Returns `Ok` if there are no errors, or [`BusError::Validation`]
+    /// otherwise.
+    pub fn into_result(self) -> Result<(), BusError> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self.into())
+        }
+    }
+}
+
+impl fmt::Display for FieldErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (field, messages)) in self.iter().enumerate() {
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{field}: {}", messages.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// A structured description of why a message failed.
+///
+/// Passed to [`MessageBroker::dead_letter_with_reason`] and carried by
+/// [`MessageDeadLettered`] so that a dead-letter queue can store failures in
+/// a filterable form, e.g. to list every `deadline_exceeded` failure, rather
+/// than as a flat string. Drivers can classify their own errors with
+/// [`MessageBusDriver::error_details`].
+///
+/// [`MessageBroker::dead_letter_with_reason`]: crate::broker::MessageBroker::dead_letter_with_reason
+/// [`MessageDeadLettered`]: crate::dead_letter::MessageDeadLettered
+/// [`MessageBusDriver::error_details`]: crate::driver::MessageBusDriver::error_details
+#[derive(Clone, Debug, Serialize)]
+pub struct ErrorDetails {
+    /// The kind of failure.
+    ///
+    /// For a [`BusError`] this is its [`category`](BusError::category).
+    /// Other errors are classified as `"application"`, unless the driver
+    /// classifies them itself.
+    pub category: &'static str,
+
+    /// The error's message, without its causes.
+    pub message: String,
+
+    /// The messages of the error's causes, outermost first.
+    pub causes: Vec<String>,
+
+    /// The invalid fields, if the error is a [`BusError::Validation`].
+    pub field_errors: Option<FieldErrors>,
+
+    /// The backtrace captured with the error, if backtraces are enabled
+    /// (e.g. with `RUST_BACKTRACE=1`).
+    pub backtrace: Option<String>,
+}
+
+impl ErrorDetails {
+    /// Replaces the category, e.g. to classify an application error.
+    pub fn with_category(mut self, category: &'static str) -> Self {
+        self.category = category;
+        self
+    }
+}
+
+impl From<&anyhow::Error> for ErrorDetails {
+    fn from(error: &anyhow::Error) -> Self {
+        let bus_err = error.downcast_ref::<BusError>();
+        let backtrace = error.backtrace();
+        Self {
+            category: bus_err.map_or("application", BusError::category),
+            message: error.to_string(),
+            causes: error.chain().skip(1).map(ToString::to_string).collect(),
+            field_errors: match bus_err {
+                Some(BusError::Validation { errors }) => Some(errors.clone()),
+                _ => None,
+            },
+            backtrace: (backtrace.status() == BacktraceStatus::Captured)
+                .then(|| backtrace.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for ErrorDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.category, self.message)?;
+        for cause in &self.causes {
+            write!(f, ": {cause}")?;
+        }
+        Ok(())
+    }
+}
@@ -1,7 +1,16 @@
-use anyhow::Result;
+use crate::error::BusError;
 
 pub trait Factory: Send + Sync {
     type Output: Send;
 
-    fn create(&self) -> impl Future<Output = Result<Self::Output>> + Send;
+    /// The error returned when the output cannot be created.
+    ///
+    /// Converting into a [`BusError`] lets a factory tell the bus how to
+    /// react: a [`BusError::CreateFailed`] that is retryable (e.g. an
+    /// exhausted connection pool) is retried like any other failure, while
+    /// a non-retryable one (e.g. failed authentication) stops the bus.
+    /// Any other `anyhow::Error` is retried like an error from a handler.
+    type Error: Into<BusError>;
+
+    fn create(&self) -> impl Future<Output = Result<Self::Output, Self::Error>> + Send;
 }
@@ -0,0 +1,112 @@
+use std::any::type_name;
+
+use anyhow::{Context, Result};
+use futures::{
+    FutureExt, StreamExt,
+    channel::oneshot,
+    future::{BoxFuture, Shared},
+    stream::FuturesUnordered,
+};
+
+use crate::{
+    broker::MessageBroker, bus::MessageBus, driver::MessageBusDriver, handler::CommandHandler,
+    message::SideEffect, policy::Policy,
+};
+
+/// Several message buses run together in one process.
+///
+/// A modular monolith splits its domain into bounded-context modules, each
+/// with its own [`MessageBusDriver`] and therefore its own command, event,
+/// and projection types. `BusGroup` runs the processing loop of every
+/// module's bus side by side:
+///
+/// ```rust,ignore
+/// BusGroup::new()
+///     .with(MessageBus::from(&OrdersDriver::init().await?))
+///     .with(MessageBus::from(&BillingDriver::init().await?))
+///     .start()
+///     .await?;
+/// ```
+///
+/// Each message is owned by the module whose types it belongs to, and only
+/// that module's bus receives it. When the modules share a broker, give
+/// each a [`BusConfig::subscription`] covering just its own subjects.
+/// Modules talk to each other through the shared broker, by publishing
+/// commands or events that another module subscribes to, rather than by
+/// calling each other's handlers.
+///
+/// [`BusConfig::subscription`]: crate::config::BusConfig::subscription
+#[derive(Default)]
+pub struct BusGroup {
+    buses: Vec<(&'static str, StartUntil)>,
+}
+
+/// Signals the buses of a group to stop.
+type Stop = Shared<BoxFuture<'static, ()>>;
+
+/// Runs a bus's processing loop until the group is stopped.
+type StartUntil = Box<dyn FnOnce(Stop) -> BoxFuture<'static, Result<()>> + Send>;
+
+impl BusGroup {
+    /// Creates an empty group.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a module's bus to the group.
+    pub fn with<D>(mut self, bus: MessageBus<D>) -> Self
+    where
+        D: MessageBusDriver + 'static,
+        D::Handler: CommandHandler<D::Command, D>,
+        D::Policy: Policy<D::Event, D, Output = SideEffect<D::Command, D::Projection>>,
+        D::Command: Sync,
+        D::Event: Sync,
+        D::Projection: Sync,
+        <D::Broker as MessageBroker>::Id: Sync,
+    {
+        let start: StartUntil = Box::new(move |stop| bus.start_until(stop).boxed());
+        self.buses.push((type_name::<D>(), start));
+        self
+    }
+
+    /// The number of buses in the group.
+    pub fn len(&self) -> usize {
+        self.buses.len()
+    }
+
+    /// Whether the group has no buses.
+    pub fn is_empty(&self) -> bool {
+        self.buses.is_empty()
+    }
+
+    /// Runs every bus's processing loop until one of them stops.
+    ///
+    /// See [`MessageBus::start`]. Once a bus stops, the others are told to
+    /// stop with [`MessageBus::start_until`], and are awaited while they
+    /// settle the messages they already received. The result of the bus
+    /// that stopped first is returned, or else the first error from the
+    /// others, naming the driver of a bus that failed. The buses run concurrently on the
+    /// calling task, which may be spawned.
+    pub async fn start(self) -> Result<()> {
+        let (stop, stopped) = oneshot::channel::<()>();
+        let stopped = stopped.map(|_| ()).boxed().shared();
+        let mut running: FuturesUnordered<_> = self
+            .buses
+            .into_iter()
+            .map(|(driver, start)| {
+                start(stopped.clone())
+                    .map(move |res| res.with_context(|| format!("message bus `{driver}` failed")))
+            })
+            .collect();
+        let Some(mut res) = running.next().await else {
+            return Ok(());
+        };
+        drop(stop);
+        while let Some(stopped) = running.next().await {
+            if res.is_ok() {
+                res = stopped;
+            }
+        }
+        res
+    }
+}
@@ -1,5 +1,8 @@
+use std::future;
+
 use crate::driver::MessageBusDriver;
 use anyhow::Result;
+use futures::Stream;
 
 /// Represents the response type of a command.
 ///
@@ -34,9 +37,240 @@ pub trait CommandHandler<C: Command, D: MessageBusDriver>: Clone + Send + Sync {
     ///
     /// If the command is successful, the `UnitOfWork` will be committed.
     /// If an error is returned, the `UnitOfWork` will be rolled back.
+    ///
+    /// Return [`HandlerOutcome::NoChange`] when the command turned out to
+    /// be a no-op, e.g. because it was re-executed and the state already
+    /// matches. The `UnitOfWork` is then rolled back and no events are
+    /// published.
     fn handle(
         &self,
         uow: &mut D::UnitOfWork,
         cmd: C,
-    ) -> impl Future<Output = Result<Option<D::Identifier>>> + Send;
+    ) -> impl Future<Output = Result<HandlerOutcome<D::Identifier>>> + Send;
+
+    /// Prepare to handle a command.
+    ///
+    /// Called before [`handle`](Self::handle) with the same unit of work.
+    /// Use this to acquire per-invocation resources, such as a distributed
+    /// lock or lease on the target aggregate, keeping any state needed to
+    /// release them in the unit of work. If an error is returned, the
+    /// command is not handled and the unit of work is rolled back.
+    ///
+    /// Commands are not required to be `Sync`, so implementations should
+    /// read what they need from `cmd` before awaiting. The default
+    /// implementation does nothing.
+    fn before_handle(
+        &self,
+        _uow: &mut D::UnitOfWork,
+        _cmd: &C,
+    ) -> impl Future<Output = Result<()>> + Send {
+        future::ready(Ok(()))
+    }
+
+    /// Clean up after handling a command.
+    ///
+    /// Called after [`handle`](Self::handle) whenever
+    /// [`before_handle`](Self::before_handle) succeeded, even if `handle`
+    /// returned an error or panicked, making it the place to release locks
+    /// or leases. If an error is returned, the command is treated as failed
+    /// and the unit of work is rolled back.
+    ///
+    /// The default implementation does nothing.
+    fn after_handle(&self, _uow: &mut D::UnitOfWork) -> impl Future<Output = Result<()>> + Send {
+        future::ready(Ok(()))
+    }
+
+    /// The concurrency key of the given command, if any.
+    ///
+    /// Commands dispatched through the same message bus that share a
+    /// concurrency key never run at the same time: each waits for the
+    /// previous one to commit and publish its events before it starts.
+    /// Use this for singleton-style commands, such as rebuilding global
+    /// statistics, that must not run concurrently with themselves:
+    ///
+    /// ```rust,ignore
+    /// fn concurrency_key(&self, cmd: &MyCommand) -> Option<String> {
+    ///     matches!(cmd, MyCommand::RebuildStats).then(|| "rebuild-stats".into())
+    /// }
+    /// ```
+    ///
+    /// Keys only serialize commands within a single process. Defaults to
+    /// `None`, allowing the command to run concurrently with any other.
+    fn concurrency_key(&self, _cmd: &C) -> Option<String> {
+        None
+    }
+
+    /// Whether the given command should be queued rather than executed.
+    ///
+    /// Commands submitted with
+    /// [`MessageBus::submit`](crate::bus::MessageBus::submit) are published
+    /// to the broker for asynchronous execution when this returns `true`,
+    /// and executed immediately otherwise. Use this for long-running
+    /// commands that an API should accept without waiting for.
+    ///
+    /// Defaults to `false`.
+    fn should_queue(&self, _cmd: &C) -> bool {
+        false
+    }
+
+    /// The permissions a principal needs to dispatch the given command.
+    ///
+    /// Commands dispatched by the application are checked against the
+    /// current [`Principal`](crate::auth::Principal) before a unit of work
+    /// is created, failing with
+    /// [`BusError::Unauthorized`](crate::error::BusError::Unauthorized) if
+    /// any is missing. This keeps authorization declarative:
+    ///
+    /// ```rust,ignore
+    /// fn required_permissions(&self, cmd: &MyCommand) -> Vec<String> {
+    ///     match cmd {
+    ///         MyCommand::CancelOrder { .. } => vec!["orders:cancel".into()],
+    ///         _ => Vec::new(),
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// See [`as_principal`](crate::auth::as_principal). Defaults to no
+    /// permissions, allowing anyone to dispatch the command.
+    fn required_permissions(&self, _cmd: &C) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// The outcome of a successfully handled command.
+///
+/// Returned by [`CommandHandler::handle`]. A handler that changed state
+/// returns [`Changed`](Self::Changed), optionally with the identifier of
+/// the affected aggregate; `Option<I>` converts into it:
+///
+/// ```rust,ignore
+/// async fn handle(&self, uow: &mut MyUnitOfWork, cmd: Rename) -> Result<HandlerOutcome<Uuid>> {
+///     let mut user = uow.users().get(cmd.id).await?;
+///     if user.name == cmd.name {
+///         return Ok(HandlerOutcome::NoChange);
+///     }
+///     user.rename(cmd.name);
+///     Ok(Some(user.id).into())
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HandlerOutcome<I> {
+    /// The command changed state, and its unit of work is committed.
+    Changed(Option<I>),
+
+    /// The command was a no-op, and its unit of work is rolled back.
+    NoChange,
+}
+
+impl<I> HandlerOutcome<I> {
+    /// Whether the command changed state.
+    pub fn is_changed(&self) -> bool {
+        matches!(self, HandlerOutcome::Changed(_))
+    }
+
+    /// The identifier returned by the handler, if any.
+    pub fn into_result(self) -> Option<I> {
+        match self {
+            HandlerOutcome::Changed(result) => result,
+            HandlerOutcome::NoChange => None,
+        }
+    }
+}
+
+impl<I> From<Option<I>> for HandlerOutcome<I> {
+    fn from(result: Option<I>) -> Self {
+        HandlerOutcome::Changed(result)
+    }
+}
+
+/// A handler for commands too large to execute in a single transaction.
+///
+/// A `StreamingCommandHandler` processes a command's input (e.g. the rows of
+/// a bulk import) as a stream, split into sub-batches. The message bus runs
+/// each sub-batch in its own `UnitOfWork`, committing it and publishing its
+/// events before moving on to the next, so memory use and transaction size
+/// stay bounded regardless of the input size.
+///
+/// To make a long-running command resumable, implementations should record
+/// their progress (a checkpoint) in the same unit of work as each batch, and
+/// have [`input`](Self::input) resume after the last committed checkpoint.
+/// A crash mid-import then loses at most the uncommitted batch.
+///
+/// Streaming commands are executed with
+/// [`MessageBus::dispatch_chunked`](crate::bus::MessageBus::dispatch_chunked).
+pub trait StreamingCommandHandler<C: Command, D: MessageBusDriver>: Clone + Send + Sync {
+    /// A single input item processed by this handler.
+    type Item: Send;
+
+    /// Open the input stream for the given command.
+    ///
+    /// When resuming a previously interrupted command, the stream should
+    /// start after the last checkpoint committed by
+    /// [`handle_batch`](Self::handle_batch).
+    fn input(
+        &self,
+        cmd: &C,
+    ) -> impl Future<Output = Result<impl Stream<Item = Result<Self::Item>> + Send>> + Send;
+
+    /// Handle one sub-batch of input items within the given unit of work.
+    ///
+    /// If the batch succeeds, the unit of work is committed and its events
+    /// are published. If an error is returned, the unit of work is rolled
+    /// back and the command stops; batches committed before it are kept.
+    fn handle_batch(
+        &self,
+        uow: &mut D::UnitOfWork,
+        cmd: &C,
+        batch: Vec<Self::Item>,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// The maximum number of input items per sub-batch.
+    ///
+    /// Defaults to `1000`.
+    fn batch_size(&self, _cmd: &C) -> usize {
+        1000
+    }
+
+    /// The permissions the current principal must have to dispatch the
+    /// command.
+    ///
+    /// Checked as for [`CommandHandler::required_permissions`], before any
+    /// input is read. Defaults to none.
+    fn required_permissions(&self, _cmd: &C) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// A command that expands into many independent sub-commands.
+///
+/// Some commands, such as one targeting "all accounts in region X", are
+/// really many per-aggregate operations. Rather than handling them in one
+/// large transaction, a `FanOutCommand` splits itself into the driver's
+/// commands, which the bus publishes to the broker. Each sub-command is then
+/// received and handled in its own `UnitOfWork`, and retried on its own if
+/// it fails.
+///
+/// Fan-out commands are executed with
+/// [`MessageBus::dispatch_fan_out`](crate::bus::MessageBus::dispatch_fan_out).
+pub trait FanOutCommand<D: MessageBusDriver>: Command + Sync {
+    /// Split this command into the sub-commands to publish.
+    ///
+    /// The policy context gives read access to whatever is needed to find
+    /// the targeted aggregates (e.g. the accounts in a region). It must not
+    /// be used to mutate state.
+    fn split(
+        &self,
+        ctx: &mut D::PolicyContext,
+    ) -> impl Future<Output = Result<Vec<D::Command>>> + Send;
+
+    /// The permissions the current principal must have to dispatch this
+    /// command.
+    ///
+    /// Checked before the command is split. Each sub-command is then
+    /// authorized against its handler's
+    /// [`required_permissions`](CommandHandler::required_permissions)
+    /// before anything is published. Defaults to none.
+    fn required_permissions(&self) -> Vec<String> {
+        Vec::new()
+    }
 }
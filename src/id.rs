@@ -0,0 +1,42 @@
+use uuid::Uuid;
+
+/// The identifier of a message published by the bus.
+///
+/// See [`Envelope::id`](crate::message::Envelope::id).
+pub type MessageId = String;
+
+/// A source of unique message ids.
+///
+/// The message bus stamps every message it publishes with an id from its
+/// `IdGenerator`, except the side effects of an identified event, whose ids
+/// are derived from the event's id. Choose a generator to control the id
+/// format, e.g. [`UuidV7Generator`] for brokers that benefit from
+/// time-sortable ids.
+///
+/// A generator is configured via
+/// [`BusConfig::with_id_generator`](crate::config::BusConfig::with_id_generator)
+/// and defaults to [`UuidV4Generator`].
+pub trait IdGenerator: Send + Sync {
+    /// Generates a new, unique message id.
+    fn generate(&self) -> MessageId;
+}
+
+/// Generates random (version 4) UUIDs.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UuidV4Generator;
+
+impl IdGenerator for UuidV4Generator {
+    fn generate(&self) -> MessageId {
+        Uuid::new_v4().to_string()
+    }
+}
+
+/// Generates time-ordered (version 7) UUIDs.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UuidV7Generator;
+
+impl IdGenerator for UuidV7Generator {
+    fn generate(&self) -> MessageId {
+        Uuid::now_v7().to_string()
+    }
+}
@@ -0,0 +1,36 @@
+use anyhow::Result;
+use futures::future::BoxFuture;
+
+use crate::{bus::DispatchResult, id::MessageId};
+
+/// Remembers the results of commands dispatched with a client-supplied id.
+///
+/// Clients on flaky networks retry a command when they do not hear back,
+/// although the first attempt may have succeeded. By sending the same
+/// command id with every attempt, and dispatching with
+/// [`MessageBus::dispatch_with_id`], each command runs only once: the
+/// store records its result, and later attempts are answered with it.
+///
+/// Implementations should be durable, e.g. a table keyed by command id,
+/// so that retries are recognized across restarts, and may expire results
+/// once clients no longer retry. Results are typically persisted
+/// serialized, since [`DispatchResult`] implements `Serialize` and
+/// `Deserialize` for serializable identifiers. A store is supplied by
+/// [`MessageBusDriver::idempotency_store`].
+///
+/// [`MessageBus::dispatch_with_id`]: crate::bus::MessageBus::dispatch_with_id
+/// [`MessageBusDriver::idempotency_store`]: crate::driver::MessageBusDriver::idempotency_store
+pub trait IdempotencyStore<I>: Send + Sync {
+    /// The recorded result of the command with the given id, if any.
+    fn get(&self, command_id: MessageId) -> BoxFuture<'_, Result<Option<DispatchResult<I>>>>;
+
+    /// Record the result of the command with the given id.
+    ///
+    /// Implementations should read what they need from `result` before
+    /// awaiting, since identifiers are not required to be `Sync`.
+    fn put<'a>(
+        &'a self,
+        command_id: MessageId,
+        result: &'a DispatchResult<I>,
+    ) -> BoxFuture<'a, Result<()>>;
+}
@@ -21,7 +21,7 @@
 //! - Projections are dispatched to external systems for read-model updates or notifications
 //!
 //! ## Example
-//! ```rust
+//! ```rust,ignore
 //! use anyhow::Result;
 //! use my_app::driver::MyDriver;
 //! use my_framework::bus::MessageBus;
@@ -54,16 +54,44 @@
 //!
 //! This module forms the backbone of the message-based execution model used across your system.
 
+mod dedup;
 mod engine;
+mod lock;
 
+pub mod auth;
+#[cfg(feature = "axum")]
+pub mod axum;
 pub mod broker;
 pub mod bus;
+pub mod clock;
+pub mod config;
+pub mod coordinator;
+pub mod dead_letter;
+pub mod deadline;
 pub mod driver;
+pub mod enricher;
+pub mod error;
 pub mod factory;
+pub mod group;
 pub mod handler;
+pub mod id;
+pub mod idempotency;
 pub mod message;
+pub mod metrics;
+pub mod migration;
+pub mod observer;
+pub mod pipeline;
 pub mod policy;
 pub mod prelude;
+pub mod progress;
 pub mod projector;
+pub mod rate;
+pub mod registry;
+pub mod replay;
+pub mod rollout;
+pub mod split;
+#[cfg(feature = "test-util")]
+pub mod testing;
+pub mod trace;
 pub mod uow;
 pub mod view;
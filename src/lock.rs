@@ -0,0 +1,63 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::OwnedMutexGuard;
+
+/// A set of named asynchronous locks.
+///
+/// Each key maps to its own mutex, created on first use and removed once no
+/// task holds or awaits it. Tasks locking the same key are queued and run
+/// one at a time; tasks with different keys do not contend.
+#[derive(Clone, Default)]
+pub struct KeyedLocks {
+    locks: Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>>,
+}
+
+impl KeyedLocks {
+    /// Waits until the lock for `key` is available and acquires it.
+    ///
+    /// The lock is held until the returned guard is dropped.
+    pub async fn lock(&self, key: String) -> KeyedGuard {
+        let mutex = self
+            .locks
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_default()
+            .clone();
+        let guard = mutex.lock_owned().await;
+        KeyedGuard {
+            locks: self.clone(),
+            key,
+            guard: Some(guard),
+        }
+    }
+
+    /// The number of keys currently held or awaited.
+    pub fn active_keys(&self) -> usize {
+        self.locks.lock().unwrap().len()
+    }
+}
+
+/// Holds the lock for a single key of a [`KeyedLocks`].
+pub struct KeyedGuard {
+    locks: KeyedLocks,
+    key: String,
+    guard: Option<OwnedMutexGuard<()>>,
+}
+
+impl Drop for KeyedGuard {
+    fn drop(&mut self) {
+        let mut locks = self.locks.locks.lock().unwrap();
+        self.guard.take();
+        // Only the map still references the mutex, so nobody is waiting on it.
+        if locks
+            .get(&self.key)
+            .is_some_and(|mutex| Arc::strong_count(mutex) == 1)
+        {
+            locks.remove(&self.key);
+        }
+    }
+}
@@ -1,4 +1,6 @@
-use crate::{driver::MessageBusDriver, handler::Command};
+use std::{any::type_name, fmt, time::SystemTime};
+
+use crate::{driver::MessageBusDriver, handler::Command, id::MessageId};
 
 /// A top-level message envelope for routing through the message bus.
 ///
@@ -7,13 +9,18 @@ use crate::{driver::MessageBusDriver, handler::Command};
 ///
 /// - `Command`: An intent to change domain state, handled via a `UnitOfWork`.
 /// - `Event`: A fact that something has already happened, emitted by the domain and
-///            passed to `Policy` implementations.
+///   passed to `Policy` implementations.
 /// - `Projection`: A side-effect-only message used to update external systems,
-///                 handled by a `Projector`.
+///   handled by a `Projector`.
 ///
 /// This enum is used internally to represent all message types in transit across
 /// the system. Each variant will be routed to the appropriate handler based on
 /// its type.
+///
+/// New kinds of messages may be added, so matches on a message must have a
+/// wildcard arm.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
 pub enum Message<C, E, P>
 where
     C: Send + Command,
@@ -26,11 +33,74 @@ where
     /// A domain event message, representing something that has occurred.
     Event(E),
 
+    /// The events committed together by one command, in order.
+    ///
+    /// Published instead of separate events when
+    /// [`BusConfig::batch_committed_events`](crate::config::BusConfig::batch_committed_events)
+    /// is set, so that policies can react to the whole set at once. Batches
+    /// are events in transit, and share their [`MessageKind`].
+    EventBatch(Vec<E>),
+
     /// A projection message, representing a read-side update or infrastructure
     /// effect.
     Projection(P),
 }
 
+impl<C, E, P> Message<C, E, P>
+where
+    C: Send + Command,
+    E: Send,
+    P: Send,
+{
+    /// The kind of this message, without its payload.
+    pub fn kind(&self) -> MessageKind {
+        match self {
+            Message::Command(_) => MessageKind::Command,
+            Message::Event(_) | Message::EventBatch(_) => MessageKind::Event,
+            Message::Projection(_) => MessageKind::Projection,
+        }
+    }
+
+    /// The type name of this message's payload.
+    ///
+    /// This is the name of the driver's command, event, or projection type,
+    /// as reported by [`std::any::type_name`]. It is intended for tagging and
+    /// logging; the exact format is not guaranteed to be stable.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Message::Command(_) => type_name::<C>(),
+            Message::Event(_) | Message::EventBatch(_) => type_name::<E>(),
+            Message::Projection(_) => type_name::<P>(),
+        }
+    }
+}
+
+/// The kind of a [`Message`], without its payload.
+///
+/// `MessageKind` lets brokers, middleware, and tooling tag or route messages
+/// uniformly without matching on the full message enum.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MessageKind {
+    /// A command message.
+    Command,
+
+    /// A domain event message.
+    Event,
+
+    /// A projection message.
+    Projection,
+}
+
+impl fmt::Display for MessageKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MessageKind::Command => f.write_str("command"),
+            MessageKind::Event => f.write_str("event"),
+            MessageKind::Projection => f.write_str("projection"),
+        }
+    }
+}
+
 /// A type alias for a fully typed message handled by the message bus.
 ///
 /// `DriverMessage` resolves the concrete command, event, and projection types
@@ -43,6 +113,139 @@ pub type DriverMessage<D> = Message<
     <D as MessageBusDriver>::Projection,
 >;
 
+/// A message together with the metadata the bus attaches in transit.
+///
+/// `Envelope` is the unit exchanged with the [`MessageBroker`]: the bus
+/// wraps every message it publishes in an envelope and unwraps every
+/// envelope it receives. Brokers may read the metadata (for example to
+/// order or partition messages), but must deliver it unchanged.
+///
+/// [`MessageBroker`]: crate::broker::MessageBroker
+#[derive(Clone, Debug)]
+pub struct Envelope<M> {
+    /// The message being carried.
+    pub message: M,
+
+    /// A stable identifier for the message, if one is known.
+    ///
+    /// The bus stamps every message it publishes with an id (see
+    /// [`IdGenerator`](crate::id::IdGenerator)). Brokers that do not carry it should set this on
+    /// receive from their native message id, so that it is the same on
+    /// every redelivery. Side effects published in response to an
+    /// identified event are given ids derived from it, which lets
+    /// [`MessageBroker::publish_idempotent`] discard duplicates when the
+    /// event is handled again.
+    ///
+    /// [`MessageBroker::publish_idempotent`]: crate::broker::MessageBroker::publish_idempotent
+    pub id: Option<MessageId>,
+
+    /// The id of the message whose handling published this one.
+    ///
+    /// `None` for events of a command dispatched outside the bus's receive
+    /// loop that has no id of its own. See [`TraceEntry`](crate::trace::TraceEntry).
+    pub causation_id: Option<MessageId>,
+
+    /// The id of the message that started the flow this message belongs to.
+    pub correlation_id: Option<MessageId>,
+
+    /// The position of this message among those published together.
+    ///
+    /// Events committed by a single unit of work are stamped `0, 1, 2, ...`
+    /// in capture order, so a broker or consumer can preserve the causal
+    /// order of a command's events even if the batch is reordered in
+    /// transit. An [`EventBatch`](Message::EventBatch) holding them all is
+    /// stamped `0`. `None` for messages with no ordering requirement.
+    pub sequence: Option<u64>,
+
+    /// When the message was published by the bus.
+    ///
+    /// The receive loop compares this with the time a message is received
+    /// to report how long it waited in the broker (see
+    /// [`Metrics::on_queue_latency`](crate::metrics::Metrics::on_queue_latency)).
+    /// `None` for messages published outside the bus.
+    pub occurred_at: Option<SystemTime>,
+
+    /// The end-to-end deadline the message must be handled by.
+    ///
+    /// Set by the bus when a message is published within
+    /// [`with_deadline`](crate::deadline::with_deadline). The message is
+    /// handled within the same deadline when it is received.
+    pub deadline: Option<SystemTime>,
+
+    /// Whether the inline projections of this event were already applied.
+    ///
+    /// Set by the bus on the events of a command dispatched with
+    /// [`MessageBus::dispatch_consistent`](crate::bus::MessageBus::dispatch_consistent),
+    /// which applies their inline projections in the command's transaction.
+    /// When such an event is received, its inline projections are not
+    /// published again. Every other event has all of its projections
+    /// published.
+    pub projected_inline: bool,
+}
+
+impl<M> Envelope<M> {
+    /// Wraps a message in an envelope with no metadata.
+    pub fn new(message: M) -> Self {
+        Self {
+            message,
+            id: None,
+            causation_id: None,
+            correlation_id: None,
+            sequence: None,
+            occurred_at: None,
+            deadline: None,
+            projected_inline: false,
+        }
+    }
+
+    /// Sets the identifier of the message.
+    pub fn with_id(mut self, id: impl Into<MessageId>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Sets the ids of the message that caused this one and of the message
+    /// that started its flow.
+    pub fn with_causation(
+        mut self,
+        causation_id: impl Into<MessageId>,
+        correlation_id: impl Into<MessageId>,
+    ) -> Self {
+        self.causation_id = Some(causation_id.into());
+        self.correlation_id = Some(correlation_id.into());
+        self
+    }
+
+    /// Stamps the envelope with a sequence number.
+    pub fn with_sequence(mut self, sequence: u64) -> Self {
+        self.sequence = Some(sequence);
+        self
+    }
+
+    /// Sets the deadline the message must be handled by.
+    pub fn with_deadline(mut self, deadline: SystemTime) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Marks the inline projections of the message as already applied.
+    pub fn with_projected_inline(mut self) -> Self {
+        self.projected_inline = true;
+        self
+    }
+
+    /// Stamps the envelope with the time its message was published.
+    pub fn with_occurred_at(mut self, occurred_at: SystemTime) -> Self {
+        self.occurred_at = Some(occurred_at);
+        self
+    }
+}
+
+/// A type alias for an envelope carrying a fully typed driver message.
+///
+/// This is the message type exchanged with a driver's `MessageBroker`.
+pub type DriverEnvelope<D> = Envelope<DriverMessage<D>>;
+
 /// A message produced as a side effect of a domain event.
 ///
 /// A `SideEffect` is a result of applying a `Policy` to a domain event. It may
@@ -53,6 +256,7 @@ pub type DriverMessage<D> = Message<
 ///
 /// These side effects will be published to the message bus and routed as if they
 /// had been received externally.
+#[derive(Clone, Debug)]
 pub enum SideEffect<C, P>
 where
     C: Send + Command,
@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+use crate::{bus::BusStats, message::MessageKind};
+
+/// Hooks for recording message bus metrics.
+///
+/// `Metrics` is an observer that the message bus notifies at key points of
+/// its runtime. Every hook has a no-op default, so implementations only need
+/// to override the measurements they care about. Implementations are
+/// typically thin adapters over a metrics backend such as the `metrics`
+/// crate or OpenTelemetry.
+///
+/// Hooks are called inline on the bus's hot path and must not block.
+///
+/// Timing hooks receive the kind and type name of the message along with
+/// the measured [`Duration`], so that latencies can be recorded as
+/// histograms per message type and percentile-based SLOs computed from
+/// them. With the `metrics` crate, for example:
+///
+/// ```rust,ignore
+/// struct BusMetrics;
+///
+/// impl Metrics for BusMetrics {
+///     fn on_queue_latency(&self, kind: MessageKind, type_name: &'static str, latency: Duration) {
+///         let labels = [("kind", kind.to_string()), ("type", type_name.to_owned())];
+///         metrics::histogram!("bus_queue_latency_seconds", &labels).record(latency);
+///     }
+///
+///     fn on_processing_latency(&self, kind: MessageKind, type_name: &'static str, duration: Duration) {
+///         let labels = [("kind", kind.to_string()), ("type", type_name.to_owned())];
+///         metrics::histogram!("bus_processing_latency_seconds", &labels).record(duration);
+///     }
+/// }
+/// ```
+///
+/// Queue latency and processing latency are reported separately, so that
+/// time spent waiting in the broker can be told apart from time spent
+/// handling.
+///
+/// A `Metrics` implementation is configured via
+/// [`BusConfig::with_metrics`](crate::config::BusConfig::with_metrics).
+pub trait Metrics: Send + Sync {
+    /// Called after a query has been answered by the `Viewer`.
+    ///
+    /// `query` is the type name of the query, `duration` is the time spent
+    /// in the viewer, and `success` is whether the viewer returned a view.
+    fn on_query(&self, _query: &'static str, _duration: Duration, _success: bool) {}
+
+    /// Called when a message is received, before it is handled.
+    ///
+    /// `type_name` is the type name of the message and `latency` is the time
+    /// it spent in the broker between being published and received. It is
+    /// only reported for messages whose envelope carries an `occurred_at`
+    /// timestamp, and relies on the publishing and receiving hosts having
+    /// reasonably synchronized clocks. It is measured with the configured
+    /// [`BusConfig::clock`](crate::config::BusConfig::clock).
+    fn on_queue_latency(&self, _kind: MessageKind, _type_name: &'static str, _latency: Duration) {}
+
+    /// Called once a received message has been handled and settled with the
+    /// broker, whether it succeeded or failed.
+    ///
+    /// `type_name` is the type name of the message and `duration` is the
+    /// time from receiving the message to settling it, excluding its
+    /// [queue latency](Self::on_queue_latency).
+    fn on_processing_latency(
+        &self,
+        _kind: MessageKind,
+        _type_name: &'static str,
+        _duration: Duration,
+    ) {
+    }
+
+    /// Called when a received message is still being handled after the
+    /// configured [`BusConfig::slow_threshold`].
+    ///
+    /// `type_name` is the type name of the message and `elapsed` is how long
+    /// it has been running. The message keeps running; this is called at
+    /// most once per message.
+    ///
+    /// [`BusConfig::slow_threshold`]: crate::config::BusConfig::slow_threshold
+    fn on_slow_message(&self, _kind: MessageKind, _type_name: &'static str, _elapsed: Duration) {}
+
+    /// Called every [`BusConfig::heartbeat_interval`] while the bus's
+    /// processing loop is running, whether or not messages arrive.
+    ///
+    /// Unlike the other hooks, heartbeats continue while the bus is idle
+    /// and stop when its loop ends, so an alert on missing heartbeats tells
+    /// a dead consumer apart from an idle one.
+    ///
+    /// [`BusConfig::heartbeat_interval`]: crate::config::BusConfig::heartbeat_interval
+    fn on_heartbeat(&self) {}
+
+    /// Called with a snapshot of the bus's runtime gauges after every
+    /// [heartbeat](Self::on_heartbeat).
+    ///
+    /// Record each field as a gauge to observe how much work the bus holds
+    /// over time. See [`MessageBus::stats`](crate::bus::MessageBus::stats).
+    fn on_stats(&self, _stats: &BusStats) {}
+}
@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+
+/// A read model schema that can be migrated from its previous version.
+///
+/// When a read model's schema changes, rows projected with the old schema
+/// become incompatible. Implement `ProjectionMigration` on the new schema to
+/// describe how an old row becomes a new one; chains of migrations are
+/// expressed by implementing it on every version in turn.
+///
+/// Store rows as [`Versioned`] so that each records the schema it was
+/// written with. Rows can then be migrated:
+///
+/// - lazily, by a `Viewer` that migrates outdated rows as it reads them, or
+/// - eagerly, by a deploy step that migrates and rewrites every outdated row.
+///
+/// ```rust,ignore
+/// impl ProjectionMigration for OrderSummaryV2 {
+///     type Previous = OrderSummaryV1;
+///
+///     fn version() -> u32 {
+///         2
+///     }
+///
+///     fn migrate(previous: OrderSummaryV1) -> Self {
+///         Self { id: previous.id, total_cents: previous.total * 100 }
+///     }
+/// }
+/// ```
+pub trait ProjectionMigration: Sized {
+    /// The schema this version migrates from.
+    type Previous;
+
+    /// The schema version of this read model, increasing with each change.
+    fn version() -> u32;
+
+    /// Migrates a row written with the previous schema.
+    fn migrate(previous: Self::Previous) -> Self;
+}
+
+/// A read model row together with the schema version it was written with.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Versioned<T> {
+    /// The schema version of `model`.
+    pub version: u32,
+
+    /// The read model row.
+    pub model: T,
+}
+
+impl<T: ProjectionMigration> Versioned<T> {
+    /// Wraps a row written with the current schema of `T`.
+    pub fn new(model: T) -> Self {
+        Self {
+            version: T::version(),
+            model,
+        }
+    }
+
+    /// Whether the row was written with the current schema of `T`.
+    pub fn is_current(&self) -> bool {
+        self.version == T::version()
+    }
+}
+
+impl<T> Versioned<T> {
+    /// Migrates the row to the next schema version.
+    pub fn migrate<N>(self) -> Versioned<N>
+    where
+        N: ProjectionMigration<Previous = T>,
+    {
+        Versioned::new(N::migrate(self.model))
+    }
+}
@@ -0,0 +1,18 @@
+use crate::{driver::MessageBusDriver, message::DriverMessage};
+
+/// A read-only tap on every message the bus publishes.
+///
+/// A `PublishObserver` is notified after each message is successfully
+/// published to the broker, including committed events, policy side
+/// effects, and fanned-out commands. It is meant for concerns such as an
+/// audit log or a change-data-capture stream that must see everything the
+/// bus emits without being coupled to the domain. Observers cannot alter or
+/// reject messages.
+///
+/// Observers are called inline after the publish and must not block;
+/// forward messages to a channel if they need slow processing. They are
+/// supplied by [`MessageBusDriver::publish_observers`].
+pub trait PublishObserver<D: MessageBusDriver>: Send + Sync {
+    /// Called after `message` was published.
+    fn observed(&self, message: &DriverMessage<D>);
+}
@@ -0,0 +1,255 @@
+use std::future;
+
+use anyhow::Result;
+
+use crate::{
+    driver::MessageBusDriver,
+    handler::{Command, CommandHandler, HandlerOutcome},
+};
+
+/// Whether a [`CommandPipeline`] continues after a stage.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StageFlow {
+    /// Run the next stage.
+    Continue,
+
+    /// Skip the remaining stages. The command makes no change, and its unit
+    /// of work is rolled back.
+    Stop,
+}
+
+/// A stage run before the mutation of a [`CommandPipeline`], such as
+/// validation or authorization.
+///
+/// A stage short-circuits the pipeline by returning [`StageFlow::Stop`], or
+/// by failing, e.g. with a [`BusError::Validation`]. It may read through the
+/// unit of work, but should leave mutation to the pipeline's handler.
+///
+/// The unit type `()` is the empty stage, and a pair `(A, B)` runs `A` and
+/// then `B`.
+///
+/// [`BusError::Validation`]: crate::error::BusError::Validation
+pub trait Stage<C, D: MessageBusDriver>: Clone + Send + Sync {
+    /// Runs the stage for a command.
+    fn run(
+        &self,
+        uow: &mut D::UnitOfWork,
+        cmd: &C,
+    ) -> impl Future<Output = Result<StageFlow>> + Send;
+}
+
+impl<C, D: MessageBusDriver> Stage<C, D> for () {
+    fn run(
+        &self,
+        _uow: &mut D::UnitOfWork,
+        _cmd: &C,
+    ) -> impl Future<Output = Result<StageFlow>> + Send {
+        future::ready(Ok(StageFlow::Continue))
+    }
+}
+
+impl<C, D, A, B> Stage<C, D> for (A, B)
+where
+    C: Sync,
+    D: MessageBusDriver,
+    A: Stage<C, D>,
+    B: Stage<C, D>,
+{
+    async fn run(&self, uow: &mut D::UnitOfWork, cmd: &C) -> Result<StageFlow> {
+        match self.0.run(uow, cmd).await? {
+            StageFlow::Continue => self.1.run(uow, cmd).await,
+            StageFlow::Stop => Ok(StageFlow::Stop),
+        }
+    }
+}
+
+/// A [`Stage`] checking a command with a synchronous function.
+///
+/// Created by [`CommandPipeline::validate`].
+#[derive(Clone, Debug)]
+pub struct Validate<F>(F);
+
+impl<C, D, F> Stage<C, D> for Validate<F>
+where
+    D: MessageBusDriver,
+    F: Fn(&C) -> Result<()> + Clone + Send + Sync,
+{
+    fn run(
+        &self,
+        _uow: &mut D::UnitOfWork,
+        cmd: &C,
+    ) -> impl Future<Output = Result<StageFlow>> + Send {
+        future::ready((self.0)(cmd).map(|()| StageFlow::Continue))
+    }
+}
+
+/// A stage run after the mutation of a [`CommandPipeline`] changed state,
+/// such as recording a notification.
+///
+/// Notifiers run within the command's unit of work, before it commits, so
+/// a failing notifier rolls the command back. Notifications that should
+/// leave the process are best recorded as domain events, which the bus
+/// publishes once the unit of work commits.
+///
+/// The unit type `()` is the empty notifier, and a pair `(A, B)` runs `A`
+/// and then `B`.
+pub trait Notifier<D: MessageBusDriver>: Clone + Send + Sync {
+    /// Runs the notifier for the outcome of a command.
+    fn notify(
+        &self,
+        uow: &mut D::UnitOfWork,
+        outcome: &HandlerOutcome<D::Identifier>,
+    ) -> impl Future<Output = Result<()>> + Send;
+}
+
+impl<D: MessageBusDriver> Notifier<D> for () {
+    fn notify(
+        &self,
+        _uow: &mut D::UnitOfWork,
+        _outcome: &HandlerOutcome<D::Identifier>,
+    ) -> impl Future<Output = Result<()>> + Send {
+        future::ready(Ok(()))
+    }
+}
+
+impl<D, A, B> Notifier<D> for (A, B)
+where
+    D: MessageBusDriver,
+    D::Identifier: Sync,
+    A: Notifier<D>,
+    B: Notifier<D>,
+{
+    async fn notify(
+        &self,
+        uow: &mut D::UnitOfWork,
+        outcome: &HandlerOutcome<D::Identifier>,
+    ) -> Result<()> {
+        self.0.notify(uow, outcome).await?;
+        self.1.notify(uow, outcome).await
+    }
+}
+
+/// A [`CommandHandler`] that runs a command through typed, ordered stages.
+///
+/// Complex commands are often a pipeline of validation, authorization,
+/// mutation, and notification. `CommandPipeline` composes these as separate
+/// stages around the handler performing the mutation, instead of cramming
+/// them into one `handle`:
+///
+/// ```rust,ignore
+/// let pipeline = CommandPipeline::new(PlaceOrderHandler)
+///     .validate(|cmd: &PlaceOrder| {
+///         let mut errors = FieldErrors::new();
+///         if cmd.lines.is_empty() {
+///             errors.add("lines", "must not be empty");
+///         }
+///         Ok(errors.into_result()?)
+///     })
+///     .stage(CheckCreditLimit)
+///     .notify(RecordSalesNotification);
+/// ```
+///
+/// The stages run in the order they were added, then the handler, then,
+/// if the handler changed state, the notifiers. All of them share the
+/// command's unit of work, and a stage that fails or stops keeps every
+/// later stage and the handler from running. A pipeline is itself a
+/// command handler, so it can serve as a driver's `Handler`, or be called
+/// from one for the commands that need it; the bus then runs it in
+/// [`dispatch`](crate::bus::MessageBus::dispatch) like any handler.
+///
+/// The handler's lifecycle hooks, concurrency key, queueing, and required
+/// permissions apply to the whole pipeline. Commands run through a
+/// pipeline must be `Sync`, since every stage borrows them.
+#[derive(Clone, Debug)]
+pub struct CommandPipeline<H, S = (), N = ()> {
+    handler: H,
+    stages: S,
+    notifiers: N,
+}
+
+impl<H> CommandPipeline<H> {
+    /// Creates a pipeline whose mutation stage is `handler`.
+    pub fn new(handler: H) -> Self {
+        Self {
+            handler,
+            stages: (),
+            notifiers: (),
+        }
+    }
+}
+
+impl<H, S, N> CommandPipeline<H, S, N> {
+    /// Adds a stage to run after the stages added so far.
+    pub fn stage<T>(self, stage: T) -> CommandPipeline<H, (S, T), N> {
+        CommandPipeline {
+            handler: self.handler,
+            stages: (self.stages, stage),
+            notifiers: self.notifiers,
+        }
+    }
+
+    /// Adds a stage checking the command with a synchronous function, such
+    /// as one collecting [`FieldErrors`](crate::error::FieldErrors).
+    pub fn validate<F>(self, check: F) -> CommandPipeline<H, (S, Validate<F>), N> {
+        self.stage(Validate(check))
+    }
+
+    /// Adds a notifier to run after the notifiers added so far.
+    pub fn notify<T>(self, notifier: T) -> CommandPipeline<H, S, (N, T)> {
+        CommandPipeline {
+            handler: self.handler,
+            stages: self.stages,
+            notifiers: (self.notifiers, notifier),
+        }
+    }
+}
+
+impl<C, D, H, S, N> CommandHandler<C, D> for CommandPipeline<H, S, N>
+where
+    C: Command + Sync,
+    D: MessageBusDriver,
+    D::Identifier: Sync,
+    H: CommandHandler<C, D>,
+    S: Stage<C, D>,
+    N: Notifier<D>,
+{
+    async fn handle(
+        &self,
+        uow: &mut D::UnitOfWork,
+        cmd: C,
+    ) -> Result<HandlerOutcome<D::Identifier>> {
+        if self.stages.run(uow, &cmd).await? == StageFlow::Stop {
+            println!("Command pipeline stopped before its handler.");
+            return Ok(HandlerOutcome::NoChange);
+        }
+        let outcome = self.handler.handle(uow, cmd).await?;
+        if outcome.is_changed() {
+            self.notifiers.notify(uow, &outcome).await?;
+        }
+        Ok(outcome)
+    }
+
+    fn before_handle(
+        &self,
+        uow: &mut D::UnitOfWork,
+        cmd: &C,
+    ) -> impl Future<Output = Result<()>> + Send {
+        self.handler.before_handle(uow, cmd)
+    }
+
+    fn after_handle(&self, uow: &mut D::UnitOfWork) -> impl Future<Output = Result<()>> + Send {
+        self.handler.after_handle(uow)
+    }
+
+    fn concurrency_key(&self, cmd: &C) -> Option<String> {
+        self.handler.concurrency_key(cmd)
+    }
+
+    fn should_queue(&self, cmd: &C) -> bool {
+        self.handler.should_queue(cmd)
+    }
+
+    fn required_permissions(&self, cmd: &C) -> Vec<String> {
+        self.handler.required_permissions(cmd)
+    }
+}
@@ -1,4 +1,12 @@
-use crate::{driver::MessageBusDriver, factory::Factory};
+use std::{future, sync::Arc};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    driver::MessageBusDriver,
+    factory::Factory,
+    handler::Command,
+    message::SideEffect,
+};
 use anyhow::Result;
 
 /// Provides read-only access to domain state for a `Policy`.
@@ -28,6 +36,15 @@ pub trait PolicyContext: Send {
     /// a chance to release connections, clean up internal state, or perform
     /// post-read cleanup. It is guaranteed to be called once per context.
     fn close(self) -> impl Future<Output = Result<()>> + Send;
+
+    /// The clock available to policies evaluated with this context.
+    ///
+    /// Defaults to the real [`SystemClock`]. Override this to inject a
+    /// deterministic clock (such as [`ManualClock`](crate::clock::ManualClock))
+    /// when testing time-dependent policies.
+    fn clock(&self) -> &dyn Clock {
+        &SystemClock
+    }
 }
 
 /// A stateless rule that reacts to domain events.
@@ -52,9 +69,12 @@ pub trait Policy<E: Send, D: MessageBusDriver>: Clone + Send + Sync {
 
     /// Apply this policy to the given domain event using the provided context.
     ///
-    /// This function must return a list of zero or more messages that should
-    /// be emitted in response to the event. These may include commands (to be
-    /// processed by a command handler) or projections (to be handled by a projector).
+    /// This function must return zero or more messages that should be emitted
+    /// in response to the event. These may include commands (to be processed
+    /// by a command handler) or projections (to be handled by a projector).
+    ///
+    /// Any [`IntoPolicyOutput`] may be returned: a `Vec` of messages, a single
+    /// message, an `Option`, or `()` when nothing should be emitted.
     ///
     /// This function must not mutate domain state and should only perform reads
     /// using the provided context.
@@ -62,5 +82,191 @@ pub trait Policy<E: Send, D: MessageBusDriver>: Clone + Send + Sync {
         &self,
         ctx: &mut D::PolicyContext,
         event: E,
-    ) -> impl Future<Output = Result<Vec<Self::Output>>> + Send;
+    ) -> impl Future<Output = Result<impl IntoPolicyOutput<Self::Output>>> + Send;
+
+    /// Whether this policy subscribes to the given event.
+    ///
+    /// The message bus consults this before creating a `PolicyContext`, and
+    /// events the policy does not subscribe to are settled without calling
+    /// [`apply`](Self::apply), as configured by
+    /// [`BusConfig::unmatched_events`](crate::config::BusConfig::unmatched_events).
+    /// Override this to declare the subset of
+    /// event variants the policy reacts to, typically with `matches!`:
+    ///
+    /// ```rust,ignore
+    /// fn subscribes_to(&self, event: &MyEvent) -> bool {
+    ///     matches!(event, MyEvent::OrderPaid(_) | MyEvent::OrderCancelled(_))
+    /// }
+    /// ```
+    ///
+    /// This must be cheap and must not depend on anything but the event.
+    /// Defaults to `true`, subscribing to every event.
+    fn subscribes_to(&self, _event: &E) -> bool {
+        true
+    }
+
+    /// Whether this policy applies the events committed together by one
+    /// command as a whole, with [`apply_batch`](Self::apply_batch).
+    ///
+    /// Defaults to `false`: each subscribed event of a received
+    /// [`Message::EventBatch`] is then handled in turn, exactly as if it had
+    /// been published on its own, with its own context and enrichment.
+    ///
+    /// [`Message::EventBatch`]: crate::message::Message::EventBatch
+    fn applies_batches(&self) -> bool {
+        false
+    }
+
+    /// Apply this policy to the events committed together by one command.
+    ///
+    /// Called for each received [`Message::EventBatch`] when
+    /// [`applies_batches`](Self::applies_batches) returns `true`, with the
+    /// events in the order they were committed, so that a policy can decide
+    /// based on the whole set, e.g. only once both `Debited` and `Credited`
+    /// happened. The events share one context, enriched with each
+    /// subscribed event before the batch is applied.
+    ///
+    /// The default implementation applies the policy to each subscribed
+    /// event in turn.
+    ///
+    /// [`Message::EventBatch`]: crate::message::Message::EventBatch
+    fn apply_batch(
+        &self,
+        ctx: &mut D::PolicyContext,
+        events: Vec<E>,
+    ) -> impl Future<Output = Result<Vec<Self::Output>>> + Send {
+        async move {
+            let mut outputs = Vec::new();
+            for event in events {
+                if self.subscribes_to(&event) {
+                    let output = self.apply(ctx, event).await?;
+                    outputs.extend(output.into_policy_output());
+                }
+            }
+            Ok(outputs)
+        }
+    }
+}
+
+/// A value that can be returned from [`Policy::apply`].
+///
+/// `IntoPolicyOutput` lets policies return whatever shape is most natural
+/// for the number of side effects they produce, rather than always wrapping
+/// them in a `Vec`:
+///
+/// - `Vec<O>` for any number of side effects.
+/// - `Option<O>` for at most one side effect.
+/// - [`SideEffect`] for exactly one side effect.
+/// - `()` when the policy never emits anything.
+pub trait IntoPolicyOutput<O>: Send {
+    /// Converts this value into the list of side effects to publish.
+    fn into_policy_output(self) -> Vec<O>;
+}
+
+impl<O: Send> IntoPolicyOutput<O> for Vec<O> {
+    fn into_policy_output(self) -> Vec<O> {
+        self
+    }
+}
+
+impl<O: Send> IntoPolicyOutput<O> for Option<O> {
+    fn into_policy_output(self) -> Vec<O> {
+        self.into_iter().collect()
+    }
+}
+
+impl<O> IntoPolicyOutput<O> for () {
+    fn into_policy_output(self) -> Vec<O> {
+        Vec::new()
+    }
+}
+
+impl<C, P> IntoPolicyOutput<SideEffect<C, P>> for SideEffect<C, P>
+where
+    C: Send + Command,
+    P: Send,
+{
+    fn into_policy_output(self) -> Vec<SideEffect<C, P>> {
+        vec![self]
+    }
+}
+
+/// A `Policy` built from declarative event-to-command mappings.
+///
+/// Many policies only react to an event by issuing a command built from the
+/// event's fields. `EventToCommandMap` expresses these without a
+/// hand-written `Policy` implementation:
+///
+/// ```rust,ignore
+/// let policy = EventToCommandMap::new()
+///     .map::<OrderPaid, _>(|e| MyCommand::ShipOrder(ShipOrder { id: e.order_id }))
+///     .map::<OrderCancelled, _>(|e| MyCommand::Refund(Refund { id: e.order_id }));
+/// ```
+///
+/// Each mapping applies to events that convert into its event type via
+/// `TryFrom`, and every matching mapping issues one command. The policy never
+/// reads from its `PolicyContext` and emits no projections.
+///
+/// To use it as a driver's `Policy`, implement `From<&MyDriver>` for
+/// `EventToCommandMap<MyEvent, MyCommand>` and register the mappings there.
+pub struct EventToCommandMap<E, C> {
+    mappings: Vec<Mapping<E, C>>,
+}
+
+/// A single event-to-command mapping.
+type Mapping<E, C> = Arc<dyn Fn(&E) -> Option<C> + Send + Sync>;
+
+impl<E, C> EventToCommandMap<E, C> {
+    /// Creates a policy without any mappings.
+    pub fn new() -> Self {
+        Self {
+            mappings: Vec::new(),
+        }
+    }
+
+    /// Issues the command returned by `f` for every event that converts
+    /// into a `T`.
+    pub fn map<T, F>(mut self, f: F) -> Self
+    where
+        E: Clone,
+        T: TryFrom<E>,
+        F: Fn(T) -> C + Send + Sync + 'static,
+    {
+        self.mappings.push(Arc::new(move |event: &E| {
+            T::try_from(event.clone()).ok().map(&f)
+        }));
+        self
+    }
+}
+
+impl<E, C> Default for EventToCommandMap<E, C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E, C> Clone for EventToCommandMap<E, C> {
+    fn clone(&self) -> Self {
+        Self {
+            mappings: self.mappings.clone(),
+        }
+    }
+}
+
+impl<D: MessageBusDriver> Policy<D::Event, D> for EventToCommandMap<D::Event, D::Command> {
+    type Output = SideEffect<D::Command, D::Projection>;
+
+    fn apply(
+        &self,
+        _ctx: &mut D::PolicyContext,
+        event: D::Event,
+    ) -> impl Future<Output = Result<impl IntoPolicyOutput<Self::Output>>> + Send {
+        let side_effects = self
+            .mappings
+            .iter()
+            .filter_map(|mapping| mapping(&event))
+            .map(SideEffect::Command)
+            .collect::<Vec<_>>();
+        future::ready(Ok(side_effects))
+    }
 }
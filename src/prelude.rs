@@ -1,9 +1,33 @@
+pub use crate::auth::*;
+#[cfg(feature = "axum")]
+pub use crate::axum::*;
 pub use crate::broker::*;
 pub use crate::bus::*;
+pub use crate::clock::*;
+pub use crate::config::*;
+pub use crate::coordinator::*;
+pub use crate::dead_letter::*;
+pub use crate::deadline::*;
 pub use crate::driver::*;
+pub use crate::enricher::*;
+pub use crate::error::*;
 pub use crate::factory::*;
+pub use crate::group::*;
 pub use crate::handler::*;
+pub use crate::id::*;
+pub use crate::idempotency::*;
 pub use crate::message::*;
+pub use crate::metrics::*;
+pub use crate::migration::*;
+pub use crate::observer::*;
+pub use crate::pipeline::*;
 pub use crate::policy::*;
+pub use crate::progress::*;
 pub use crate::projector::*;
+pub use crate::rate::*;
+pub use crate::registry::*;
+pub use crate::replay::*;
+pub use crate::rollout::*;
+pub use crate::split::*;
+pub use crate::trace::*;
 pub use crate::uow::*;
@@ -0,0 +1,105 @@
+use anyhow::Result;
+use futures::channel::mpsc::UnboundedSender;
+
+use crate::bus::DispatchResult;
+
+tokio::task_local! {
+    static PROGRESS: ProgressSink;
+}
+
+/// An item of the stream returned by
+/// [`MessageBus::dispatch_streaming`](crate::bus::MessageBus::dispatch_streaming).
+#[derive(Debug)]
+pub enum Progress<T> {
+    /// The handler reported progress.
+    Update(ProgressReport),
+
+    /// The command finished, successfully or not. Always the last item.
+    Done(Result<DispatchResult<T>>),
+}
+
+/// How far a long-running command has come.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProgressReport {
+    /// The units of work completed so far, e.g. rows imported.
+    pub completed: u64,
+
+    /// The total units of work, if known.
+    pub total: Option<u64>,
+
+    /// A human-readable description of the current step, if any.
+    pub message: Option<String>,
+}
+
+impl ProgressReport {
+    /// A report of `completed` units of work.
+    pub fn new(completed: u64) -> Self {
+        Self {
+            completed,
+            ..Self::default()
+        }
+    }
+
+    /// Sets the total units of work.
+    pub fn with_total(mut self, total: u64) -> Self {
+        self.total = Some(total);
+        self
+    }
+
+    /// Sets the description of the current step.
+    pub fn with_message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+}
+
+/// Where a command handler reports its progress to the caller.
+///
+/// Commands dispatched with
+/// [`MessageBus::dispatch_streaming`](crate::bus::MessageBus::dispatch_streaming)
+/// run with a sink whose reports are streamed back to the caller, e.g. to
+/// drive a progress bar:
+///
+/// ```rust,ignore
+/// async fn handle(&self, uow: &mut MyUnitOfWork, cmd: ImportRows) -> Result<HandlerOutcome<Uuid>> {
+///     let total = cmd.rows.len() as u64;
+///     for (done, row) in cmd.rows.into_iter().enumerate() {
+///         uow.rows().insert(row).await?;
+///         report_progress(ProgressReport::new(done as u64 + 1).with_total(total));
+///     }
+///     Ok(HandlerOutcome::Changed(None))
+/// }
+/// ```
+///
+/// Reporting is cheap and never blocks. Reports made after the command
+/// finished or the caller dropped the stream, or outside
+/// `dispatch_streaming`, are discarded.
+#[derive(Clone, Debug)]
+pub struct ProgressSink(UnboundedSender<ProgressReport>);
+
+impl ProgressSink {
+    /// A sink forwarding reports to `sender`.
+    pub(crate) fn new(sender: UnboundedSender<ProgressReport>) -> Self {
+        Self(sender)
+    }
+
+    /// The sink of the current task, if it runs a streaming dispatch.
+    pub fn current() -> Option<Self> {
+        PROGRESS.try_with(Clone::clone).ok()
+    }
+
+    /// Reports progress to the caller.
+    pub fn report(&self, report: ProgressReport) {
+        let _ = self.0.unbounded_send(report);
+    }
+
+    /// Runs `fut` with this sink as the current task's sink.
+    pub(crate) async fn scope<F: Future>(self, fut: F) -> F::Output {
+        PROGRESS.scope(self, fut).await
+    }
+}
+
+/// Reports progress to the current task's [`ProgressSink`], if any.
+pub fn report_progress(report: ProgressReport) {
+    let _ = PROGRESS.try_with(|sink| sink.report(report));
+}
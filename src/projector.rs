@@ -1,4 +1,250 @@
+use std::{error::Error, fmt};
+
 use anyhow::Result;
+use futures::future::BoxFuture;
+
+use crate::{error::ErrorDetails, uow::UnitOfWork};
+
+/// The outcome of successfully applying a projection.
+///
+/// Most projections complete without anything worth recording, in which case
+/// [`ProjectionResult::default`] should be returned. Projections into systems
+/// that confirm writes with their own identifier can attach it as a receipt.
+/// Projections into systems that confirm asynchronously can return
+/// [`ProjectionResult::pending`] to defer acknowledging the projection.
+///
+/// A projection's completion can itself be a fact other parts of the system
+/// react to (e.g. "search index updated"). Such follow-up side effects, of
+/// type `S`, are attached with [`ProjectionResult::with_follow_up`] and
+/// published by the bus once the projection succeeds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProjectionResult<S = ()> {
+    /// An optional identifier or receipt returned by the external system.
+    pub receipt: Option<String>,
+
+    /// Whether the projection is complete or awaits downstream confirmation.
+    pub outcome: ProjectionOutcome,
+
+    /// Side effects to publish after the projection succeeds.
+    pub follow_ups: Vec<S>,
+}
+
+impl<S> Default for ProjectionResult<S> {
+    fn default() -> Self {
+        Self {
+            receipt: None,
+            outcome: ProjectionOutcome::default(),
+            follow_ups: Vec::new(),
+        }
+    }
+}
+
+impl<S> ProjectionResult<S> {
+    /// A projection result carrying the given external receipt.
+    pub fn with_receipt(receipt: impl Into<String>) -> Self {
+        Self {
+            receipt: Some(receipt.into()),
+            ..Self::default()
+        }
+    }
+
+    /// A projection result awaiting confirmation of the given token.
+    pub fn pending(token: impl Into<ConfirmationToken>) -> Self {
+        Self {
+            outcome: ProjectionOutcome::Pending(token.into()),
+            ..Self::default()
+        }
+    }
+
+    /// Add a side effect to publish after the projection succeeds.
+    pub fn with_follow_up(mut self, side_effect: impl Into<S>) -> Self {
+        self.follow_ups.push(side_effect.into());
+        self
+    }
+}
+
+/// Whether a projection has completed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum ProjectionOutcome {
+    /// The projection completed and its message can be acknowledged.
+    #[default]
+    Complete,
+
+    /// The projection was handed to a downstream system that confirms
+    /// asynchronously.
+    ///
+    /// The bus defers acknowledging the projection message until
+    /// [`MessageBus::confirm`] is called with the token, or negatively
+    /// acknowledges it on [`MessageBus::reject`]. Deferred acknowledgements
+    /// are held in memory, so a message whose bus stops before it is
+    /// confirmed is redelivered by the broker and projected again.
+    ///
+    /// [`MessageBus::confirm`]: crate::bus::MessageBus::confirm
+    /// [`MessageBus::reject`]: crate::bus::MessageBus::reject
+    Pending(ConfirmationToken),
+}
+
+/// Identifies a projection awaiting downstream confirmation.
+///
+/// Tokens are chosen by the projector, typically from an identifier the
+/// downstream system will echo back when it confirms, and must be unique
+/// among the projections pending on a bus.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ConfirmationToken(pub String);
+
+impl From<String> for ConfirmationToken {
+    fn from(token: String) -> Self {
+        Self(token)
+    }
+}
+
+impl From<&str> for ConfirmationToken {
+    fn from(token: &str) -> Self {
+        Self(token.to_owned())
+    }
+}
+
+/// Persists receipts returned by projectors.
+///
+/// A `ReceiptStore` records the downstream identifiers of successful
+/// projections so they can be reconciled against the external system later.
+/// It is configured via [`BusConfig::with_receipt_store`].
+///
+/// [`BusConfig::with_receipt_store`]: crate::config::BusConfig::with_receipt_store
+pub trait ReceiptStore: Send + Sync {
+    /// Record the receipt of a projection of the given type.
+    ///
+    /// If recording fails, the projection message is treated as failed and
+    /// will be retried, so projections must be safe to re-apply.
+    fn record(&self, projection: &'static str, receipt: String) -> BoxFuture<'_, Result<()>>;
+}
+
+/// Identifies the read model a projection builds.
+///
+/// Returned by [`Projector::projection_id`] and [`Projector::depends_on`] to
+/// declare that one read model must be built before another.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ProjectionId(pub String);
+
+impl From<String> for ProjectionId {
+    fn from(id: String) -> Self {
+        Self(id)
+    }
+}
+
+impl From<&str> for ProjectionId {
+    fn from(id: &str) -> Self {
+        Self(id.to_owned())
+    }
+}
+
+impl fmt::Display for ProjectionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Tracks which projections have been applied for which events.
+///
+/// Used to order projections that [`depend on`](Projector::depends_on)
+/// each other. After a projection with a
+/// [`projection_id`](Projector::projection_id) is applied, the bus records
+/// a checkpoint for it and the event that caused it. A dependent projection
+/// caused by the same event is only applied once that checkpoint has been
+/// reached. The store must be shared by every instance of the bus.
+///
+/// A checkpoint store is configured via
+/// [`BusConfig::with_projection_checkpoints`](crate::config::BusConfig::with_projection_checkpoints).
+pub trait ProjectionCheckpoints: Send + Sync {
+    /// Record that the projection was applied for the given event.
+    fn record(&self, projection: &ProjectionId, event: &str) -> BoxFuture<'_, Result<()>>;
+
+    /// Whether the projection has been applied for the given event.
+    fn reached(&self, projection: &ProjectionId, event: &str) -> BoxFuture<'_, Result<bool>>;
+}
+
+/// A projection that permanently failed, as handed to a
+/// [`ProjectionErrorHandler`].
+#[derive(Clone, Debug)]
+pub struct ProjectionFailure<P> {
+    /// The projection that failed.
+    pub projection: P,
+
+    /// The number of times the projection was delivered before giving up.
+    pub attempts: u32,
+
+    /// The error from the final failed attempt.
+    pub error: String,
+
+    /// The structured form of [`error`](Self::error), as classified by
+    /// [`MessageBusDriver::error_details`].
+    ///
+    /// [`MessageBusDriver::error_details`]: crate::driver::MessageBusDriver::error_details
+    pub details: ErrorDetails,
+}
+
+/// Handles projections that permanently failed, in place of the broker's
+/// dead-letter queue.
+///
+/// Projections usually fail permanently because of bad external data, and
+/// are best reconciled by hand against the read model rather than replayed
+/// from a dead-letter queue shared with commands and events. A handler can,
+/// for instance, record them in a "failed projections" table. It is
+/// supplied by [`MessageBusDriver::projection_error_handler`].
+///
+/// A projection is handed to the handler once it exhausts its retries or
+/// is rejected by [`Projector::validate`], and its message is then
+/// acknowledged and reported as [`Settled::HandedOff`]. If the handler
+/// fails, the message is dead-lettered as usual.
+///
+/// [`Settled::HandedOff`]: crate::bus::Settled::HandedOff
+/// [`MessageBusDriver::projection_error_handler`]: crate::driver::MessageBusDriver::projection_error_handler
+pub trait ProjectionErrorHandler<P>: Send + Sync {
+    /// Handle a projection that permanently failed.
+    fn handle(&self, failure: ProjectionFailure<P>) -> BoxFuture<'_, Result<()>>;
+}
+
+/// A projection type able to represent the deletion of an entity.
+///
+/// Tombstones give deletions one standard shape instead of every deletion
+/// event becoming a projection that projectors special-case. A driver's
+/// projection type typically gets a dedicated variant:
+///
+/// ```rust,ignore
+/// impl Tombstone for MyProjection {
+///     type Id = Uuid;
+///
+///     fn tombstone(id: Uuid) -> Self {
+///         MyProjection::Tombstone(id)
+///     }
+///
+///     fn as_tombstone(&self) -> Option<&Uuid> {
+///         match self {
+///             MyProjection::Tombstone(id) => Some(id),
+///             _ => None,
+///         }
+///     }
+/// }
+/// ```
+///
+/// Policies emit `P::tombstone(id)` when an aggregate is deleted, and
+/// projectors remove (or soft-delete) every read model of the entity when
+/// they apply one, so that viewers stop returning it.
+pub trait Tombstone: Sized {
+    /// The identifier of a deleted entity.
+    type Id;
+
+    /// The tombstone projection for the given entity.
+    fn tombstone(id: Self::Id) -> Self;
+
+    /// The deleted entity, if this projection is a tombstone.
+    fn as_tombstone(&self) -> Option<&Self::Id>;
+
+    /// Whether this projection is a tombstone.
+    fn is_tombstone(&self) -> bool {
+        self.as_tombstone().is_some()
+    }
+}
 
 /// A handler responsible for executing projections.
 ///
@@ -10,7 +256,23 @@ use anyhow::Result;
 ///
 /// Projectors must be idempotent and side-effectful. Unlike command handlers,
 /// they do not interact with the domain model and should not mutate domain aggregates.
-pub trait Projector<P>: Clone + Sync + Send {
+///
+/// `S` is the type of the follow-up side effects a projector may return,
+/// which is [`DriverSideEffect`](crate::message::DriverSideEffect) for a
+/// driver's projector. It defaults to `()`, matching the default of
+/// [`ProjectionResult`], so projectors written before follow-ups existed
+/// keep compiling. Projectors that never return follow-ups can be
+/// implemented for any `S`:
+///
+/// ```rust,ignore
+/// impl<S: Send> Projector<SearchIndexUpdate, S> for SearchProjector {
+///     async fn project(&self, update: SearchIndexUpdate) -> Result<ProjectionResult<S>> {
+///         self.index.upsert(update).await?;
+///         Ok(ProjectionResult::default())
+///     }
+/// }
+/// ```
+pub trait Projector<P, S = ()>: Clone + Sync + Send {
     /// Applies the given projection.
     ///
     /// The provided message contains all necessary data to perform the
@@ -19,5 +281,183 @@ pub trait Projector<P>: Clone + Sync + Send {
     /// and return a result indicating success or failure.
     ///
     /// Projection logic must be safe to retry and should not mutate domain state.
-    fn project(&self, projection: P) -> impl Future<Output = Result<()>> + Send;
+    /// Side effects that cannot be repeated safely, such as sending emails,
+    /// should be skipped when [`is_replay`](crate::replay::is_replay) is true.
+    ///
+    /// If the external system returns a confirmation (e.g. a document id),
+    /// it can be returned as a receipt on the [`ProjectionResult`]. The bus
+    /// persists receipts to the configured
+    /// [`BusConfig::receipt_store`](crate::config::BusConfig::receipt_store),
+    /// if any, for later reconciliation. If the external system confirms
+    /// asynchronously, a [`ProjectionOutcome::Pending`] result defers the
+    /// acknowledgement of the projection until it is confirmed.
+    ///
+    /// Any [`follow_ups`](ProjectionResult::follow_ups) are published once
+    /// the projection has succeeded, before it is acknowledged.
+    fn project(&self, projection: P) -> impl Future<Output = Result<ProjectionResult<S>>> + Send;
+
+    /// Whether the given projection is applied inline with its command.
+    ///
+    /// When the command is dispatched with
+    /// [`MessageBus::dispatch_consistent`], inline projections are applied
+    /// inside its [`UnitOfWork`] before it commits, and are not published to
+    /// the broker, trading throughput for a read model that never lags the
+    /// write model. For commands dispatched any other way, inline
+    /// projections are published and applied like any other.
+    ///
+    /// Defaults to `false`, meaning every projection is applied asynchronously.
+    ///
+    /// [`UnitOfWork`]: crate::uow::UnitOfWork
+    /// [`MessageBus::dispatch_consistent`]: crate::bus::MessageBus::dispatch_consistent
+    fn is_inline(&self, _projection: &P) -> bool {
+        false
+    }
+
+    /// Checks that a projection is well-formed before it is applied.
+    ///
+    /// Called by the bus before [`project`](Self::project). A projection
+    /// that fails validation will never succeed, so rather than being
+    /// retried it is dead-lettered immediately with
+    /// [`BusError::ProjectionInvalid`](crate::error::BusError::ProjectionInvalid).
+    /// Validation must not touch the external system.
+    ///
+    /// The default implementation accepts every projection.
+    fn validate(&self, _projection: &P) -> Result<(), ValidationError> {
+        Ok(())
+    }
+
+    /// The read model the given projection builds, if it is tracked.
+    ///
+    /// When [`BusConfig::projection_checkpoints`] is set, the bus records a
+    /// checkpoint once a projection with an id is applied, letting
+    /// projections that [`depend on`](Self::depends_on) it proceed.
+    /// Defaults to `None`.
+    ///
+    /// [`BusConfig::projection_checkpoints`]: crate::config::BusConfig::projection_checkpoints
+    fn projection_id(&self, _projection: &P) -> Option<ProjectionId> {
+        None
+    }
+
+    /// The read model that must be built before the given projection, if
+    /// any.
+    ///
+    /// When [`BusConfig::projection_checkpoints`] is set, a received
+    /// projection is held until the projection it depends on, caused by the
+    /// same event, has been applied. For example, an order summary can
+    /// depend on the line items derived from the same `OrderPlaced` event:
+    ///
+    /// ```rust,ignore
+    /// fn depends_on(&self, projection: &MyProjection) -> Option<ProjectionId> {
+    ///     matches!(projection, MyProjection::OrderSummary(_)).then(|| "line-items".into())
+    /// }
+    /// ```
+    ///
+    /// A held projection fails with [`BusError::DependencyPending`] and is
+    /// negatively acknowledged for redelivery after
+    /// [`BusConfig::nack_delay`], or a second if unset. Holding it does not
+    /// count towards its retries. Defaults to `None`.
+    ///
+    /// [`BusConfig::projection_checkpoints`]: crate::config::BusConfig::projection_checkpoints
+    /// [`BusConfig::nack_delay`]: crate::config::BusConfig::nack_delay
+    /// [`BusError::DependencyPending`]: crate::error::BusError::DependencyPending
+    fn depends_on(&self, _projection: &P) -> Option<ProjectionId> {
+        None
+    }
+
+    /// Removes the read models of a deleted entity.
+    ///
+    /// Received tombstones are applied through [`project`](Self::project)
+    /// like any projection, so projectors should handle them there, e.g. by
+    /// deleting or flagging the entity's documents. `remove` lets other
+    /// callers, such as admin tooling, delete an entity's read models
+    /// directly.
+    ///
+    /// The default implementation projects the entity's
+    /// [`Tombstone::tombstone`].
+    fn remove(&self, id: P::Id) -> impl Future<Output = Result<ProjectionResult<S>>> + Send
+    where
+        P: Tombstone,
+    {
+        self.project(P::tombstone(id))
+    }
+
+    /// The key grouping projections that can be applied together, if any.
+    ///
+    /// When [`BusConfig::projection_batching`] is set, received projections
+    /// sharing a key (e.g. the search index they target) are buffered and
+    /// applied with a single [`project_batch`](Self::project_batch) call.
+    /// Defaults to `None`, applying every projection on its own.
+    ///
+    /// [`BusConfig::projection_batching`]: crate::config::BusConfig::projection_batching
+    fn batch_key(&self, _projection: &P) -> Option<String> {
+        None
+    }
+
+    /// Applies several projections sharing a [`batch_key`](Self::batch_key).
+    ///
+    /// Override this to turn many round-trips to the external system into
+    /// one bulk write. Must return one result per projection, in order. If
+    /// an error is returned, every projection of the batch is retried.
+    ///
+    /// The default implementation projects each in turn.
+    fn project_batch(
+        &self,
+        projections: Vec<P>,
+    ) -> impl Future<Output = Result<Vec<ProjectionResult<S>>>> + Send
+    where
+        P: Send,
+        S: Send,
+    {
+        async move {
+            let mut results = Vec::with_capacity(projections.len());
+            for projection in projections {
+                results.push(self.project(projection).await?);
+            }
+            Ok(results)
+        }
+    }
+}
+
+/// The reason a projection was rejected by [`Projector::validate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationError {
+    /// A description of what is wrong with the projection.
+    pub reason: String,
+}
+
+impl ValidationError {
+    /// A validation error with the given reason.
+    pub fn new(reason: impl Into<String>) -> Self {
+        Self {
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.reason)
+    }
+}
+
+impl Error for ValidationError {}
+
+/// A unit of work capable of applying projections within its transaction.
+///
+/// `InlineProjection` is implemented by a [`UnitOfWork`] that can write read
+/// models using the same transaction as the command it is executing. It is
+/// required by [`MessageBus::dispatch_consistent`], which evaluates policies
+/// against the captured events before commit and applies any projection the
+/// [`Projector`] marks as inline.
+///
+/// [`MessageBus::dispatch_consistent`]: crate::bus::MessageBus::dispatch_consistent
+pub trait InlineProjection<P>: UnitOfWork {
+    /// The events captured so far by this unit of work, in capture order.
+    fn pending_events(&self) -> &[Self::Event];
+
+    /// Apply the given projection inside this unit of work's transaction.
+    ///
+    /// Changes made here are committed or rolled back together with the
+    /// command's domain changes.
+    fn project_inline(&mut self, projection: P) -> impl Future<Output = Result<()>> + Send;
 }
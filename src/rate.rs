@@ -0,0 +1,76 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+/// A token bucket limiting how many messages are published per second.
+///
+/// The bucket holds up to one second's worth of tokens, so short bursts up
+/// to `per_second` messages pass immediately, and sustained throughput is
+/// paced to `per_second`. Clones share the same bucket.
+///
+/// Configured via
+/// [`BusConfig::with_max_publish_rate`](crate::config::BusConfig::with_max_publish_rate).
+#[derive(Clone)]
+pub struct RateLimiter {
+    per_second: f64,
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    /// A limiter allowing `per_second` messages per second.
+    ///
+    /// A rate of `0` is treated as `1`.
+    pub fn new(per_second: u32) -> Self {
+        let per_second = per_second.max(1) as f64;
+        Self {
+            per_second,
+            bucket: Arc::new(Mutex::new(Bucket {
+                tokens: per_second,
+                refilled_at: Instant::now(),
+            })),
+        }
+    }
+
+    /// The number of messages allowed per second.
+    pub fn per_second(&self) -> f64 {
+        self.per_second
+    }
+
+    /// Waits until `messages` messages may be published.
+    ///
+    /// Tokens are reserved immediately, so waiters are served in the order
+    /// they called `acquire`, and a batch larger than the bucket waits for
+    /// as long as its size requires.
+    pub async fn acquire(&self, messages: usize) {
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap();
+            let now = Instant::now();
+            let refill = now.duration_since(bucket.refilled_at).as_secs_f64() * self.per_second;
+            bucket.tokens = (bucket.tokens + refill).min(self.per_second);
+            bucket.refilled_at = now;
+            bucket.tokens -= messages as f64;
+            if bucket.tokens >= 0.0 {
+                return;
+            }
+            Duration::from_secs_f64(-bucket.tokens / self.per_second)
+        };
+        tokio::time::sleep(wait).await;
+    }
+}
+
+impl fmt::Debug for RateLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimiter")
+            .field("per_second", &self.per_second)
+            .finish()
+    }
+}
@@ -0,0 +1,277 @@
+use std::{
+    any::{Any, TypeId, type_name},
+    collections::HashMap,
+    marker::PhantomData,
+    sync::Arc,
+};
+
+use anyhow::{Result, anyhow};
+use futures::{FutureExt, future::BoxFuture};
+
+use crate::projector::{ProjectionId, ProjectionResult, Projector, ValidationError};
+
+/// A projection type whose variants can be routed to separate projectors.
+///
+/// A driver's `Projection` is typically an enum whose variants each wrap a
+/// distinct payload type. `RoutableProjection` unwraps the payload so that a
+/// [`ProjectorRegistry`] can route it by type:
+///
+/// ```rust,ignore
+/// impl RoutableProjection for MyProjection {
+///     fn payload(&self) -> &dyn Any {
+///         match self {
+///             MyProjection::SearchIndex(p) => p,
+///             MyProjection::Email(p) => p,
+///         }
+///     }
+///
+///     fn into_payload(self) -> Box<dyn Any + Send> {
+///         match self {
+///             MyProjection::SearchIndex(p) => Box::new(p),
+///             MyProjection::Email(p) => Box::new(p),
+///         }
+///     }
+/// }
+/// ```
+pub trait RoutableProjection: Send + 'static {
+    /// Borrows this projection's variant's payload.
+    fn payload(&self) -> &dyn Any;
+
+    /// Unwraps this projection into its variant's payload.
+    fn into_payload(self) -> Box<dyn Any + Send>;
+}
+
+/// A type-erased projector for a single payload type.
+trait ErasedProjector<S>: Send + Sync {
+    fn project(&self, payload: Box<dyn Any + Send>) -> BoxFuture<'_, Result<ProjectionResult<S>>>;
+
+    fn project_batch(
+        &self,
+        payloads: Vec<Box<dyn Any + Send>>,
+    ) -> BoxFuture<'_, Result<Vec<ProjectionResult<S>>>>;
+
+    fn is_inline(&self, payload: &dyn Any) -> bool;
+
+    fn validate(&self, payload: &dyn Any) -> Result<(), ValidationError>;
+
+    fn projection_id(&self, payload: &dyn Any) -> Option<ProjectionId>;
+
+    fn depends_on(&self, payload: &dyn Any) -> Option<ProjectionId>;
+
+    fn batch_key(&self, payload: &dyn Any) -> Option<String>;
+}
+
+/// Adapts a typed [`Projector`] to [`ErasedProjector`].
+struct Typed<T, PR> {
+    projector: PR,
+    _payload: PhantomData<fn(T)>,
+}
+
+impl<T, S, PR> ErasedProjector<S> for Typed<T, PR>
+where
+    T: Send + 'static,
+    S: Send + 'static,
+    PR: Projector<T, S> + 'static,
+{
+    fn project(&self, payload: Box<dyn Any + Send>) -> BoxFuture<'_, Result<ProjectionResult<S>>> {
+        match payload.downcast::<T>() {
+            Ok(payload) => self.projector.project(*payload).boxed(),
+            Err(_) => futures::future::ready(Err(anyhow!(
+                "projection payload is not a `{}`",
+                type_name::<T>()
+            )))
+            .boxed(),
+        }
+    }
+
+    fn project_batch(
+        &self,
+        payloads: Vec<Box<dyn Any + Send>>,
+    ) -> BoxFuture<'_, Result<Vec<ProjectionResult<S>>>> {
+        let payloads = payloads
+            .into_iter()
+            .map(|payload| payload.downcast::<T>().map(|payload| *payload))
+            .collect::<Result<Vec<_>, _>>();
+        match payloads {
+            Ok(payloads) => self.projector.project_batch(payloads).boxed(),
+            Err(_) => futures::future::ready(Err(anyhow!(
+                "projection batch holds a payload that is not a `{}`",
+                type_name::<T>()
+            )))
+            .boxed(),
+        }
+    }
+
+    fn is_inline(&self, payload: &dyn Any) -> bool {
+        (payload.downcast_ref::<T>()).is_some_and(|payload| self.projector.is_inline(payload))
+    }
+
+    fn validate(&self, payload: &dyn Any) -> Result<(), ValidationError> {
+        match payload.downcast_ref::<T>() {
+            Some(payload) => self.projector.validate(payload),
+            None => Ok(()),
+        }
+    }
+
+    fn projection_id(&self, payload: &dyn Any) -> Option<ProjectionId> {
+        self.projector.projection_id(payload.downcast_ref::<T>()?)
+    }
+
+    fn depends_on(&self, payload: &dyn Any) -> Option<ProjectionId> {
+        self.projector.depends_on(payload.downcast_ref::<T>()?)
+    }
+
+    fn batch_key(&self, payload: &dyn Any) -> Option<String> {
+        // Keys are scoped to the payload type, so a batch never mixes
+        // payloads of different projectors.
+        let key = self.projector.batch_key(payload.downcast_ref::<T>()?)?;
+        Some(format!("{}/{key}", type_name::<T>()))
+    }
+}
+
+/// A [`Projector`] that routes each projection to a projector for its type.
+///
+/// Rather than one projector matching over every projection variant,
+/// `ProjectorRegistry` lets each payload type have its own projector with
+/// its own dependencies, living in its own module. The registry itself
+/// implements `Projector<P, S>`, so it can be used directly as the driver's
+/// `Projector`:
+///
+/// ```rust,ignore
+/// impl From<&MyDriver> for ProjectorRegistry<MyProjection, DriverSideEffect<MyDriver>> {
+///     fn from(driver: &MyDriver) -> Self {
+///         ProjectorRegistry::new()
+///             .register::<SearchIndexUpdate, _>(SearchProjector::from(driver))
+///             .register::<EmailNotification, _>(EmailProjector::from(driver))
+///     }
+/// }
+/// ```
+///
+/// The registry forwards every [`Projector`] hook, such as
+/// [`validate`](Projector::validate) and
+/// [`batch_key`](Projector::batch_key), to the projector registered for the
+/// projection's payload. Batch keys are scoped to the payload type.
+/// Projecting a payload with no registered projector is an error.
+pub struct ProjectorRegistry<P, S = ()> {
+    projectors: Arc<HashMap<TypeId, Arc<dyn ErasedProjector<S>>>>,
+    _projection: PhantomData<fn(P)>,
+}
+
+impl<P, S: Send + 'static> ProjectorRegistry<P, S> {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self {
+            projectors: Arc::new(HashMap::new()),
+            _projection: PhantomData,
+        }
+    }
+
+    /// Registers the projector for payloads of type `T`.
+    ///
+    /// Registering a second projector for the same type replaces the first.
+    pub fn register<T, PR>(mut self, projector: PR) -> Self
+    where
+        T: Send + 'static,
+        PR: Projector<T, S> + 'static,
+    {
+        let typed = Typed {
+            projector,
+            _payload: PhantomData::<fn(T)>,
+        };
+        Arc::make_mut(&mut self.projectors).insert(TypeId::of::<T>(), Arc::new(typed));
+        self
+    }
+
+    /// The projector registered for the given projection's payload, if any.
+    fn projector_for(&self, projection: &P) -> Option<&Arc<dyn ErasedProjector<S>>>
+    where
+        P: RoutableProjection,
+    {
+        self.projectors.get(&(*projection.payload()).type_id())
+    }
+}
+
+impl<P, S: Send + 'static> Default for ProjectorRegistry<P, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P, S> Clone for ProjectorRegistry<P, S> {
+    fn clone(&self) -> Self {
+        Self {
+            projectors: self.projectors.clone(),
+            _projection: PhantomData,
+        }
+    }
+}
+
+impl<P: RoutableProjection, S: Send + 'static> Projector<P, S> for ProjectorRegistry<P, S> {
+    fn project(&self, projection: P) -> impl Future<Output = Result<ProjectionResult<S>>> + Send {
+        let payload = projection.into_payload();
+        let projector = self.projectors.get(&(*payload).type_id()).cloned();
+        async move {
+            match projector {
+                Some(projector) => projector.project(payload).await,
+                None => Err(anyhow!(
+                    "no projector registered for this `{}` variant",
+                    type_name::<P>()
+                )),
+            }
+        }
+    }
+
+    fn is_inline(&self, projection: &P) -> bool {
+        (self.projector_for(projection))
+            .is_some_and(|projector| projector.is_inline(projection.payload()))
+    }
+
+    fn validate(&self, projection: &P) -> Result<(), ValidationError> {
+        match self.projector_for(projection) {
+            Some(projector) => projector.validate(projection.payload()),
+            None => Ok(()),
+        }
+    }
+
+    fn projection_id(&self, projection: &P) -> Option<ProjectionId> {
+        self.projector_for(projection)?
+            .projection_id(projection.payload())
+    }
+
+    fn depends_on(&self, projection: &P) -> Option<ProjectionId> {
+        self.projector_for(projection)?
+            .depends_on(projection.payload())
+    }
+
+    fn batch_key(&self, projection: &P) -> Option<String> {
+        self.projector_for(projection)?
+            .batch_key(projection.payload())
+    }
+
+    fn project_batch(
+        &self,
+        projections: Vec<P>,
+    ) -> impl Future<Output = Result<Vec<ProjectionResult<S>>>> + Send
+    where
+        P: Send,
+        S: Send,
+    {
+        // Batches share a batch key, and so a payload type.
+        let projector = (projections.first()).and_then(|projection| self.projector_for(projection));
+        let projector = projector.cloned();
+        let payloads = projections
+            .into_iter()
+            .map(RoutableProjection::into_payload)
+            .collect::<Vec<_>>();
+        async move {
+            match projector {
+                Some(projector) => projector.project_batch(payloads).await,
+                None if payloads.is_empty() => Ok(Vec::new()),
+                None => Err(anyhow!(
+                    "no projector registered for this `{}` variant",
+                    type_name::<P>()
+                )),
+            }
+        }
+    }
+}
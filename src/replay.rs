@@ -0,0 +1,33 @@
+tokio::task_local! {
+    static REPLAY: bool;
+}
+
+/// Runs `fut` in replay mode.
+///
+/// Projections applied within `fut` re-run over history, e.g. to rebuild a
+/// read model, and projectors can detect this with [`is_replay`].
+/// [`MessageBus::replay`](crate::bus::MessageBus::replay) applies its
+/// projections in replay mode, and applications rebuilding read models by
+/// other means can wrap their work with this.
+pub async fn replaying<F: Future>(fut: F) -> F::Output {
+    REPLAY.scope(true, fut).await
+}
+
+/// Whether the current task runs in replay mode.
+///
+/// Projectors use this to skip external side effects that are not
+/// idempotent while still rebuilding their read models, such as sending
+/// emails:
+///
+/// ```rust,ignore
+/// async fn project(&self, projection: MyProjection) -> Result<ProjectionResult<S>> {
+///     self.orders.upsert(&projection).await?;
+///     if !is_replay() {
+///         self.mailer.send_confirmation(&projection).await?;
+///     }
+///     Ok(ProjectionResult::default())
+/// }
+/// ```
+pub fn is_replay() -> bool {
+    REPLAY.try_with(|replay| *replay).unwrap_or(false)
+}
@@ -0,0 +1,186 @@
+use std::{
+    hash::{Hash, Hasher},
+    panic::{self, AssertUnwindSafe},
+};
+
+use anyhow::Result;
+use futures::FutureExt;
+
+use crate::{
+    driver::MessageBusDriver,
+    handler::{Command, CommandHandler, HandlerOutcome},
+};
+
+/// A version of a command handler taking part in a [`Rollout`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HandlerVersion {
+    /// The established handler.
+    Current,
+
+    /// The new handler being rolled out.
+    Candidate,
+}
+
+impl HandlerVersion {
+    /// Selects the candidate for roughly `percent` percent of keys.
+    ///
+    /// The choice is derived from a hash of `key`, so the same key (e.g. an
+    /// aggregate id) always selects the same version, and raising `percent`
+    /// only moves keys from the current version to the candidate. The hash
+    /// is a fixed FNV-1a, so instances built with different Rust versions
+    /// agree on the selection during a mixed-version deploy.
+    pub fn canary(key: impl Hash, percent: u8) -> Self {
+        let mut hasher = Fnv1a::default();
+        key.hash(&mut hasher);
+        if hasher.finish() % 100 < u64::from(percent) {
+            HandlerVersion::Candidate
+        } else {
+            HandlerVersion::Current
+        }
+    }
+}
+
+/// The 64-bit FNV-1a hash, whose output does not depend on the build.
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Chooses which version of a command handler handles a command.
+///
+/// Typically backed by a feature flag or a rollout percentage. Any
+/// `Fn(&C) -> HandlerVersion` closure is a selector:
+///
+/// ```rust,ignore
+/// let selector = |cmd: &PlaceOrder| HandlerVersion::canary(cmd.order_id, 10);
+/// ```
+pub trait HandlerSelector<C>: Clone + Send + Sync {
+    /// The version that should handle the given command.
+    fn select(&self, cmd: &C) -> HandlerVersion;
+}
+
+impl<C, F> HandlerSelector<C> for F
+where
+    F: Fn(&C) -> HandlerVersion + Clone + Send + Sync,
+{
+    fn select(&self, cmd: &C) -> HandlerVersion {
+        self(cmd)
+    }
+}
+
+/// A [`CommandHandler`] that routes each command to one of two versions of
+/// a handler.
+///
+/// `Rollout` enables canary releases of handler logic without branching
+/// inside the handler: the current and candidate implementations live side
+/// by side, and a [`HandlerSelector`] picks one for every command. It can be
+/// used directly as a driver's `Handler`:
+///
+/// ```rust,ignore
+/// impl From<&MyDriver> for Rollout<OrderHandler, OrderHandlerV2, Flag> {
+///     fn from(driver: &MyDriver) -> Self {
+///         Rollout::new(
+///             OrderHandler::from(driver),
+///             OrderHandlerV2::from(driver),
+///             driver.flags.selector("order-handler-v2"),
+///         )
+///     }
+/// }
+/// ```
+///
+/// The selected version's [`before_handle`](CommandHandler::before_handle)
+/// and [`after_handle`](CommandHandler::after_handle) hooks run around its
+/// `handle`. The [`concurrency_key`](CommandHandler::concurrency_key),
+/// [`should_queue`](CommandHandler::should_queue), and
+/// [`required_permissions`](CommandHandler::required_permissions) hooks are
+/// taken from the current version, so that they do not depend on the
+/// selection.
+#[derive(Clone, Debug)]
+pub struct Rollout<H, N, S> {
+    current: H,
+    candidate: N,
+    selector: S,
+}
+
+impl<H, N, S> Rollout<H, N, S> {
+    /// Creates a rollout from the current and candidate handlers.
+    pub fn new(current: H, candidate: N, selector: S) -> Self {
+        Self {
+            current,
+            candidate,
+            selector,
+        }
+    }
+}
+
+impl<C, D, H, N, S> CommandHandler<C, D> for Rollout<H, N, S>
+where
+    C: Command,
+    D: MessageBusDriver,
+    H: CommandHandler<C, D>,
+    N: CommandHandler<C, D>,
+    S: HandlerSelector<C>,
+{
+    async fn handle(
+        &self,
+        uow: &mut D::UnitOfWork,
+        cmd: C,
+    ) -> Result<HandlerOutcome<D::Identifier>> {
+        match self.selector.select(&cmd) {
+            HandlerVersion::Current => run(&self.current, uow, cmd).await,
+            HandlerVersion::Candidate => {
+                tracing::debug!("handling command with the candidate handler");
+                run(&self.candidate, uow, cmd).await
+            }
+        }
+    }
+
+    fn concurrency_key(&self, cmd: &C) -> Option<String> {
+        self.current.concurrency_key(cmd)
+    }
+
+    fn should_queue(&self, cmd: &C) -> bool {
+        self.current.should_queue(cmd)
+    }
+
+    fn required_permissions(&self, cmd: &C) -> Vec<String> {
+        self.current.required_permissions(cmd)
+    }
+}
+
+/// Runs a handler with its lifecycle hooks, as the message bus does.
+async fn run<C, D, H>(
+    handler: &H,
+    uow: &mut D::UnitOfWork,
+    cmd: C,
+) -> Result<HandlerOutcome<D::Identifier>>
+where
+    C: Command,
+    D: MessageBusDriver,
+    H: CommandHandler<C, D>,
+{
+    handler.before_handle(uow, &cmd).await?;
+    let handled = AssertUnwindSafe(handler.handle(uow, cmd))
+        .catch_unwind()
+        .await;
+    let released = handler.after_handle(uow).await;
+    match handled {
+        Ok(handled) => handled.and_then(|res| released.map(|()| res)),
+        Err(panic) => panic::resume_unwind(panic),
+    }
+}
@@ -0,0 +1,444 @@
+use std::{marker::PhantomData, time::Duration};
+
+use anyhow::Result;
+use futures::{
+    StreamExt, TryFutureExt,
+    future::{self, BoxFuture},
+    stream::{self, FuturesUnordered, Stream},
+};
+
+use crate::{
+    broker::{MessageBroker, NackReason, PublishError, RouteKey},
+    driver::MessageBusDriver,
+    error::ErrorDetails,
+    message::{DriverEnvelope, MessageKind},
+};
+
+/// A [`MessageBroker`] that uses a separate broker per message kind.
+///
+/// Real topologies often carry commands, events, and projections over
+/// different transports, e.g. commands through an HTTP-fronted queue and
+/// events through a Kafka topic. `SplitBroker` combines one broker per kind
+/// into a single broker for a driver:
+///
+/// ```rust,ignore
+/// impl MessageBusDriver for MyDriver {
+///     type Broker = SplitBroker<Self, CommandQueue, KafkaBroker, CommandQueue>;
+///     // ...
+/// }
+/// ```
+///
+/// Each message is published to the broker for its kind, and the message bus
+/// consumes the receivers of all three brokers concurrently. Received
+/// messages are acknowledged through the broker they came from. A driver
+/// with a single transport simply uses that broker directly.
+pub struct SplitBroker<D, C, E, P> {
+    commands: C,
+    events: E,
+    projections: P,
+    driver: PhantomData<fn() -> D>,
+}
+
+impl<D, C, E, P> SplitBroker<D, C, E, P> {
+    /// Combines the brokers for commands, events, and projections.
+    pub fn new(commands: C, events: E, projections: P) -> Self {
+        Self {
+            commands,
+            events,
+            projections,
+            driver: PhantomData,
+        }
+    }
+}
+
+impl<D, C: Clone, E: Clone, P: Clone> Clone for SplitBroker<D, C, E, P> {
+    fn clone(&self) -> Self {
+        Self::new(
+            self.commands.clone(),
+            self.events.clone(),
+            self.projections.clone(),
+        )
+    }
+}
+
+impl<D, C, E, P> From<&D> for SplitBroker<D, C, E, P>
+where
+    C: for<'a> From<&'a D>,
+    E: for<'a> From<&'a D>,
+    P: for<'a> From<&'a D>,
+{
+    fn from(driver: &D) -> Self {
+        Self::new(From::from(driver), From::from(driver), From::from(driver))
+    }
+}
+
+/// The id of a message received through a [`SplitBroker`].
+///
+/// Records which of the brokers delivered the message, so it can be
+/// acknowledged through the same broker.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SplitId<C, E, P> {
+    /// A message received from the command broker.
+    Command(C),
+
+    /// A message received from the event broker.
+    Event(E),
+
+    /// A message received from the projection broker.
+    Projection(P),
+}
+
+impl<D, C, E, P> SplitBroker<D, C, E, P>
+where
+    D: MessageBusDriver,
+    C: MessageBroker<Message = DriverEnvelope<D>>,
+    E: MessageBroker<Message = DriverEnvelope<D>>,
+    P: MessageBroker<Message = DriverEnvelope<D>>,
+{
+    /// Publishes each message through the broker for its kind, reporting
+    /// the outcome of each message in the original order.
+    async fn publish_split(
+        &self,
+        messages: Vec<DriverEnvelope<D>>,
+        idempotent: bool,
+    ) -> Vec<Result<(), PublishError>> {
+        let len = messages.len();
+        let mut parts: [(Vec<usize>, Vec<_>); 3] = Default::default();
+        for (index, message) in messages.into_iter().enumerate() {
+            let part = &mut parts[kind_index(message.message.kind())];
+            part.0.push(index);
+            part.1.push(message);
+        }
+        let [(c_idx, c), (e_idx, e), (p_idx, p)] = parts;
+
+        let (c_res, e_res, p_res) = future::join3(
+            async {
+                match (c.is_empty(), idempotent) {
+                    (true, _) => Vec::new(),
+                    (false, true) => self.commands.publish_idempotent(c).await,
+                    (false, false) => self.commands.publish_batch_detailed(c).await,
+                }
+            },
+            async {
+                match (e.is_empty(), idempotent) {
+                    (true, _) => Vec::new(),
+                    (false, true) => self.events.publish_idempotent(e).await,
+                    (false, false) => self.events.publish_batch_detailed(e).await,
+                }
+            },
+            async {
+                match (p.is_empty(), idempotent) {
+                    (true, _) => Vec::new(),
+                    (false, true) => self.projections.publish_idempotent(p).await,
+                    (false, false) => self.projections.publish_batch_detailed(p).await,
+                }
+            },
+        )
+        .await;
+
+        let mut results = vec![Ok(()); len];
+        for (indices, part) in [(c_idx, c_res), (e_idx, e_res), (p_idx, p_res)] {
+            for (index, result) in indices.into_iter().zip(part) {
+                results[index] = result;
+            }
+        }
+        results
+    }
+}
+
+impl<D, C, E, P> MessageBroker for SplitBroker<D, C, E, P>
+where
+    D: MessageBusDriver,
+    C: MessageBroker<Message = DriverEnvelope<D>>,
+    E: MessageBroker<Message = DriverEnvelope<D>>,
+    P: MessageBroker<Message = DriverEnvelope<D>>,
+{
+    type Message = DriverEnvelope<D>;
+    type Id = SplitId<C::Id, E::Id, P::Id>;
+
+    fn receiver(&self) -> impl Stream<Item = (Self::Id, Self::Message)> + Send {
+        let commands = self
+            .commands
+            .receiver()
+            .map(|(id, message)| (SplitId::Command(id), message));
+        let events = self
+            .events
+            .receiver()
+            .map(|(id, message)| (SplitId::Event(id), message));
+        let projections = self
+            .projections
+            .receiver()
+            .map(|(id, message)| (SplitId::Projection(id), message));
+        stream::select(stream::select(commands, events), projections)
+    }
+
+    /// Receives the first non-empty batch from any of the brokers.
+    ///
+    /// All three brokers are asked for a batch at once, and the calls still
+    /// pending when one of them returns messages are dropped. Each broker's
+    /// `receive_batch` must therefore not lose messages when cancelled.
+    async fn receive_batch(&self, max: usize) -> Result<Vec<(Self::Id, Self::Message)>> {
+        let mut receiving: FuturesUnordered<BoxFuture<'_, Result<Vec<_>>>> =
+            FuturesUnordered::new();
+        receiving.push(Box::pin(self.commands.receive_batch(max).map_ok(|batch| {
+            let tagged = batch
+                .into_iter()
+                .map(|(id, message)| (SplitId::Command(id), message));
+            tagged.collect()
+        })));
+        receiving.push(Box::pin(self.events.receive_batch(max).map_ok(|batch| {
+            let tagged = batch
+                .into_iter()
+                .map(|(id, message)| (SplitId::Event(id), message));
+            tagged.collect()
+        })));
+        receiving.push(Box::pin(self.projections.receive_batch(max).map_ok(
+            |batch| {
+                let tagged = batch
+                    .into_iter()
+                    .map(|(id, message)| (SplitId::Projection(id), message));
+                tagged.collect()
+            },
+        )));
+        while let Some(batch) = receiving.next().await {
+            let batch = batch?;
+            if !batch.is_empty() {
+                return Ok(batch);
+            }
+        }
+        Ok(Vec::new())
+    }
+
+    async fn publish(&self, message: Self::Message) -> Result<()> {
+        match message.message.kind() {
+            MessageKind::Command => self.commands.publish(message).await,
+            MessageKind::Event => self.events.publish(message).await,
+            MessageKind::Projection => self.projections.publish(message).await,
+        }
+    }
+
+    async fn publish_to(&self, route: &RouteKey, message: Self::Message) -> Result<()> {
+        match message.message.kind() {
+            MessageKind::Command => self.commands.publish_to(route, message).await,
+            MessageKind::Event => self.events.publish_to(route, message).await,
+            MessageKind::Projection => self.projections.publish_to(route, message).await,
+        }
+    }
+
+    async fn publish_to_idempotent(&self, route: &RouteKey, message: Self::Message) -> Result<()> {
+        match message.message.kind() {
+            MessageKind::Command => self.commands.publish_to_idempotent(route, message).await,
+            MessageKind::Event => self.events.publish_to_idempotent(route, message).await,
+            MessageKind::Projection => self.projections.publish_to_idempotent(route, message).await,
+        }
+    }
+
+    async fn publish_batch(&self, messages: Vec<Self::Message>) -> Result<()> {
+        let mut parts: [Vec<_>; 3] = Default::default();
+        for message in messages {
+            parts[kind_index(message.message.kind())].push(message);
+        }
+        let [c, e, p] = parts;
+        future::try_join3(
+            async {
+                if c.is_empty() {
+                    Ok(())
+                } else {
+                    self.commands.publish_batch(c).await
+                }
+            },
+            async {
+                if e.is_empty() {
+                    Ok(())
+                } else {
+                    self.events.publish_batch(e).await
+                }
+            },
+            async {
+                if p.is_empty() {
+                    Ok(())
+                } else {
+                    self.projections.publish_batch(p).await
+                }
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn publish_batch_detailed(
+        &self,
+        messages: Vec<Self::Message>,
+    ) -> Vec<Result<(), PublishError>> {
+        self.publish_split(messages, false).await
+    }
+
+    async fn publish_idempotent(
+        &self,
+        messages: Vec<Self::Message>,
+    ) -> Vec<Result<(), PublishError>> {
+        self.publish_split(messages, true).await
+    }
+
+    async fn ack(&self, id: Self::Id) -> Result<()> {
+        match id {
+            SplitId::Command(id) => self.commands.ack(id).await,
+            SplitId::Event(id) => self.events.ack(id).await,
+            SplitId::Projection(id) => self.projections.ack(id).await,
+        }
+    }
+
+    async fn ack_batch(&self, ids: Vec<Self::Id>) -> Result<()> {
+        let (c, e, p) = split_ids(ids);
+        future::try_join3(
+            async {
+                if c.is_empty() {
+                    Ok(())
+                } else {
+                    self.commands.ack_batch(c).await
+                }
+            },
+            async {
+                if e.is_empty() {
+                    Ok(())
+                } else {
+                    self.events.ack_batch(e).await
+                }
+            },
+            async {
+                if p.is_empty() {
+                    Ok(())
+                } else {
+                    self.projections.ack_batch(p).await
+                }
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn nack(&self, id: Self::Id) -> Result<()> {
+        match id {
+            SplitId::Command(id) => self.commands.nack(id).await,
+            SplitId::Event(id) => self.events.nack(id).await,
+            SplitId::Projection(id) => self.projections.nack(id).await,
+        }
+    }
+
+    async fn nack_with_reason(&self, id: Self::Id, reason: NackReason) -> Result<()> {
+        match id {
+            SplitId::Command(id) => self.commands.nack_with_reason(id, reason).await,
+            SplitId::Event(id) => self.events.nack_with_reason(id, reason).await,
+            SplitId::Projection(id) => self.projections.nack_with_reason(id, reason).await,
+        }
+    }
+
+    async fn nack_batch(&self, ids: Vec<Self::Id>, reason: NackReason) -> Result<()> {
+        let (c, e, p) = split_ids(ids);
+        future::try_join3(
+            async {
+                if c.is_empty() {
+                    Ok(())
+                } else {
+                    self.commands.nack_batch(c, reason.clone()).await
+                }
+            },
+            async {
+                if e.is_empty() {
+                    Ok(())
+                } else {
+                    self.events.nack_batch(e, reason.clone()).await
+                }
+            },
+            async {
+                if p.is_empty() {
+                    Ok(())
+                } else {
+                    self.projections.nack_batch(p, reason.clone()).await
+                }
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn nack_with_delay(
+        &self,
+        id: Self::Id,
+        reason: NackReason,
+        delay: Duration,
+    ) -> Result<()> {
+        match id {
+            SplitId::Command(id) => self.commands.nack_with_delay(id, reason, delay).await,
+            SplitId::Event(id) => self.events.nack_with_delay(id, reason, delay).await,
+            SplitId::Projection(id) => self.projections.nack_with_delay(id, reason, delay).await,
+        }
+    }
+
+    fn delivery_attempt(&self, id: &Self::Id) -> u32 {
+        match id {
+            SplitId::Command(id) => self.commands.delivery_attempt(id),
+            SplitId::Event(id) => self.events.delivery_attempt(id),
+            SplitId::Projection(id) => self.projections.delivery_attempt(id),
+        }
+    }
+
+    async fn dead_letter(&self, id: Self::Id) -> Result<()> {
+        match id {
+            SplitId::Command(id) => self.commands.dead_letter(id).await,
+            SplitId::Event(id) => self.events.dead_letter(id).await,
+            SplitId::Projection(id) => self.projections.dead_letter(id).await,
+        }
+    }
+
+    async fn dead_letter_with_reason(&self, id: Self::Id, details: ErrorDetails) -> Result<()> {
+        match id {
+            SplitId::Command(id) => self.commands.dead_letter_with_reason(id, details).await,
+            SplitId::Event(id) => self.events.dead_letter_with_reason(id, details).await,
+            SplitId::Projection(id) => self.projections.dead_letter_with_reason(id, details).await,
+        }
+    }
+
+    /// Peeks the command, event, and projection brokers in turn, returning
+    /// the first message found.
+    async fn peek(&self) -> Result<Option<(Self::Id, Self::Message)>> {
+        if let Some((id, message)) = self.commands.peek().await? {
+            return Ok(Some((SplitId::Command(id), message)));
+        }
+        if let Some((id, message)) = self.events.peek().await? {
+            return Ok(Some((SplitId::Event(id), message)));
+        }
+        let peeked = self.projections.peek().await?;
+        Ok(peeked.map(|(id, message)| (SplitId::Projection(id), message)))
+    }
+
+    /// Subscribes every broker to the pattern.
+    async fn subscribe(&self, pattern: &str) -> Result<()> {
+        self.commands.subscribe(pattern).await?;
+        self.events.subscribe(pattern).await?;
+        self.projections.subscribe(pattern).await
+    }
+}
+
+/// The position of a message kind's broker in a [`SplitBroker`].
+fn kind_index(kind: MessageKind) -> usize {
+    match kind {
+        MessageKind::Command => 0,
+        MessageKind::Event => 1,
+        MessageKind::Projection => 2,
+    }
+}
+
+/// Partitions the ids of a [`SplitBroker`] by the broker that delivered
+/// them.
+fn split_ids<C, E, P>(ids: Vec<SplitId<C, E, P>>) -> (Vec<C>, Vec<E>, Vec<P>) {
+    let (mut c, mut e, mut p) = (Vec::new(), Vec::new(), Vec::new());
+    for id in ids {
+        match id {
+            SplitId::Command(id) => c.push(id),
+            SplitId::Event(id) => e.push(id),
+            SplitId::Projection(id) => p.push(id),
+        }
+    }
+    (c, e, p)
+}
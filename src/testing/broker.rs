@@ -0,0 +1,399 @@
+use std::{
+    marker::PhantomData,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Result;
+use futures::{Stream, StreamExt};
+
+use crate::{
+    broker::{MessageBroker, NackReason, PublishError, RouteKey},
+    driver::MessageBusDriver,
+    error::ErrorDetails,
+    message::{DriverEnvelope, DriverMessage, Message},
+};
+
+/// A [`MessageBroker`] that records its interactions with the message bus.
+///
+/// `RecordingBroker` wraps another broker, such as
+/// [`NoOpBroker`](crate::testing::NoOpBroker) or a broker backed by a test
+/// container, and forwards every call to it. Along the way it records each
+/// successfully published envelope and each received, acknowledged,
+/// negatively acknowledged, and dead-lettered message, for tests to assert
+/// on through a [`BrokerInspector`]:
+///
+/// ```rust,ignore
+/// let broker = RecordingBroker::new(NoOpBroker);
+/// let inspector = broker.inspector();
+/// // ... run the bus with `broker` ...
+/// assert_eq!(inspector.published_events().len(), 1);
+/// assert!(inspector.nacked().is_empty());
+/// ```
+///
+/// Clones share their recording, so a test driver typically holds the
+/// broker and hands out clones of it from its `From<&Driver>` conversion.
+pub struct RecordingBroker<D: MessageBusDriver, B: MessageBroker> {
+    inner: B,
+    record: Arc<Mutex<Record<D, B::Id>>>,
+}
+
+impl<D: MessageBusDriver, B: MessageBroker> RecordingBroker<D, B> {
+    /// Wraps a broker, recording its interactions from now on.
+    pub fn new(inner: B) -> Self {
+        Self {
+            inner,
+            record: Arc::default(),
+        }
+    }
+
+    /// An inspector over the interactions recorded by this broker and its
+    /// clones.
+    pub fn inspector(&self) -> BrokerInspector<D, B> {
+        BrokerInspector {
+            record: self.record.clone(),
+            _broker: PhantomData,
+        }
+    }
+
+    fn record(&self, update: impl FnOnce(&mut Record<D, B::Id>)) {
+        update(&mut self.record.lock().unwrap());
+    }
+}
+
+impl<D: MessageBusDriver, B: MessageBroker> Clone for RecordingBroker<D, B> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            record: self.record.clone(),
+        }
+    }
+}
+
+/// Everything a [`RecordingBroker`] has seen, in order.
+struct Record<D: MessageBusDriver, Id> {
+    published: Vec<DriverEnvelope<D>>,
+    received: Vec<Id>,
+    acked: Vec<Id>,
+    nacked: Vec<(Id, Option<NackReason>)>,
+    dead_lettered: Vec<(Id, Option<ErrorDetails>)>,
+}
+
+impl<D: MessageBusDriver, Id> Default for Record<D, Id> {
+    fn default() -> Self {
+        Self {
+            published: Vec::new(),
+            received: Vec::new(),
+            acked: Vec::new(),
+            nacked: Vec::new(),
+            dead_lettered: Vec::new(),
+        }
+    }
+}
+
+/// Assertions over the interactions recorded by a [`RecordingBroker`].
+///
+/// Every accessor returns a snapshot, in the order the interactions
+/// happened.
+pub struct BrokerInspector<D: MessageBusDriver, B: MessageBroker> {
+    record: Arc<Mutex<Record<D, B::Id>>>,
+    _broker: PhantomData<fn() -> B>,
+}
+
+impl<D: MessageBusDriver, B: MessageBroker> Clone for BrokerInspector<D, B> {
+    fn clone(&self) -> Self {
+        Self {
+            record: self.record.clone(),
+            _broker: PhantomData,
+        }
+    }
+}
+
+impl<D, B> BrokerInspector<D, B>
+where
+    D: MessageBusDriver,
+    B: MessageBroker,
+    DriverEnvelope<D>: Clone,
+{
+    /// Every envelope published successfully.
+    pub fn published(&self) -> Vec<DriverEnvelope<D>> {
+        self.record.lock().unwrap().published.clone()
+    }
+
+    /// The commands of every envelope published successfully.
+    pub fn published_commands(&self) -> Vec<D::Command> {
+        self.published_matching(|message| match message {
+            Message::Command(cmd) => vec![cmd],
+            _ => Vec::new(),
+        })
+    }
+
+    /// The events of every envelope published successfully, including the
+    /// events of published batches.
+    pub fn published_events(&self) -> Vec<D::Event> {
+        self.published_matching(|message| match message {
+            Message::Event(event) => vec![event],
+            Message::EventBatch(events) => events,
+            _ => Vec::new(),
+        })
+    }
+
+    /// The projections of every envelope published successfully.
+    pub fn published_projections(&self) -> Vec<D::Projection> {
+        self.published_matching(|message| match message {
+            Message::Projection(projection) => vec![projection],
+            _ => Vec::new(),
+        })
+    }
+
+    fn published_matching<T>(&self, select: impl Fn(DriverMessage<D>) -> Vec<T>) -> Vec<T> {
+        self.published()
+            .into_iter()
+            .flat_map(|envelope| select(envelope.message))
+            .collect()
+    }
+}
+
+impl<D, B> BrokerInspector<D, B>
+where
+    D: MessageBusDriver,
+    B: MessageBroker,
+{
+    /// The ids of every message received.
+    pub fn received(&self) -> Vec<B::Id> {
+        self.record.lock().unwrap().received.clone()
+    }
+
+    /// The ids of every message acknowledged.
+    pub fn acked(&self) -> Vec<B::Id> {
+        self.record.lock().unwrap().acked.clone()
+    }
+
+    /// The ids of every message negatively acknowledged, with the reason
+    /// given by the bus, if any.
+    pub fn nacked(&self) -> Vec<(B::Id, Option<NackReason>)> {
+        self.record.lock().unwrap().nacked.clone()
+    }
+
+    /// The ids of every message dead-lettered, with the error details given
+    /// by the bus, if any.
+    pub fn dead_lettered(&self) -> Vec<(B::Id, Option<ErrorDetails>)> {
+        self.record.lock().unwrap().dead_lettered.clone()
+    }
+
+    /// The ids of the messages received but not yet settled.
+    ///
+    /// A message is settled once it is acknowledged, negatively
+    /// acknowledged, or dead-lettered.
+    pub fn in_flight(&self) -> Vec<B::Id>
+    where
+        B::Id: PartialEq,
+    {
+        let record = self.record.lock().unwrap();
+        let settled = record
+            .acked
+            .iter()
+            .chain(record.nacked.iter().map(|(id, _)| id))
+            .chain(record.dead_lettered.iter().map(|(id, _)| id));
+        let mut in_flight = record.received.clone();
+        for id in settled {
+            if let Some(index) = in_flight.iter().position(|received| received == id) {
+                in_flight.remove(index);
+            }
+        }
+        in_flight
+    }
+
+    /// Forgets every recorded interaction.
+    pub fn clear(&self) {
+        *self.record.lock().unwrap() = Record::default();
+    }
+}
+
+impl<D, B> RecordingBroker<D, B>
+where
+    D: MessageBusDriver,
+    B: MessageBroker<Message = DriverEnvelope<D>>,
+    DriverEnvelope<D>: Clone,
+{
+    /// Records the messages whose publish succeeded.
+    fn record_published(
+        &self,
+        messages: Vec<DriverEnvelope<D>>,
+        results: &[Result<(), PublishError>],
+    ) {
+        self.record(|record| {
+            let published = messages
+                .into_iter()
+                .zip(results)
+                .filter(|(_, result)| result.is_ok())
+                .map(|(message, _)| message);
+            record.published.extend(published);
+        });
+    }
+}
+
+impl<D, B> MessageBroker for RecordingBroker<D, B>
+where
+    D: MessageBusDriver,
+    B: MessageBroker<Message = DriverEnvelope<D>>,
+    DriverEnvelope<D>: Clone,
+{
+    type Message = DriverEnvelope<D>;
+    type Id = B::Id;
+
+    fn receiver(&self) -> impl Stream<Item = (Self::Id, Self::Message)> + Send {
+        let record = self.record.clone();
+        self.inner.receiver().map(move |(id, message)| {
+            record.lock().unwrap().received.push(id.clone());
+            (id, message)
+        })
+    }
+
+    async fn receive_batch(&self, max: usize) -> Result<Vec<(Self::Id, Self::Message)>> {
+        let batch = self.inner.receive_batch(max).await?;
+        self.record(|record| {
+            let ids = batch.iter().map(|(id, _)| id.clone());
+            record.received.extend(ids);
+        });
+        Ok(batch)
+    }
+
+    async fn publish(&self, message: Self::Message) -> Result<()> {
+        self.inner.publish(message.clone()).await?;
+        self.record(|record| record.published.push(message));
+        Ok(())
+    }
+
+    async fn publish_to(&self, route: &RouteKey, message: Self::Message) -> Result<()> {
+        self.inner.publish_to(route, message.clone()).await?;
+        self.record(|record| record.published.push(message));
+        Ok(())
+    }
+
+    async fn publish_to_idempotent(&self, route: &RouteKey, message: Self::Message) -> Result<()> {
+        self.inner
+            .publish_to_idempotent(route, message.clone())
+            .await?;
+        self.record(|record| record.published.push(message));
+        Ok(())
+    }
+
+    async fn publish_batch(&self, messages: Vec<Self::Message>) -> Result<()> {
+        self.inner.publish_batch(messages.clone()).await?;
+        self.record(|record| record.published.extend(messages));
+        Ok(())
+    }
+
+    async fn publish_batch_detailed(
+        &self,
+        messages: Vec<Self::Message>,
+    ) -> Vec<Result<(), PublishError>> {
+        let results = self.inner.publish_batch_detailed(messages.clone()).await;
+        self.record_published(messages, &results);
+        results
+    }
+
+    fn supports_transactions(&self) -> bool {
+        self.inner.supports_transactions()
+    }
+
+    async fn publish_transactional(&self, messages: Vec<Self::Message>) -> Result<()> {
+        self.inner.publish_transactional(messages.clone()).await?;
+        self.record(|record| record.published.extend(messages));
+        Ok(())
+    }
+
+    async fn publish_transactional_idempotent(&self, messages: Vec<Self::Message>) -> Result<()> {
+        self.inner
+            .publish_transactional_idempotent(messages.clone())
+            .await?;
+        self.record(|record| record.published.extend(messages));
+        Ok(())
+    }
+
+    async fn publish_idempotent(
+        &self,
+        messages: Vec<Self::Message>,
+    ) -> Vec<Result<(), PublishError>> {
+        let results = self.inner.publish_idempotent(messages.clone()).await;
+        self.record_published(messages, &results);
+        results
+    }
+
+    async fn ack(&self, id: Self::Id) -> Result<()> {
+        self.inner.ack(id.clone()).await?;
+        self.record(|record| record.acked.push(id));
+        Ok(())
+    }
+
+    async fn ack_batch(&self, ids: Vec<Self::Id>) -> Result<()> {
+        self.inner.ack_batch(ids.clone()).await?;
+        self.record(|record| record.acked.extend(ids));
+        Ok(())
+    }
+
+    async fn nack(&self, id: Self::Id) -> Result<()> {
+        self.inner.nack(id.clone()).await?;
+        self.record(|record| record.nacked.push((id, None)));
+        Ok(())
+    }
+
+    async fn nack_with_reason(&self, id: Self::Id, reason: NackReason) -> Result<()> {
+        self.inner
+            .nack_with_reason(id.clone(), reason.clone())
+            .await?;
+        self.record(|record| record.nacked.push((id, Some(reason))));
+        Ok(())
+    }
+
+    async fn nack_batch(&self, ids: Vec<Self::Id>, reason: NackReason) -> Result<()> {
+        self.inner.nack_batch(ids.clone(), reason.clone()).await?;
+        self.record(|record| {
+            let nacked = ids.into_iter().map(|id| (id, Some(reason.clone())));
+            record.nacked.extend(nacked);
+        });
+        Ok(())
+    }
+
+    async fn nack_with_delay(
+        &self,
+        id: Self::Id,
+        reason: NackReason,
+        delay: Duration,
+    ) -> Result<()> {
+        let nacked = self
+            .inner
+            .nack_with_delay(id.clone(), reason.clone(), delay);
+        nacked.await?;
+        self.record(|record| record.nacked.push((id, Some(reason))));
+        Ok(())
+    }
+
+    fn delivery_attempt(&self, id: &Self::Id) -> u32 {
+        self.inner.delivery_attempt(id)
+    }
+
+    async fn dead_letter(&self, id: Self::Id) -> Result<()> {
+        self.inner.dead_letter(id.clone()).await?;
+        self.record(|record| record.dead_lettered.push((id, None)));
+        Ok(())
+    }
+
+    async fn dead_letter_with_reason(&self, id: Self::Id, details: ErrorDetails) -> Result<()> {
+        let dead_lettered = self
+            .inner
+            .dead_letter_with_reason(id.clone(), details.clone());
+        dead_lettered.await?;
+        self.record(|record| record.dead_lettered.push((id, Some(details))));
+        Ok(())
+    }
+
+    async fn peek(&self) -> Result<Option<(Self::Id, Self::Message)>> {
+        self.inner.peek().await
+    }
+
+    async fn subscribe(&self, pattern: &str) -> Result<()> {
+        self.inner.subscribe(pattern).await
+    }
+}
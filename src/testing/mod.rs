@@ -0,0 +1,24 @@
+//! # Testing Utilities
+//!
+//! Drivers and components for exercising the message bus without real
+//! infrastructure. These are intended for tests, benchmarks, and as
+//! minimal reference implementations of the framework traits.
+//!
+//! This module is only available with the `test-util` feature enabled.
+//! Test modules can bring every framework trait and test utility into scope
+//! with a single glob import:
+//!
+//! ```rust,ignore
+//! use buzzard::testing::prelude::*;
+//! ```
+
+mod broker;
+mod noop;
+mod policy;
+pub mod prelude;
+mod trace;
+
+pub use broker::*;
+pub use noop::*;
+pub use policy::*;
+pub use trace::*;
@@ -0,0 +1,224 @@
+use std::{convert::Infallible, future};
+
+use anyhow::Result;
+use futures::{Stream, stream};
+
+use crate::{
+    prelude::*,
+    view::{View, Viewer},
+};
+
+/// A [`MessageBusDriver`] whose components all do nothing.
+///
+/// Every component of `NoOpDriver` returns immediately: the broker never
+/// yields messages and discards anything published, the unit of work commits
+/// no events, the handler and policy produce nothing, and the projector and
+/// viewer return empty results. All message types are `()`.
+///
+/// This makes it useful for benchmarking the overhead of the bus machinery
+/// itself (e.g. `dispatch` throughput), and as the smallest complete example
+/// of a driver.
+///
+/// ```rust,ignore
+/// let bus = MessageBus::from(&NoOpDriver);
+/// bus.dispatch(()).await?;
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoOpDriver;
+
+impl MessageBusDriver for NoOpDriver {
+    type Identifier = ();
+    type Command = ();
+    type Event = ();
+    type Projection = ();
+    type Broker = NoOpBroker;
+    type UnitOfWork = NoOpUnitOfWork;
+    type PolicyContext = NoOpPolicyContext;
+    type Projector = NoOpProjector;
+    type Handler = NoOpHandler;
+    type Policy = NoOpPolicy;
+    type Viewer = NoOpViewer;
+}
+
+/// A [`MessageBroker`] that never delivers messages and discards publishes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoOpBroker;
+
+impl From<&NoOpDriver> for NoOpBroker {
+    fn from(_: &NoOpDriver) -> Self {
+        Self
+    }
+}
+
+impl MessageBroker for NoOpBroker {
+    type Message = DriverEnvelope<NoOpDriver>;
+    type Id = ();
+
+    fn receiver(&self) -> impl Stream<Item = (Self::Id, Self::Message)> + Send {
+        stream::empty()
+    }
+
+    fn publish(&self, _message: Self::Message) -> impl Future<Output = Result<()>> + Send {
+        future::ready(Ok(()))
+    }
+
+    fn publish_batch(
+        &self,
+        _message: Vec<Self::Message>,
+    ) -> impl Future<Output = Result<()>> + Send {
+        future::ready(Ok(()))
+    }
+
+    fn ack(&self, _id: Self::Id) -> impl Future<Output = Result<()>> + Send {
+        future::ready(Ok(()))
+    }
+
+    fn nack(&self, _id: Self::Id) -> impl Future<Output = Result<()>> + Send {
+        future::ready(Ok(()))
+    }
+}
+
+/// A [`UnitOfWork`] that discards captured events and commits nothing.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoOpUnitOfWork;
+
+impl UnitOfWork for NoOpUnitOfWork {
+    type Factory = NoOpUnitOfWorkFactory;
+    type Event = ();
+
+    fn capture_event(&mut self, _event: impl Into<Self::Event>) -> Result<()> {
+        Ok(())
+    }
+
+    fn commit(self) -> impl Future<Output = Result<Vec<Self::Event>>> + Send {
+        future::ready(Ok(Vec::new()))
+    }
+
+    fn rollback(self) -> impl Future<Output = Result<()>> + Send {
+        future::ready(Ok(()))
+    }
+}
+
+/// A [`Factory`] producing [`NoOpUnitOfWork`]s.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoOpUnitOfWorkFactory;
+
+impl From<&NoOpDriver> for NoOpUnitOfWorkFactory {
+    fn from(_: &NoOpDriver) -> Self {
+        Self
+    }
+}
+
+impl Factory for NoOpUnitOfWorkFactory {
+    type Output = NoOpUnitOfWork;
+    type Error = Infallible;
+
+    fn create(&self) -> impl Future<Output = Result<Self::Output, Self::Error>> + Send {
+        future::ready(Ok(NoOpUnitOfWork))
+    }
+}
+
+/// A [`PolicyContext`] with nothing to read and nothing to close.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoOpPolicyContext;
+
+impl PolicyContext for NoOpPolicyContext {
+    type Factory = NoOpPolicyContextFactory;
+
+    fn close(self) -> impl Future<Output = Result<()>> + Send {
+        future::ready(Ok(()))
+    }
+}
+
+/// A [`Factory`] producing [`NoOpPolicyContext`]s.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoOpPolicyContextFactory;
+
+impl From<&NoOpDriver> for NoOpPolicyContextFactory {
+    fn from(_: &NoOpDriver) -> Self {
+        Self
+    }
+}
+
+impl Factory for NoOpPolicyContextFactory {
+    type Output = NoOpPolicyContext;
+    type Error = Infallible;
+
+    fn create(&self) -> impl Future<Output = Result<Self::Output, Self::Error>> + Send {
+        future::ready(Ok(NoOpPolicyContext))
+    }
+}
+
+/// A [`CommandHandler`] that accepts every command and does nothing.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoOpHandler;
+
+impl From<&NoOpDriver> for NoOpHandler {
+    fn from(_: &NoOpDriver) -> Self {
+        Self
+    }
+}
+
+impl CommandHandler<(), NoOpDriver> for NoOpHandler {
+    fn handle(
+        &self,
+        _uow: &mut NoOpUnitOfWork,
+        _cmd: (),
+    ) -> impl Future<Output = Result<HandlerOutcome<()>>> + Send {
+        future::ready(Ok(HandlerOutcome::Changed(None)))
+    }
+}
+
+/// A [`Policy`] that produces no side effects.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoOpPolicy;
+
+impl From<&NoOpDriver> for NoOpPolicy {
+    fn from(_: &NoOpDriver) -> Self {
+        Self
+    }
+}
+
+impl Policy<(), NoOpDriver> for NoOpPolicy {
+    type Output = DriverSideEffect<NoOpDriver>;
+
+    fn apply(
+        &self,
+        _ctx: &mut NoOpPolicyContext,
+        _event: (),
+    ) -> impl Future<Output = Result<impl IntoPolicyOutput<Self::Output>>> + Send {
+        future::ready(Ok(()))
+    }
+}
+
+/// A [`Projector`] that applies nothing.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoOpProjector;
+
+impl From<&NoOpDriver> for NoOpProjector {
+    fn from(_: &NoOpDriver) -> Self {
+        Self
+    }
+}
+
+impl<S: Send> Projector<(), S> for NoOpProjector {
+    fn project(&self, _projection: ()) -> impl Future<Output = Result<ProjectionResult<S>>> + Send {
+        future::ready(Ok(ProjectionResult::default()))
+    }
+}
+
+/// A [`Viewer`] that answers every `()` query with `()`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoOpViewer;
+
+impl From<&NoOpDriver> for NoOpViewer {
+    fn from(_: &NoOpDriver) -> Self {
+        Self
+    }
+}
+
+impl Viewer<()> for NoOpViewer {
+    fn view(&self, _query: ()) -> impl Future<Output = Result<impl View>> + Send {
+        future::ready(Ok(()))
+    }
+}
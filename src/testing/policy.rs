@@ -0,0 +1,150 @@
+use std::{convert::Infallible, fmt, future};
+
+use anyhow::Result;
+
+use crate::{clock::ManualClock, prelude::*};
+
+/// A [`PolicyContext`] serving user-seeded read data.
+///
+/// `FakePolicyContext` stands in for a real context when unit-testing a
+/// [`Policy`]. Instead of querying a database, the policy reads the canned
+/// `data` the test seeded it with. Policies under test typically read
+/// through a trait that both the real context and `T` implement.
+///
+/// Use it as the `PolicyContext` of a test driver, together with a
+/// [`PolicyTester`].
+#[derive(Clone, Debug, Default)]
+pub struct FakePolicyContext<T> {
+    data: T,
+    clock: ManualClock,
+}
+
+impl<T> FakePolicyContext<T> {
+    /// Creates a context serving the given data.
+    pub fn new(data: T) -> Self {
+        Self {
+            data,
+            clock: ManualClock::default(),
+        }
+    }
+
+    /// Sets the clock reported by [`PolicyContext::clock`].
+    pub fn with_clock(mut self, clock: ManualClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// The seeded data.
+    pub fn data(&self) -> &T {
+        &self.data
+    }
+}
+
+impl<T: Clone + Send + Sync + 'static> PolicyContext for FakePolicyContext<T> {
+    type Factory = FakePolicyContextFactory<T>;
+
+    fn close(self) -> impl Future<Output = Result<()>> + Send {
+        future::ready(Ok(()))
+    }
+
+    fn clock(&self) -> &dyn Clock {
+        &self.clock
+    }
+}
+
+/// A [`Factory`] producing copies of a seeded [`FakePolicyContext`].
+///
+/// When built from a driver, the factory serves `T::default()`.
+#[derive(Clone, Debug, Default)]
+pub struct FakePolicyContextFactory<T> {
+    context: FakePolicyContext<T>,
+}
+
+impl<T> FakePolicyContextFactory<T> {
+    /// Creates a factory producing copies of `context`.
+    pub fn new(context: FakePolicyContext<T>) -> Self {
+        Self { context }
+    }
+}
+
+impl<D: MessageBusDriver, T: Default> From<&D> for FakePolicyContextFactory<T> {
+    fn from(_: &D) -> Self {
+        Self::new(FakePolicyContext::new(T::default()))
+    }
+}
+
+impl<T: Clone + Send + Sync + 'static> Factory for FakePolicyContextFactory<T> {
+    type Output = FakePolicyContext<T>;
+    type Error = Infallible;
+
+    fn create(&self) -> impl Future<Output = Result<Self::Output, Self::Error>> + Send {
+        future::ready(Ok(self.context.clone()))
+    }
+}
+
+/// Runs a driver's [`Policy`] in isolation.
+///
+/// `PolicyTester` applies the policy to a single event the way the message
+/// bus would: events the policy does not subscribe to produce nothing, and
+/// otherwise a context is created, the policy applied, and the context
+/// closed. The side effects are returned for assertion instead of being
+/// published. The driver's enricher is not run.
+///
+/// ```rust,ignore
+/// let contexts = FakePolicyContextFactory::new(FakePolicyContext::new(orders));
+/// let tester = PolicyTester::<TestDriver>::new(OrderPolicy, contexts);
+///
+/// let side_effects = tester.apply(OrderPaid { order_id: 7 }.into()).await?;
+/// assert!(matches!(side_effects[..], [SideEffect::Command(MyCommand::ShipOrder(_))]));
+///
+/// tester.expect_no_side_effects(OrderViewed { order_id: 7 }.into()).await?;
+/// ```
+pub struct PolicyTester<D: MessageBusDriver> {
+    policy: D::Policy,
+    contexts: <D::PolicyContext as PolicyContext>::Factory,
+}
+
+impl<D: MessageBusDriver> PolicyTester<D> {
+    /// Creates a tester for `policy`, creating contexts with `contexts`.
+    pub fn new(policy: D::Policy, contexts: <D::PolicyContext as PolicyContext>::Factory) -> Self {
+        Self { policy, contexts }
+    }
+
+    /// Applies the policy to `event` and returns its side effects.
+    pub async fn apply(&self, event: D::Event) -> Result<Vec<DriverSideEffect<D>>> {
+        if !self.policy.subscribes_to(&event) {
+            return Ok(Vec::new());
+        }
+        let mut ctx = self.contexts.create().await.map_err(|err| {
+            let err: BusError = err.into();
+            err.into_anyhow()
+        })?;
+        let applied = self
+            .policy
+            .apply(&mut ctx, event)
+            .await
+            .map(IntoPolicyOutput::into_policy_output);
+        ctx.close().await?;
+        applied
+    }
+    /// Applies the policy to `event` and asserts that it produced no side
+    /// effects.
+    ///
+    /// Use this in negative tests, e.g. to prove that an irrelevant event is
+    /// ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics, listing the side effects, if the policy produced any.
+    pub async fn expect_no_side_effects(&self, event: D::Event) -> Result<()>
+    where
+        DriverSideEffect<D>: fmt::Debug,
+    {
+        let side_effects = self.apply(event).await?;
+        assert!(
+            side_effects.is_empty(),
+            "expected no side effects, but the policy produced {side_effects:#?}"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,2 @@
+pub use crate::prelude::*;
+pub use crate::testing::*;
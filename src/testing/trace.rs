@@ -0,0 +1,51 @@
+use std::sync::{Arc, Mutex};
+
+use crate::trace::{TraceEntry, TraceSink};
+
+/// A [`TraceSink`] that keeps every entry in memory.
+///
+/// Clones share the same entries, so a test can keep one handle while the
+/// message bus records to another:
+///
+/// ```rust,ignore
+/// let sink = InMemoryTraceSink::default();
+/// let config = BusConfig::default().with_trace_sink(sink.clone());
+/// // ... run the bus ...
+/// let flow = sink.correlated(&root_id);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct InMemoryTraceSink {
+    entries: Arc<Mutex<Vec<TraceEntry>>>,
+}
+
+impl InMemoryTraceSink {
+    /// Every entry recorded so far, in recording order.
+    pub fn entries(&self) -> Vec<TraceEntry> {
+        self.entries.lock().unwrap().clone()
+    }
+
+    /// The entries of the flow started by the given message.
+    pub fn correlated(&self, correlation_id: &str) -> Vec<TraceEntry> {
+        self.filter(|entry| entry.correlation_id == correlation_id)
+    }
+
+    /// The entries directly caused by the given message.
+    pub fn caused_by(&self, message_id: &str) -> Vec<TraceEntry> {
+        self.filter(|entry| entry.causation_id.as_deref() == Some(message_id))
+    }
+
+    fn filter(&self, predicate: impl Fn(&TraceEntry) -> bool) -> Vec<TraceEntry> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .iter()
+            .filter(|entry| predicate(entry))
+            .cloned()
+            .collect()
+    }
+}
+
+impl TraceSink for InMemoryTraceSink {
+    fn record(&self, entry: TraceEntry) {
+        self.entries.lock().unwrap().push(entry);
+    }
+}
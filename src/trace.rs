@@ -0,0 +1,165 @@
+use std::{collections::BTreeMap, fmt, sync::Mutex};
+
+use tracing::{Instrument, Span, field::Empty};
+
+use crate::{id::MessageId, message::MessageKind};
+
+/// A record of one message processed by the message bus.
+///
+/// Entries sharing a `correlation_id` belong to the same flow, and each
+/// entry's `causation_id` names the message that caused it. Together they
+/// describe the causation tree of a flow, e.g. "command A caused events B
+/// and C; event C caused command D".
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceEntry {
+    /// The id of the processed message.
+    pub message_id: MessageId,
+
+    /// The id of the message that caused this one, or `None` for a message
+    /// dispatched directly by the application.
+    pub causation_id: Option<MessageId>,
+
+    /// The id of the message that started the flow.
+    pub correlation_id: MessageId,
+
+    /// The kind of the processed message.
+    pub kind: MessageKind,
+
+    /// The type name of the processed message.
+    pub type_name: &'static str,
+}
+
+/// A destination for [`TraceEntry`] records.
+///
+/// The message bus records an entry for every command dispatched by the
+/// application and every message it receives, before handling it. Assemble
+/// the entries of one correlation id to reconstruct the causation tree of
+/// that flow.
+///
+/// Like [`Metrics`](crate::metrics::Metrics), `record` is called inline on
+/// the bus's hot path and must not block. A sink is configured via
+/// [`BusConfig::with_trace_sink`](crate::config::BusConfig::with_trace_sink).
+pub trait TraceSink: Send + Sync {
+    /// Records that a message is being processed.
+    fn record(&self, entry: TraceEntry);
+}
+
+/// The message currently being processed by a task.
+#[derive(Clone, Debug)]
+pub(crate) struct TraceContext {
+    pub message_id: MessageId,
+    pub correlation_id: MessageId,
+
+    /// Whether the message is an event whose inline projections were
+    /// already applied (see [`Envelope::projected_inline`]).
+    ///
+    /// [`Envelope::projected_inline`]: crate::message::Envelope::projected_inline
+    pub projected_inline: bool,
+}
+
+tokio::task_local! {
+    static TRACE: TraceContext;
+}
+
+impl TraceContext {
+    /// The context of the current task, if it is processing a message.
+    pub fn current() -> Option<Self> {
+        TRACE.try_with(Clone::clone).ok()
+    }
+
+    /// Runs `fut` as the processing of this context's message.
+    pub async fn scope<F: Future>(self, fut: F) -> F::Output {
+        TRACE.scope(self, fut).await
+    }
+}
+
+/// The tracing span opened by the message bus for the message a task is
+/// processing, with the attributes recorded on it so far.
+struct SpanAttributes {
+    span: Span,
+    attributes: Mutex<BTreeMap<String, String>>,
+}
+
+tokio::task_local! {
+    static SPAN: SpanAttributes;
+}
+
+/// The span the message bus opens around a message received from the
+/// broker.
+///
+/// Besides identifying the message, the span declares an empty
+/// `aggregate_id` field and an `attributes` field for [`record_attr`].
+pub(crate) fn message_span(kind: MessageKind, type_name: &'static str, ctx: &TraceContext) -> Span {
+    tracing::info_span!(
+        "message",
+        %kind,
+        message_type = type_name,
+        message_id = %ctx.message_id,
+        correlation_id = %ctx.correlation_id,
+        aggregate_id = Empty,
+        attributes = Empty,
+    )
+}
+
+/// The span the message bus opens around a command dispatched by the
+/// application, with the same fields as a [`message_span`].
+pub(crate) fn dispatch_span(type_name: &'static str, ctx: &TraceContext) -> Span {
+    tracing::info_span!(
+        "dispatch",
+        message_type = type_name,
+        message_id = %ctx.message_id,
+        correlation_id = %ctx.correlation_id,
+        aggregate_id = Empty,
+        attributes = Empty,
+    )
+}
+
+/// Runs `fut` within `span`, making it the target of [`record_attr`].
+pub(crate) async fn in_span<F: Future>(span: Span, fut: F) -> F::Output {
+    let attributes = SpanAttributes {
+        span: span.clone(),
+        attributes: Mutex::default(),
+    };
+    SPAN.scope(attributes, fut.instrument(span)).await
+}
+
+/// Records an attribute on the span the message bus opened for the
+/// message being processed.
+///
+/// Command handlers, policies, and projectors run within a span opened by
+/// the bus, named `dispatch` for commands dispatched by the application and
+/// `message` for messages received from the broker. Domain code can enrich
+/// that span without managing spans itself:
+///
+/// ```rust,ignore
+/// async fn handle(&self, uow: &mut MyUnitOfWork, cmd: Deposit) -> Result<HandlerOutcome<Uuid>> {
+///     record_attr("aggregate_id", cmd.account_id);
+///     record_attr("amount", cmd.amount);
+///     // ...
+/// }
+/// ```
+///
+/// Tracing spans only accept the fields they declared when opened. The
+/// bus's span declares `aggregate_id`, which is recorded as its own field;
+/// every other attribute, including one named like another of the span's
+/// fields, is collected into the span's `attributes` field as `key=value`
+/// pairs. Recording a key again replaces its value. Outside
+/// a bus-managed span, this does nothing.
+pub fn record_attr(key: &str, value: impl fmt::Display) {
+    let _ = SPAN.try_with(|current| {
+        let value = value.to_string();
+        // The bus's own fields, such as `message_id`, cannot be overwritten.
+        if key == "aggregate_id" {
+            current.span.record(key, value.as_str());
+            return;
+        }
+        let mut attributes = current.attributes.lock().unwrap();
+        attributes.insert(key.to_owned(), value);
+        let joined = attributes
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        current.span.record("attributes", joined.as_str());
+    });
+}
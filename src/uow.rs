@@ -1,4 +1,7 @@
-use crate::factory::Factory;
+use crate::{
+    clock::{Clock, SystemClock},
+    factory::Factory,
+};
 use anyhow::Result;
 
 /// A transactional boundary for domain mutation.
@@ -11,6 +14,32 @@ use anyhow::Result;
 /// Any events produced during the unit of work should be captured using
 /// `capture_event`. These events will be published only if the unit of
 /// work is successfully committed.
+///
+/// # Commands spanning multiple aggregates
+///
+/// A single command may load and mutate several aggregates within one unit
+/// of work, e.g. merging two accounts. The unit of work is the transaction
+/// boundary: every aggregate it touches is committed or rolled back
+/// together, so implementations whose aggregates live in different stores
+/// or partitions must coordinate the commit across them. The handler
+/// captures each aggregate's events in the order they should be observed:
+///
+/// ```rust,ignore
+/// async fn handle(&self, uow: &mut MyUnitOfWork, cmd: MergeAccounts) -> Result<Option<Id>> {
+///     let mut source = uow.accounts().load(cmd.source).await?;
+///     let mut target = uow.accounts().load(cmd.target).await?;
+///     target.absorb(&mut source)?;
+///     source.close()?;
+///
+///     uow.capture_events(target.take_events())?;
+///     uow.capture_events(source.take_events())?;
+///     Ok(Some(cmd.target))
+/// }
+/// ```
+///
+/// All captured events are published together after the commit, stamped
+/// with their capture order (see
+/// [`Envelope::sequence`](crate::message::Envelope::sequence)).
 pub trait UnitOfWork: Send {
     /// A factory used to produce new `UnitOfWork` instances.
     ///
@@ -33,9 +62,25 @@ pub trait UnitOfWork: Send {
     ///
     /// This method must never publish the event directly — events are only
     /// published by the message bus after a successful commit.
+    ///
+    /// Implementations must return the captured events from `commit` in the
+    /// order they were captured, regardless of which aggregate emitted them.
     // TODO: This shouldn't be allowed to throw an error.
     fn capture_event(&mut self, event: impl Into<Self::Event>) -> Result<()>;
 
+    /// Capture several events, in order.
+    ///
+    /// A convenience for capturing all pending events of an aggregate at
+    /// once. Stops at the first event that fails to be captured.
+    fn capture_events<E: Into<Self::Event>>(
+        &mut self,
+        events: impl IntoIterator<Item = E>,
+    ) -> Result<()> {
+        events
+            .into_iter()
+            .try_for_each(|event| self.capture_event(event))
+    }
+
     /// Commit all changes made within the unit of work.
     ///
     /// This is called once command handling is complete and no errors occurred.
@@ -55,4 +100,28 @@ pub trait UnitOfWork: Send {
     /// This function must leave the system in the same state as before the
     /// unit of work was created.
     fn rollback(self) -> impl Future<Output = Result<()>> + Send;
+
+    /// The identifier of a sealed aggregate loaded by this unit of work.
+    ///
+    /// Aggregates in a terminal state (e.g. a closed account) accept no
+    /// further commands. Implementations should record when such an
+    /// aggregate is loaded and report it here. After the command handler
+    /// runs, the message bus checks this hook and, if an aggregate is
+    /// sealed, rolls back the unit of work and rejects the command with
+    /// [`BusError::AggregateSealed`](crate::error::BusError::AggregateSealed),
+    /// regardless of what the handler returned.
+    ///
+    /// Defaults to `None`, meaning no aggregate is ever sealed.
+    fn sealed(&self) -> Option<&str> {
+        None
+    }
+
+    /// The clock available to command handlers during this unit of work.
+    ///
+    /// Defaults to the real [`SystemClock`]. Override this to inject a
+    /// deterministic clock (such as [`ManualClock`](crate::clock::ManualClock))
+    /// when testing time-dependent domain logic.
+    fn clock(&self) -> &dyn Clock {
+        &SystemClock
+    }
 }
@@ -1,5 +1,10 @@
+use std::{
+    fmt,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
 use anyhow::Result;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
 
 pub trait Query: for<'de> Deserialize<'de> + Send + Sync {}
 impl<T: for<'de> Deserialize<'de> + Send + Sync> Query for T {}
@@ -7,6 +12,180 @@ impl<T: for<'de> Deserialize<'de> + Send + Sync> Query for T {}
 pub trait View: Serialize {}
 impl<T: Serialize> View for T {}
 
+/// Hashes the content of events, to recognize duplicates under
+/// [`DedupBy::ContentHash`].
+///
+/// Events with the same logical content must hash alike, whatever their
+/// envelope. A [`ContentType`] is a content hasher for serializable events,
+/// hashing their serialization with [`ContentType::content_hash`]. A hasher
+/// is supplied by [`MessageBusDriver::content_hasher`].
+///
+/// [`DedupBy::ContentHash`]: crate::config::DedupBy::ContentHash
+/// [`MessageBusDriver::content_hasher`]: crate::driver::MessageBusDriver::content_hasher
+pub trait ContentHasher<E>: Send + Sync {
+    /// A hash of the given event's content, if it can be computed.
+    ///
+    /// Events without a hash are never recognized as duplicates.
+    fn hash(&self, event: &E) -> Option<u64>;
+}
+
+impl<E: Serialize> ContentHasher<E> for ContentType {
+    fn hash(&self, event: &E) -> Option<u64> {
+        self.content_hash(event).ok()
+    }
+}
+
 pub trait Viewer<Q: Query> {
     fn view(&self, query: Q) -> impl Future<Output = Result<impl View>> + Send;
 }
+
+/// A fallback for queries whose viewer fails.
+///
+/// Implemented by a driver's `Viewer` alongside [`Viewer<Q>`] for queries
+/// that can degrade gracefully, such as dashboards that may be served from
+/// a cache while the read replica is down. Consulted by
+/// [`MessageBus::view_with_fallback`](crate::bus::MessageBus::view_with_fallback)
+/// when [`Viewer::view`] returns an error:
+///
+/// ```rust,ignore
+/// impl ViewerFallback<DashboardQuery> for MyViewer {
+///     fn fallback(&self, query: DashboardQuery) -> impl Future<Output = Option<impl View>> + Send {
+///         async move { self.cache.get_dashboard(&query).await }
+///     }
+/// }
+/// ```
+pub trait ViewerFallback<Q: Query>: Viewer<Q> {
+    /// A stale view to serve in place of the failed one, if any.
+    ///
+    /// The viewer's error is logged before the fallback is consulted.
+    /// Returning `None` fails the query with
+    /// [`BusError::ReadUnavailable`](crate::error::BusError::ReadUnavailable).
+    fn fallback(&self, query: Q) -> impl Future<Output = Option<impl View>> + Send;
+}
+
+/// A view served either by the viewer or by its fallback.
+///
+/// Returned by
+/// [`MessageBus::view_with_fallback`](crate::bus::MessageBus::view_with_fallback).
+/// Serializes as the inner view, so callers that do not care whether the
+/// data is stale can treat it like any other view.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Served<V, F> {
+    /// The up-to-date view returned by the viewer.
+    Fresh(V),
+
+    /// The possibly stale view returned by the fallback.
+    Stale(F),
+}
+
+impl<V, F> Served<V, F> {
+    /// Whether the view was served by the fallback.
+    pub fn is_stale(&self) -> bool {
+        matches!(self, Served::Stale(_))
+    }
+}
+
+impl<V: Serialize, F: Serialize> Serialize for Served<V, F> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Served::Fresh(view) => view.serialize(serializer),
+            Served::Stale(view) => view.serialize(serializer),
+        }
+    }
+}
+
+/// A serialization format for a [`View`].
+///
+/// Used with [`MessageBus::view_as`](crate::bus::MessageBus::view_as) to
+/// render the same view in whichever format a client asked for. Each format
+/// is enabled by the feature of the same name: `json`, `msgpack`, or `cbor`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ContentType {
+    /// JSON (`application/json`).
+    #[cfg(feature = "json")]
+    Json,
+
+    /// MessagePack (`application/msgpack`).
+    #[cfg(feature = "msgpack")]
+    MsgPack,
+
+    /// CBOR (`application/cbor`).
+    #[cfg(feature = "cbor")]
+    Cbor,
+}
+
+impl ContentType {
+    /// Finds the enabled format for a media type, such as an entry of an
+    /// `Accept` header.
+    ///
+    /// Parameters (e.g. `; charset=utf-8`) and case are ignored. Returns
+    /// `None` if the media type is unknown or its feature is disabled.
+    pub fn from_mime(mime: &str) -> Option<Self> {
+        let essence = mime.split(';').next().unwrap_or_default().trim();
+        Self::all()
+            .iter()
+            .copied()
+            .find(|content_type| content_type.mime().eq_ignore_ascii_case(essence))
+    }
+
+    /// The media type of this format.
+    pub fn mime(&self) -> &'static str {
+        match *self {
+            #[cfg(feature = "json")]
+            Self::Json => "application/json",
+            #[cfg(feature = "msgpack")]
+            Self::MsgPack => "application/msgpack",
+            #[cfg(feature = "cbor")]
+            Self::Cbor => "application/cbor",
+        }
+    }
+
+    /// Serializes a view in this format.
+    #[cfg_attr(
+        not(any(feature = "json", feature = "msgpack", feature = "cbor")),
+        allow(unused_variables)
+    )]
+    pub fn serialize(&self, view: &impl View) -> Result<Vec<u8>> {
+        match *self {
+            #[cfg(feature = "json")]
+            Self::Json => Ok(serde_json::to_vec(view)?),
+            #[cfg(feature = "msgpack")]
+            Self::MsgPack => Ok(rmp_serde::to_vec_named(view)?),
+            #[cfg(feature = "cbor")]
+            Self::Cbor => {
+                let mut buf = Vec::new();
+                ciborium::into_writer(view, &mut buf)?;
+                Ok(buf)
+            }
+        }
+    }
+
+    /// A hash of a value's serialization in this format.
+    ///
+    /// Values that serialize identically hash alike. The hash is stable
+    /// within a build, but may change between Rust versions.
+    pub fn content_hash(&self, value: &impl View) -> Result<u64> {
+        let mut hasher = DefaultHasher::new();
+        self.serialize(value)?.hash(&mut hasher);
+        Ok(hasher.finish())
+    }
+
+    /// Every format enabled in this build.
+    fn all() -> &'static [Self] {
+        &[
+            #[cfg(feature = "json")]
+            Self::Json,
+            #[cfg(feature = "msgpack")]
+            Self::MsgPack,
+            #[cfg(feature = "cbor")]
+            Self::Cbor,
+        ]
+    }
+}
+
+impl fmt::Display for ContentType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.mime())
+    }
+}
@@ -0,0 +1,189 @@
+#![cfg(feature = "test-util")]
+
+use std::{
+    collections::VecDeque,
+    future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Result;
+use buzzard::testing::prelude::*;
+use futures::{Stream, future::BoxFuture, stream};
+
+/// Messages awaiting delivery, with their broker ids.
+type Queue = Arc<Mutex<VecDeque<(u32, DriverEnvelope<TestDriver>)>>>;
+
+/// A broker delivering the messages queued in it, once each.
+#[derive(Clone, Default)]
+struct QueueBroker {
+    queue: Queue,
+}
+
+impl MessageBroker for QueueBroker {
+    type Message = DriverEnvelope<TestDriver>;
+    type Id = u32;
+
+    fn receiver(&self) -> impl Stream<Item = (Self::Id, Self::Message)> + Send {
+        let queued: Vec<_> = self.queue.lock().unwrap().drain(..).collect();
+        stream::iter(queued)
+    }
+
+    fn publish(&self, _message: Self::Message) -> impl Future<Output = Result<()>> + Send {
+        future::ready(Ok(()))
+    }
+
+    fn publish_batch(
+        &self,
+        _message: Vec<Self::Message>,
+    ) -> impl Future<Output = Result<()>> + Send {
+        future::ready(Ok(()))
+    }
+
+    fn ack(&self, _id: Self::Id) -> impl Future<Output = Result<()>> + Send {
+        future::ready(Ok(()))
+    }
+
+    fn nack(&self, _id: Self::Id) -> impl Future<Output = Result<()>> + Send {
+        future::ready(Ok(()))
+    }
+}
+
+/// A coordinator under which another instance has claimed `taken`.
+struct TakenCoordinator {
+    taken: MessageId,
+}
+
+impl Coordinator for TakenCoordinator {
+    fn claim(&self, message_id: MessageId, _lease: Duration) -> BoxFuture<'_, Result<bool>> {
+        Box::pin(future::ready(Ok(message_id != self.taken)))
+    }
+
+    fn renew(&self, _message_id: MessageId, _lease: Duration) -> BoxFuture<'_, Result<()>> {
+        Box::pin(future::ready(Ok(())))
+    }
+
+    fn release(&self, _message_id: MessageId, _completed: bool) -> BoxFuture<'_, Result<()>> {
+        Box::pin(future::ready(Ok(())))
+    }
+}
+
+#[derive(Clone)]
+struct TestDriver {
+    broker: RecordingBroker<TestDriver, QueueBroker>,
+}
+
+impl MessageBusDriver for TestDriver {
+    type Identifier = ();
+    type Command = ();
+    type Event = ();
+    type Projection = ();
+    type Broker = RecordingBroker<TestDriver, QueueBroker>;
+    type UnitOfWork = NoOpUnitOfWork;
+    type PolicyContext = NoOpPolicyContext;
+    type Projector = NoOpProjector;
+    type Handler = TestHandler;
+    type Policy = TestPolicy;
+    type Viewer = NoOpViewer;
+
+    fn config(&self) -> BusConfig {
+        let coordinator = TakenCoordinator {
+            taken: "taken".into(),
+        };
+        BusConfig::default()
+            .with_atomic_receive_batching(2, 1)
+            .with_coordinator(coordinator)
+    }
+}
+
+impl From<&TestDriver> for RecordingBroker<TestDriver, QueueBroker> {
+    fn from(driver: &TestDriver) -> Self {
+        driver.broker.clone()
+    }
+}
+
+impl From<&TestDriver> for NoOpUnitOfWorkFactory {
+    fn from(_: &TestDriver) -> Self {
+        Self
+    }
+}
+
+impl From<&TestDriver> for NoOpPolicyContextFactory {
+    fn from(_: &TestDriver) -> Self {
+        Self
+    }
+}
+
+impl From<&TestDriver> for NoOpProjector {
+    fn from(_: &TestDriver) -> Self {
+        Self
+    }
+}
+
+impl From<&TestDriver> for NoOpViewer {
+    fn from(_: &TestDriver) -> Self {
+        Self
+    }
+}
+
+#[derive(Clone)]
+struct TestHandler;
+
+impl From<&TestDriver> for TestHandler {
+    fn from(_: &TestDriver) -> Self {
+        Self
+    }
+}
+
+impl CommandHandler<(), TestDriver> for TestHandler {
+    fn handle(
+        &self,
+        _uow: &mut NoOpUnitOfWork,
+        _cmd: (),
+    ) -> impl Future<Output = Result<HandlerOutcome<()>>> + Send {
+        future::ready(Ok(HandlerOutcome::Changed(None)))
+    }
+}
+
+#[derive(Clone)]
+struct TestPolicy;
+
+impl From<&TestDriver> for TestPolicy {
+    fn from(_: &TestDriver) -> Self {
+        Self
+    }
+}
+
+impl Policy<(), TestDriver> for TestPolicy {
+    type Output = DriverSideEffect<TestDriver>;
+
+    fn apply(
+        &self,
+        _ctx: &mut NoOpPolicyContext,
+        _event: (),
+    ) -> impl Future<Output = Result<impl IntoPolicyOutput<Self::Output>>> + Send {
+        future::ready(Ok(()))
+    }
+}
+
+#[test]
+fn atomic_batch_acks_skipped_members_with_the_batch() -> Result<()> {
+    let queue = QueueBroker::default();
+    for (id, message_id) in [(1, "handled"), (2, "taken")] {
+        let envelope = Envelope::new(Message::Command(())).with_id(message_id);
+        queue.queue.lock().unwrap().push_back((id, envelope));
+    }
+    let broker = RecordingBroker::new(queue);
+    let inspector = broker.inspector();
+    let bus = MessageBus::from(&TestDriver { broker });
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()?;
+    let summary = runtime.block_on(bus.start_bounded(2))?;
+
+    assert_eq!(inspector.acked(), vec![1, 2]);
+    assert!(inspector.nacked().is_empty());
+    assert_eq!((summary.acked, summary.skipped), (1, 1));
+    Ok(())
+}